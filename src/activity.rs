@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{summary::preview, Block};
+
+/// A short summary of a block for dashboards, see `Chain::recent_activity`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActivitySummary {
+    /// The ID of the block.
+    pub height: u64,
+    /// The short hash of the block, see `Block::short_hash`.
+    pub hash: String,
+    /// The data of the block, truncated to `ActivitySummary::DATA_PREVIEW` chars.
+    pub data: String,
+    /// When the block was created, in Unix seconds, see `Block::timestamp`.
+    pub timestamp: u64,
+    /// How many seconds passed since the previous block was created, if both have a timestamp.
+    pub since_previous: Option<u64>,
+}
+
+impl ActivitySummary {
+    /// The maximum number of chars of block data kept in the summary.
+    pub const DATA_PREVIEW: usize = 32;
+}
+
+impl From<&Block> for ActivitySummary {
+    fn from(block: &Block) -> Self {
+        Self {
+            height: block.id,
+            hash: block.short_hash(),
+            data: preview(&block.data, Self::DATA_PREVIEW),
+            timestamp: block.timestamp,
+            since_previous: None,
+        }
+    }
+}
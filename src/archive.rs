@@ -0,0 +1,36 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::Block;
+
+/// Returns the path of the archive file for the block with the given id.
+///
+/// Files are named by zero-padded height, so they are sorted in the order of blocks.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+///
+/// use rustychain::archive_path;
+///
+/// assert_eq!(archive_path(Path::new("archive"), 7), Path::new("archive/0000000007.json"));
+/// ```
+pub fn archive_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("{:010}.json", id))
+}
+
+/// Writes the block as JSON to its own file in the archive directory.
+///
+/// The block is written to a temporary file first and then renamed, so readers never see
+/// a partially written block. A block with the same id replaces the previous one.
+pub fn archive_block(dir: &Path, block: &Block) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = archive_path(dir, block.id);
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(block)?)?;
+    fs::rename(&tmp, &path)?;
+    Ok(path)
+}
@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+/// Exponential backoff with jitter for retries.
+///
+/// Every delay doubles the previous one up to the maximum, and a random part of up to a half
+/// of it is cut off, so nodes which started retrying at the same time spread out. Until the
+/// maximum, the delays never decrease, as the shortest next delay equals the longest previous one.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use rustychain::Backoff;
+///
+/// let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(4));
+/// let first = backoff.next_delay();
+/// assert!(first >= Duration::from_millis(500) && first <= Duration::from_secs(1));
+/// assert!(backoff.next_delay() >= first);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Backoff {
+    /// The delay before the first retry, without jitter.
+    base: Duration,
+    /// The maximum delay, without jitter.
+    max: Duration,
+    /// The number of delays returned so far.
+    attempt: u32,
+}
+
+impl Backoff {
+    /// Constructs a new `Backoff` starting from `base` and capped at `max`.
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max,
+            attempt: 0,
+        }
+    }
+
+    /// Returns the delay before the next retry.
+    pub fn next_delay(&mut self) -> Duration {
+        let factor = 2u32.saturating_pow(self.attempt);
+        self.attempt = self.attempt.saturating_add(1);
+        let delay = self.base.saturating_mul(factor).min(self.max);
+        delay / 2 + delay.mul_f64(rand::random::<f64>() / 2.0)
+    }
+
+    /// Starts over from the base delay.
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
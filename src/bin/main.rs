@@ -1,22 +1,27 @@
 use std::{
-    collections::hash_map::DefaultHasher,
+    collections::{HashMap, HashSet},
     error::Error,
-    hash::{Hash, Hasher},
-    time::Duration,
+    net::SocketAddr,
+    path::PathBuf,
+    time::{Duration, Instant},
 };
 
 use async_std::io;
 
-use clap::{arg, Parser};
+use clap::{Parser, Subcommand};
 use futures::{prelude::*, StreamExt};
 use libp2p::{
     gossipsub, identity, mdns,
     swarm::{NetworkBehaviour, SwarmBuilder, SwarmEvent},
-    PeerId,
+    Multiaddr, PeerId,
 };
-use log::{error, info};
+use log::{error, info, warn, LevelFilter};
 
-use rustychain::{Block, Chain, Message, Node};
+use rustychain::{
+    calibrate_difficulty, describe_difficulty, encode_json, expected_attempts, parse_difficulty,
+    serve_health, Block, Chain, Command, DbFormat, DifficultyMode, HashAlgo, MemoryLimits, Message,
+    MessageLog, Node, NodeConfig, NodeEvent, PeerBook, StallPolicy, ValidationPolicy,
+};
 use tokio::{
     sync::{
         broadcast,
@@ -25,35 +30,267 @@ use tokio::{
     task::{self},
 };
 
-#[derive(NetworkBehaviour)]
-struct MyBehaviour {
-    gossipsub: gossipsub::Behaviour,
-    mdns: mdns::async_io::Behaviour,
-}
-
-fn validate_hex(s: &str) -> Result<String, String> {
-    if s.chars().all(|c| "0123456789abcdefABCDEF".contains(c)) {
-        Ok(s.to_lowercase())
-    } else {
-        Err(String::from("Difficulty should be a valid hex string"))
-    }
-}
+/// Name of the network: gossipsub topic and data of the genesis block.
+const NETWORK: &str = "hash-net";
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    #[arg(value_parser = validate_hex, short, long, default_value_t = String::from("00"))]
+    #[command(subcommand)]
+    mode: Option<Mode>,
+    #[arg(value_parser = parse_difficulty, short, long, default_value_t = String::from("00"))]
     difficulty: String,
+    /// Benchmark the hashrate and pick a difficulty so that a block takes about this many seconds
+    #[arg(long, value_name = "TARGET_SECS")]
+    auto_difficulty: Option<u64>,
+    /// Write every block appended to the chain to its own file in this directory
+    #[arg(long, value_name = "DIR")]
+    archive_dir: Option<PathBuf>,
+    /// Serve the health of the node over HTTP (e.g. `/healthz`) on this address
+    #[arg(long, value_name = "ADDR")]
+    health_addr: Option<SocketAddr>,
+    /// Consider the node ready without connected peers
+    #[arg(long)]
+    solo: bool,
+    /// Record every message received and sent by the node to this file
+    #[arg(long, value_name = "PATH")]
+    record: Option<PathBuf>,
+    /// Mine only the own chain, ignoring mined blocks and chains from other nodes
+    #[arg(long)]
+    solo_mining: bool,
+    /// Confirm every queued transaction from the network with an Ack message
+    #[arg(long)]
+    ack: bool,
+    /// Keep mined blocks up to this many ids ahead of the chain until it catches up with them
+    #[arg(long, value_name = "N")]
+    orphan_window: Option<u64>,
+    /// Queue own transactions when they come back from the network or after a delay
+    #[arg(long)]
+    defer_own: bool,
+    /// Start mining blocks from the queue at most once per this many seconds
+    #[arg(long, value_name = "SECS")]
+    min_block_interval: Option<u64>,
+    /// Tag of the network folded into block hashes, so blocks are valid only on this network
+    #[arg(long, value_name = "ID", default_value_t = String::new())]
+    network_id: String,
+    /// Load the chain from this file on start (if it exists) and save it there on exit
+    #[arg(long, value_name = "PATH")]
+    chain_file: Option<PathBuf>,
+    /// Format of the chain file (json or bincode); any format is detected on load
+    #[arg(long, value_name = "FORMAT", default_value_t = DbFormat::Json)]
+    db_format: DbFormat,
+    /// Maximum number of blocks from one peer in the queue
+    #[arg(long, value_name = "N")]
+    max_queued_per_peer: Option<usize>,
+    /// Notify when own transactions get this many confirmations
+    #[arg(long, value_name = "N")]
+    confirmations: Option<u64>,
+    /// Don't mine until the chain from other nodes is received or this many seconds elapse
+    #[arg(long, value_name = "SECS")]
+    initial_sync: Option<u64>,
+    /// Repeat the chain request during the initial sync after this many seconds, doubling the delay
+    #[arg(long, value_name = "SECS")]
+    sync_retry: Option<u64>,
+    /// Drop data of blocks older than this many seconds, keeping their headers
+    #[arg(long, value_name = "SECS")]
+    retention: Option<u64>,
+    /// Log a summary of the node's state every this many seconds
+    #[arg(long, value_name = "SECS")]
+    heartbeat: Option<u64>,
+    /// Mine a block again if it isn't mined in this many seconds
+    #[arg(long, value_name = "SECS")]
+    mining_timeout: Option<u64>,
+    /// Lower the difficulty by one byte every time mining is timed out (see --mining-timeout)
+    #[arg(long, requires = "mining_timeout")]
+    lower_difficulty_on_stall: bool,
+    /// Mine a block with empty data when no transactions are queued for this many seconds
+    #[arg(long, value_name = "SECS")]
+    empty_block_interval: Option<u64>,
+    /// Don't start mining the next block until this many peers are connected
+    #[arg(long, value_name = "N")]
+    min_peers_to_mine: Option<usize>,
+    /// Log only warnings and errors, ignoring RUST_LOG
+    #[arg(long, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Log debug messages too, ignoring RUST_LOG
+    #[arg(long)]
+    verbose: bool,
+    /// Mine this many blocks (empty ones if no transactions are queued), then exit
+    #[arg(long, value_name = "N")]
+    mine_blocks: Option<u64>,
+    /// Mine blocks on this many threads
+    #[arg(long, value_name = "N")]
+    mining_threads: Option<usize>,
+    /// Mine blocks with this many tasks trying random nonces
+    #[arg(long, value_name = "N")]
+    nonce_workers: Option<usize>,
+    /// Run mining threads with this nice level (Linux only), e.g. 19 for the lowest priority
+    #[arg(long, value_name = "LEVEL", allow_hyphen_values = true)]
+    mining_nice: Option<i32>,
+    /// Reject new blocks with data larger than this many bytes
+    #[arg(long, value_name = "BYTES")]
+    max_block_size: Option<usize>,
+    /// Keep bodies of at most this many newest blocks, pruning older ones
+    #[arg(long, value_name = "N")]
+    max_chain_blocks: Option<usize>,
+    /// Reject new blocks when this many blocks are queued
+    #[arg(long, value_name = "N")]
+    max_queue: Option<usize>,
+    /// Keep at most this many blocks ahead of the chain, evicting the farthest ones
+    #[arg(long, value_name = "N")]
+    max_orphans: Option<usize>,
+    /// Keep the mined last block instead of replacing it with a remote one with a lower hash
+    #[arg(long)]
+    prefer_first_seen: bool,
+    /// Reject blocks from other nodes with fewer zero bits at the mined end of the hash
+    #[arg(long, value_name = "BITS")]
+    min_difficulty_bits: Option<u32>,
+    /// Reject chains from other nodes with another genesis block
+    #[arg(long)]
+    check_genesis: bool,
+    /// Reject chains from other nodes with blocks not meeting the difficulty recorded in them
+    #[arg(long)]
+    strict_difficulty: bool,
+    /// Reject chains from other nodes with blocks created more than this many seconds in the future
+    #[arg(long, value_name = "SECS")]
+    max_time_drift: Option<u64>,
+    /// Retarget the difficulty after every mined block so blocks come about every this many seconds
+    #[arg(long, value_name = "SECS")]
+    target_block_time: Option<u64>,
+    /// Hash function of blocks of a new chain (sha256, sha3-256 or blake3); a loaded chain keeps its own
+    #[arg(long, value_name = "ALGO", default_value_t = HashAlgo::Sha256)]
+    hash_algo: HashAlgo,
+    /// Which end of block hashes must match the difficulty (prefix or suffix)
+    #[arg(long, value_name = "MODE", default_value_t = DifficultyMode::Suffix)]
+    difficulty_mode: DifficultyMode,
+    /// Dial this peer on start, e.g. `/ip4/10.0.0.1/tcp/4001` (can be repeated)
+    #[arg(long, value_name = "ADDR")]
+    peer: Vec<Multiaddr>,
+    /// Save addresses of explicit and discovered peers to this file and dial them on start
+    #[arg(long, value_name = "PATH")]
+    peers_file: Option<PathBuf>,
+    /// Reject chains from other nodes larger than this many bytes before decoding them
+    #[arg(long, value_name = "BYTES", default_value_t = Message::MAX_SIZE)]
+    max_chain_response: usize,
+}
+
+#[derive(Subcommand, Debug)]
+enum Mode {
+    /// Replay the messages recorded with --record in a node without network and print its chain
+    Replay {
+        /// The file with recorded messages
+        path: PathBuf,
+    },
+}
+
+/// Returns the number of peers either discovered with mDNS or connected.
+fn peer_count(discovered: &HashMap<PeerId, Vec<Multiaddr>>, connected: &HashSet<PeerId>) -> usize {
+    discovered
+        .keys()
+        .filter(|peer| !connected.contains(*peer))
+        .count()
+        + connected.len()
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     // Argument with difficult of blocks
     let args = Args::parse();
-    let difficulty = std::env::var("DIFFICULTY").unwrap_or_else(|_| args.difficulty.clone());
+    let mut difficulty = match std::env::var("DIFFICULTY") {
+        Ok(d) => parse_difficulty(&d)?,
+        Err(_) => args.difficulty.clone(),
+    };
 
     // Enable logging
-    pretty_env_logger::init();
+    if args.quiet {
+        pretty_env_logger::formatted_builder()
+            .filter_level(LevelFilter::Warn)
+            .init();
+    } else if args.verbose {
+        pretty_env_logger::formatted_builder()
+            .filter_level(LevelFilter::Debug)
+            .init();
+    } else {
+        pretty_env_logger::init();
+    }
+
+    // Replace difficulty with calibrated one
+    if let Some(secs) = args.auto_difficulty {
+        difficulty = calibrate_difficulty(Duration::from_secs(secs)).await;
+    }
+    println!(
+        "[Host] Difficulty \"{difficulty}\": {} per block",
+        describe_difficulty(&difficulty)
+    );
+
+    // Chain and settings of the node
+    let chain = match &args.chain_file {
+        Some(path) if path.exists() => Chain::load_from_file(path)?,
+        _ => Chain::with_genesis_algo(NETWORK, args.hash_algo),
+    };
+    let config = NodeConfig {
+        archive_dir: args.archive_dir,
+        solo: args.solo,
+        record: args.record,
+        solo_mining: args.solo_mining,
+        ack_new_blocks: args.ack,
+        orphan_window: args.orphan_window,
+        defer_own: args.defer_own,
+        min_block_interval: args.min_block_interval.map(Duration::from_secs),
+        network_id: args.network_id,
+        max_queued_per_peer: args.max_queued_per_peer,
+        confirmations: args.confirmations,
+        initial_sync: args.initial_sync.map(Duration::from_secs),
+        sync_retry: args.sync_retry.map(Duration::from_secs),
+        retention: args.retention.map(Duration::from_secs),
+        heartbeat: args.heartbeat.map(Duration::from_secs),
+        mining_timeout: args.mining_timeout.map(Duration::from_secs),
+        stall_policy: if args.lower_difficulty_on_stall {
+            StallPolicy::LowerDifficulty
+        } else {
+            StallPolicy::Redispatch
+        },
+        empty_block_interval: args.empty_block_interval.map(Duration::from_secs),
+        min_peers_to_mine: args.min_peers_to_mine,
+        mine_blocks: args.mine_blocks,
+        // the hook is for applications embedding the node
+        on_block: None,
+        mining_threads: args.mining_threads,
+        nonce_workers: args.nonce_workers,
+        mining_nice: args.mining_nice,
+        max_block_size: args.max_block_size,
+        limits: MemoryLimits {
+            max_chain_blocks: args.max_chain_blocks,
+            max_queue: args.max_queue,
+            max_orphans: args.max_orphans,
+        },
+        prefer_first_seen: args.prefer_first_seen,
+        min_difficulty_bits: args.min_difficulty_bits,
+        validation: ValidationPolicy {
+            genesis: args.check_genesis.then(|| Block::genesis(NETWORK).hash),
+            recorded_difficulty: args.strict_difficulty,
+            max_time_drift: args.max_time_drift,
+            ..Default::default()
+        },
+        target_block_time: args.target_block_time.map(Duration::from_secs),
+        difficulty_mode: args.difficulty_mode,
+    };
+
+    // Replay mode: no network, the recorded messages only
+    if let Some(Mode::Replay { path }) = &args.mode {
+        let entries = MessageLog::read(path)?;
+        let (tx_node, mut rx_net) = mpsc::channel::<Message>(64);
+        let (_tx_net, rx_node) = mpsc::channel::<Message>(1);
+        let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+        // messages sent by the node aren't needed
+        task::spawn(async move { while rx_net.recv().await.is_some() {} });
+        let mut node =
+            Node::new(chain, tx_node, rx_node, rx_cancel, difficulty.clone()).with_config(config);
+        println!("[Host] Replaying {} messages", entries.len());
+        node.replay_log(entries).await;
+        println!("Chain:\r\n + {}", node.chain());
+        return Ok(());
+    }
 
     // PeedId creating
     let local_key = identity::Keypair::generate_ed25519();
@@ -72,9 +309,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     // Validation for msg
     let message_id_fn = |message: &gossipsub::Message| {
-        let mut s = DefaultHasher::new();
-        message.data.hash(&mut s);
-        gossipsub::MessageId::from(s.finish().to_string())
+        gossipsub::MessageId::from(Message::gossip_id(&message.data))
     };
 
     // Config fabric
@@ -94,7 +329,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
     .expect("Correct configuration");
 
     // Topic
-    let topic = gossipsub::IdentTopic::new("hash-net");
+    let topic = gossipsub::IdentTopic::new(NETWORK);
     gossipsub.subscribe(&topic)?;
 
     // Create a Swarm to manage peers and events
@@ -110,6 +345,25 @@ async fn main() -> Result<(), Box<dyn Error>> {
     // Listen ports
     swarm.listen_on("/ip4/0.0.0.0/tcp/0".parse()?)?;
 
+    // Dial explicit and saved peers without waiting for discovery
+    let mut peer_book = match &args.peers_file {
+        Some(path) => Some(PeerBook::load(path)?),
+        None => None,
+    };
+    let mut dial = args.peer.clone();
+    if let Some(book) = &mut peer_book {
+        for addr in args.peer.iter() {
+            book.add(addr.clone());
+        }
+        dial = book.addrs().to_vec();
+    }
+    for addr in dial {
+        info!("Dialing peer {addr}");
+        if let Err(e) = swarm.dial(addr.clone()) {
+            warn!("Can't dial peer {addr}: {e}");
+        }
+    }
+
     info!("Now you can enter transactions via STDIN");
     info!("Transactions will be sended to other nodes");
 
@@ -119,54 +373,227 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let (tx_cancel, rx_cancel) = broadcast::channel(1);
 
     // Run task with blockchain node
-    let mut node = Node::new(Chain::new(), tx_node, rx_node, rx_cancel, difficulty);
-    let _task = task::spawn(async move {
-        node.run().await;
+    let mut node =
+        Node::new(chain, tx_node, rx_node, rx_cancel, difficulty.clone()).with_config(config);
+    let stats = node.stats();
+    let health = node.health();
+    let sync = node.sync_control();
+    let mut events = node.subscribe();
+    if let Some(addr) = args.health_addr {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        let health = health.clone();
+        task::spawn(async move {
+            if let Err(e) = serve_health(listener, health).await {
+                error!("Health endpoint error: {:?}", e);
+            }
+        });
+    }
+    let started = Instant::now();
+    let mut task = task::spawn(async move {
+        let pending = node.run().await;
+        (pending, node)
     });
+    // the node stops itself after `--mine-blocks`
+    let mut finished = None;
 
     // ls command flag
     let mut ls_flag = false;
-
+    // ls queue command flag
+    let mut queue_flag = false;
+    // dump --report command flag
+    let mut report_flag = false;
+    // dump --dot command flag
+    let mut dot_flag = false;
+    // headers command flag
+    let mut headers_flag = false;
+    // stats command flag
+    let mut stats_flag = false;
+    // compare command: the peer and the local chain to compare with its chain
+    let mut compare: Option<(String, Option<Chain>)> = None;
+    // Discovered peers with their addresses
+    let mut peers: HashMap<PeerId, Vec<Multiaddr>> = HashMap::new();
+    // Peers with open connections, e.g. dialed with --peer rather than discovered
+    let mut connected: HashSet<PeerId> = HashSet::new();
+
+    println!("[Host] Type help to list commands");
     // Return
     loop {
         tokio::select! {
+            result = &mut task => {
+                finished = Some(result);
+                break;
+            },
             line = stdin.select_next_some() => {
+                let line = line.expect("Stdin not to close");
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let command = match line.parse::<Command>() {
+                    Ok(command) => command,
+                    Err(e) => {
+                        println!("[Host] {e}");
+                        continue;
+                    }
+                };
+                // the chain is printed when the node sends it
+                let request_chain = matches!(
+                    command,
+                    Command::Ls
+                        | Command::LsQueue
+                        | Command::DumpReport
+                        | Command::DumpDot
+                        | Command::Headers
+                        | Command::Compare(_)
+                        | Command::Stats
+                );
+                match command {
+                    Command::Help => print!("{}", Command::help()),
+                    Command::Ls => ls_flag = true,
+                    Command::LsQueue => queue_flag = true,
+                    Command::DumpReport => report_flag = true,
+                    Command::DumpDot => dot_flag = true,
+                    Command::Headers => headers_flag = true,
+                    Command::Compare(peer) => compare = Some((peer, None)),
+                    Command::Stats => {
+                        println!("[Host] Messages:\r\n{}", stats.lock().unwrap());
+                        stats_flag = true;
+                    }
+                    Command::Peers => {
+                        println!("[Host] {} peers:", peers.len());
+                        for (peer_id, addrs) in peers.iter() {
+                            let addrs: Vec<String> = addrs.iter().map(|a| a.to_string()).collect();
+                            println!("[Host] {peer_id} {}", addrs.join(", "));
+                        }
+                    }
+                    Command::Debug => {
+                        if let Err(e) = tx_net.send(Message::DebugRequest).await {
+                            error!("Can't send data to host node: {e}");
+                        }
+                    }
+                    Command::CancelSync => {
+                        if sync.cancel() {
+                            println!("[Host] Sync is cancelled");
+                        } else {
+                            println!("[Host] No sync in progress");
+                        }
+                    }
+                    Command::Exit => {
+                        tx_cancel.send(()).unwrap();
+                        break;
+                    }
+                    Command::Inject(block) => {
+                        println!("[Host] Injecting block #{}",block.id);
+                        if let Err(e) = tx_net.send(Message::InjectBlock(block)).await {
+                            error!("Can't send data to host node: {e}");
+                        }
+                    }
+                    // it changes the tip under other nodes
+                    Command::Remine(_) if !args.solo_mining => println!("[Host] Re-mining the tip needs --solo-mining"),
+                    Command::Remine(bits) => {
+                        println!("[Host] Re-mining the tip for {bits} bits");
+                        if let Err(e) = tx_net.send(Message::RemineTip(bits)).await {
+                            error!("Can't send data to host node: {e}");
+                        }
+                    }
+                    Command::Transaction(data) => {
+                        let block = Block::new_single(0, data);
+                        let msg = Message::NewBlock(block.clone());
+                        let serded = match msg.to_bytes() {
+                            Ok(serded) => serded,
+                            Err(e) => {
+                                println!("[Host] Can't send transaction: {e}");
+                                continue;
+                            }
+                        };
+
+                        println!("[Host] {}",msg);
+                        info!("[Host] {}",msg);
+                        if let Err(e) = tx_net.send(Message::SubmitBlock(block)).await {
+                            error!("Can't send data to host node: {e}");
+                        }
 
-                let mut line = line.expect("Stdin not to close");
-                if line == "ls"  {
-                    ls_flag = true;
-                    if let Err(e) = tx_net.send(Message::ChainRequest).await {
-                        error!("Can't send data to host node: {e}");
+                        if let Err(e) = swarm
+                            .behaviour_mut().gossipsub
+                            .publish(topic.clone(), serded) {
+                            error!("Publish error around sending new transaction to other hosts: {e:?}");
+                        }
                     }
                 }
-                if line == "exit" {
-                    tx_cancel.send(()).unwrap();
-                    break;
-                }
-                if line.starts_with('=') && line.len() > 1 {
-                    line.remove(0);
-
-
-                    let block = Block::new(0, line);
-                    let msg = Message::NewBlock(block);
-                    let serded = serde_json::to_string(&msg).expect("Message is serializible");
-
-                    println!("[Host] {}",msg);
-                    info!("[Host] {}",msg);
-                    if let Err(e) = tx_net.send(msg).await {
+                if request_chain {
+                    if let Err(e) = tx_net.send(Message::ChainRequest).await {
                         error!("Can't send data to host node: {e}");
                     }
-
-                    if let Err(e) = swarm
-                        .behaviour_mut().gossipsub
-                        .publish(topic.clone(), serded) {
-                        error!("Publish error around sending new transaction to other hosts: {e:?}");
-                    }
                 }
             },
+            Ok(event) = events.recv() => match event {
+                NodeEvent::Confirmed { id, data, confirmations } => {
+                    println!("[Host] Transaction \"{data}\" in block #{id} has {confirmations} confirmations");
+                }
+                // the node logs heartbeats, reorgs and stalls itself
+                NodeEvent::Heartbeat { .. } | NodeEvent::Reorg { .. } | NodeEvent::MiningStalled { .. } => {}
+            },
             msg = rx_net.recv().fuse() => {
                 if let Some(msg) = msg {
+                    // debug info is only for the host
+                    if let Message::DebugResponse(debug) = msg {
+                        println!("[Host] Node state:\r\n{}", debug);
+                        continue;
+                    }
                     if let Message::ChainResponce(chain) = msg.clone() {
+                        if let Some((peer, local @ None)) = &mut compare {
+                            // now request the chain of the peer
+                            *local = Some(chain);
+                            let published = Message::ChainRequest
+                                .to_bytes()
+                                .map_err(|e| e.to_string())
+                                .and_then(|serded| swarm.behaviour_mut().gossipsub.publish(topic.clone(), serded).map_err(|e| format!("{e:?}")));
+                            if let Err(e) = published {
+                                error!("Publish error around requesting chain of {peer}: {e}");
+                                compare = None;
+                            }
+                            continue;
+                        }
+                        if report_flag {
+                            print!("{}", chain.integrity_report());
+                            report_flag = false;
+                            if !dot_flag && !ls_flag && !headers_flag && !stats_flag && !queue_flag {
+                                continue;
+                            }
+                        }
+                        if dot_flag {
+                            print!("{}", chain.to_dot());
+                            dot_flag = false;
+                            if !ls_flag && !headers_flag && !stats_flag && !queue_flag {
+                                continue;
+                            }
+                        }
+                        if queue_flag {
+                            println!("[Host] {} blocks in queue:", chain.queue.len());
+                            for (id, data) in chain.pending_ids().iter().zip(chain.pending_data()) {
+                                println!("[Host] #{id} \"{data}\"");
+                            }
+                            queue_flag = false;
+                            if !ls_flag && !headers_flag && !stats_flag {
+                                continue;
+                            }
+                        }
+                        if stats_flag {
+                            println!("[Host] Chain:\r\n{}", chain.stats());
+                            stats_flag = false;
+                            if !ls_flag && !headers_flag {
+                                continue;
+                            }
+                        }
+                        if headers_flag {
+                            match encode_json(&chain.export_headers()) {
+                                Ok(headers) => println!("{}", String::from_utf8_lossy(&headers)),
+                                Err(e) => println!("[Host] Can't encode headers: {e}"),
+                            }
+                            headers_flag = false;
+                            if !ls_flag {
+                                continue;
+                            }
+                        }
                         if ls_flag {
                             println!("Chain:\r\n + {}",chain);
                             ls_flag = false;
@@ -175,7 +602,13 @@ async fn main() -> Result<(), Box<dyn Error>> {
                     }
                     info!("[Host] {}",msg);
 
-                    let serded = serde_json::to_string(&msg).expect("Message is serializible");
+                    let serded = match msg.to_bytes() {
+                        Ok(serded) => serded,
+                        Err(e) => {
+                            error!("Skipping message which can't be sent: {e}");
+                            continue;
+                        }
+                    };
 
                     if let Err(e) = swarm
                     .behaviour_mut().gossipsub
@@ -189,13 +622,44 @@ async fn main() -> Result<(), Box<dyn Error>> {
                     for (peer_id, multiaddr) in list {
                         info!("mDNS discovered a new peer: {multiaddr} {peer_id} ");
                         swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer_id);
+                        if let Some(book) = &mut peer_book {
+                            if book.add(multiaddr.clone()) {
+                                if let Err(e) = book.save() {
+                                    error!("Can't save peer addresses: {e}");
+                                }
+                            }
+                        }
+                        let addrs = peers.entry(peer_id).or_default();
+                        if !addrs.contains(&multiaddr) {
+                            addrs.push(multiaddr);
+                        }
                     }
+                    health.lock().unwrap().peers = peer_count(&peers, &connected);
                 },
                 SwarmEvent::Behaviour(MyBehaviourEvent::Mdns(mdns::Event::Expired(list))) => {
-                    for (peer_id, _multiaddr) in list {
+                    for (peer_id, multiaddr) in list {
                         info!("mDNS discover peer has expired: {peer_id}");
                         swarm.behaviour_mut().gossipsub.remove_explicit_peer(&peer_id);
+                        if let Some(addrs) = peers.get_mut(&peer_id) {
+                            addrs.retain(|a| a != &multiaddr);
+                            if addrs.is_empty() {
+                                peers.remove(&peer_id);
+                            }
+                        }
                     }
+                    health.lock().unwrap().peers = peer_count(&peers, &connected);
+                },
+                SwarmEvent::ConnectionEstablished { peer_id, .. } => {
+                    info!("Connected to peer {peer_id}");
+                    connected.insert(peer_id);
+                    health.lock().unwrap().peers = peer_count(&peers, &connected);
+                },
+                SwarmEvent::ConnectionClosed { peer_id, num_established, .. } => {
+                    if num_established == 0 {
+                        info!("Disconnected from peer {peer_id}");
+                        connected.remove(&peer_id);
+                    }
+                    health.lock().unwrap().peers = peer_count(&peers, &connected);
                 },
                 SwarmEvent::Behaviour(MyBehaviourEvent::Gossipsub(gossipsub::Event::Message {
                     propagation_source: peer_id,
@@ -203,13 +667,42 @@ async fn main() -> Result<(), Box<dyn Error>> {
                     message,
                 })) =>  {
                     // recieve message from remote
-                    let msg = String::from_utf8_lossy(&message.data);
-                    let msg : Message = serde_json::from_str(&msg).expect("Message should be desializeble");
                     let peer = peer_id.to_string();
+                    let msg = match Message::from_slice_with_chain_limit(&message.data, args.max_chain_response) {
+                        Ok(msg) => msg,
+                        Err(e) => {
+                            warn!("[Remote {peer}]: {e}");
+                            continue;
+                        }
+                    };
                     info!("[Remote {peer}]: {msg}");
+                    let author = message.source.map_or_else(|| peer.clone(), |source| source.to_string());
+                    if let (Some((expected, Some(local))), Message::ChainResponce(remote)) = (&compare, &msg) {
+                        if *expected == author {
+                            println!("[Host] Comparing with {author}:\r\n{}", local.diff(remote));
+                            compare = None;
+                        }
+                    }
+                    if let Message::Ack { data_hash } = &msg {
+                        println!("[Host] Transaction {} is queued by {author}", hex::encode(data_hash));
+                    }
+                    // only the host submits, injects and re-mines blocks
+                    if matches!(
+                        msg,
+                        Message::Ack { .. }
+                            | Message::DebugRequest
+                            | Message::DebugResponse(_)
+                            | Message::Envelope(..)
+                            | Message::Reject { .. }
+                            | Message::RemineTip(_)
+                            | Message::SubmitBlock(_)
+                            | Message::InjectBlock(_)
+                    ) {
+                        continue;
+                    }
 
-                    // put it in host
-                    if let Err(e)  = tx_net.send(msg).await {
+                    // put it in host, on behalf of the node which published it rather than the relay
+                    if let Err(e)  = tx_net.send(Message::Envelope(author, Box::new(msg))).await {
                         error!("Can't send data to host node: {e}");
                     }
 
@@ -218,5 +711,36 @@ async fn main() -> Result<(), Box<dyn Error>> {
             }
         }
     }
+
+    if let Some(book) = &peer_book {
+        book.save()?;
+    }
+
+    // Wait for node shutdown
+    let (pending, node) = match finished {
+        Some(result) => result?,
+        None => task.await?,
+    };
+    if let Some(n) = args.mine_blocks {
+        let secs = started.elapsed().as_secs_f64();
+        let hashrate = n as f64 * expected_attempts(&difficulty) / secs.max(f64::EPSILON);
+        println!("[Host] Mined {n} blocks in {secs:.2}s, ~{hashrate:.0} H/s");
+    }
+    if let Some(path) = &args.chain_file {
+        // blocks which weren't mined are resumed on the next start
+        let mut chain = node.chain().clone();
+        chain.restore_queue(pending);
+        chain.save_to_file(path, args.db_format)?;
+        info!(
+            "Chain is saved to {} with {} pending blocks",
+            path.display(),
+            chain.queue.len()
+        );
+    } else if !pending.is_empty() {
+        info!("{} blocks weren't mined:", pending.len());
+        for block in pending {
+            info!("#-{} \"{}\"", block.id, block.data);
+        }
+    }
     Ok(())
 }
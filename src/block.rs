@@ -1,8 +1,12 @@
 use core::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
+use crate::{BlockHeader, BlockSummary, ChainError, DifficultyMode, HashAlgo};
+
 /// The `Block` struct represents a block in the blockchain.
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Block {
@@ -16,10 +20,35 @@ pub struct Block {
     pub prev: [u8; 32],
     /// The nonce used to mine the block.
     pub nonce: u64,
+    /// The tag of the network the block is mined for, folded into the hash when not empty.
+    ///
+    /// So the proof of work of a block is valid only on its network.
+    #[serde(default)]
+    pub salt: String,
+    /// When the block was created, in Unix seconds. Folded into the hash when not zero.
+    #[serde(default)]
+    pub timestamp: u64,
+    /// The transactions stored in the block after `data`, see `transactions`.
+    #[serde(default)]
+    pub transactions: Vec<String>,
+    /// How many nonces the miner tried before finding `nonce`, as reported by the miner.
+    ///
+    /// It isn't hashed, so it doesn't affect the validity of the block and can't be trusted for
+    /// blocks mined by other nodes.
+    #[serde(default)]
+    pub mining_attempts: Option<u64>,
+    /// The hash function of the block, set by the chain it's appended to (see
+    /// `Chain::hash_algo`). It isn't hashed itself.
+    #[serde(default)]
+    pub hash_algo: HashAlgo,
+    /// Whether `data` and `transactions` were dropped to save space (see `prune`), so only the
+    /// header of the block is left and its hash can't be calculated again.
+    #[serde(default)]
+    pub pruned: bool,
 }
 
 impl Block {
-    /// Creates a new `Block` with the given `id` and `data`.
+    /// Creates a new `Block` with the given `id` and `data`, created now.
     ///
     /// # Example
     ///
@@ -30,38 +59,245 @@ impl Block {
     ///
     /// assert_eq!(block.id, 1);
     /// assert_eq!(block.data, "Hello, world!");
+    /// assert!(block.timestamp > 0);
     /// ```
     pub fn new(id: u64, data: String) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        Block::new_with_timestamp(id, data, now)
+    }
+
+    /// Creates a new `Block` like `new`, but with the given creation time in Unix seconds, so the
+    /// block is the same every time.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Block;
+    ///
+    /// let block = Block::new_with_timestamp(1, "Hello, world!".to_owned(), 1_700_000_000);
+    ///
+    /// assert_eq!(block.timestamp, 1_700_000_000);
+    /// assert!(block.equals(&Block::new_with_timestamp(1, "Hello, world!".to_owned(), 1_700_000_000)));
+    /// ```
+    pub fn new_with_timestamp(id: u64, data: String, timestamp: u64) -> Self {
         Self {
             id,
             data,
             hash: [0u8; 32],
             prev: [0u8; 32],
             nonce: 0,
+            salt: String::new(),
+            timestamp,
+            transactions: Vec::new(),
+            mining_attempts: None,
+            hash_algo: HashAlgo::Sha256,
+            pruned: false,
         }
     }
 
-    /// Calculates the SHA256 hash for the block and returns it
+    /// Creates a new `Block` like `new`, but holding several transactions: the first one is
+    /// stored as `data` and the rest in `transactions`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Block;
+    ///
+    /// let block = Block::with_transactions(1, vec!["Alice".to_owned(), "Bob".to_owned()]);
+    ///
+    /// assert_eq!(block.data, "Alice");
+    /// assert_eq!(block.transactions().collect::<Vec<_>>(), ["Alice", "Bob"]);
+    /// ```
+    pub fn with_transactions(id: u64, transactions: Vec<String>) -> Self {
+        let mut transactions = transactions.into_iter();
+        let mut block = Block::new(id, transactions.next().unwrap_or_default());
+        block.transactions = transactions.collect();
+        block
+    }
+
+    /// Creates a new `Block` holding the single transaction `data`, the same as `new` and
+    /// `with_transactions` with one transaction.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Block;
+    ///
+    /// let block = Block::new_single(1, "Alice".to_owned());
+    ///
+    /// assert_eq!(block.transactions().collect::<Vec<_>>(), ["Alice"]);
+    /// ```
+    pub fn new_single(id: u64, data: String) -> Self {
+        Block::with_transactions(id, vec![data])
+    }
+
+    /// Creates a `Block` with all fields set and the hash calculated from them. The block has no
+    /// timestamp.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Block;
+    ///
+    /// let block = Block::with_nonce(1, "Hello, world!".to_owned(), [0u8; 32], 42);
+    ///
+    /// assert_eq!(block.nonce, 42);
+    /// assert!(block.validate_hash());
+    /// ```
+    pub fn with_nonce(id: u64, data: String, prev: [u8; 32], nonce: u64) -> Self {
+        let mut block = Self {
+            id,
+            data,
+            hash: [0u8; 32],
+            prev,
+            nonce,
+            salt: String::new(),
+            timestamp: 0,
+            transactions: Vec::new(),
+            mining_attempts: None,
+            hash_algo: HashAlgo::Sha256,
+            pruned: false,
+        };
+        block.update_hash();
+        block
+    }
+
+    /// Creates the genesis block of the network with the given name.
+    ///
+    /// The genesis block isn't mined: it has id 0, zero `prev`, `nonce` and `timestamp`, and the
+    /// name of the network as data. So all nodes of the same network start from the same block.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Block;
+    ///
+    /// let genesis = Block::genesis("hash-net");
+    ///
+    /// assert_eq!(genesis.id, 0);
+    /// assert!(genesis.validate_hash());
+    /// assert!(genesis.equals(&Block::genesis("hash-net")));
+    /// assert!(!genesis.equals(&Block::genesis("other-net")));
+    /// ```
+    pub fn genesis(network: &str) -> Self {
+        let mut block = Block::new_with_timestamp(0, network.to_owned(), 0);
+        block.update_hash();
+        block
+    }
+
+    /// Calculates the hash for the block with its `hash_algo` (SHA256 by default) and returns it
     /// 
     /// # Examples
     /// 
     /// ```
-    /// use rustychain::Block;
+    /// use rustychain::{Block, HashAlgo};
     /// 
-    /// let block = Block::new(0, "Hello World!".to_owned());
+    /// let mut block = Block::new(0, "Hello World!".to_owned());
     /// let hash = block.calc_hash();
+    ///
+    /// block.hash_algo = HashAlgo::Sha3_256;
+    /// assert_ne!(block.calc_hash(), hash);
     /// ```
     pub fn calc_hash(&self) -> [u8; 32] {
+        self.hash_algo.hash(&self.canonical_bytes())
+    }
+
+    /// Returns the exact bytes which are hashed by `calc_hash`, so other tools can compute
+    /// compatible hashes.
+    ///
+    /// The bytes are, in order: the length of `salt` as big-endian u64 and `salt` itself (both
+    /// only if the salt isn't empty), `id` as big-endian u64, `data`, `prev`, `nonce` as
+    /// big-endian u64, `timestamp` as big-endian u64 (even if it's zero), and every one of
+    /// `transactions` in order as its length as big-endian u64 followed by the transaction.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::Block;
+    ///
+    /// let block = Block::new_with_timestamp(1, "Hi".to_owned(), 0);
+    /// let bytes = block.canonical_bytes();
+    ///
+    /// assert_eq!(bytes.len(), 8 + 2 + 32 + 8 + 8);
+    /// assert_eq!(&bytes[..8], &1u64.to_be_bytes());
+    /// assert_eq!(&bytes[8..10], b"Hi");
+    /// ```
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        self.canonical_bytes_with_nonce(self.nonce)
+    }
+
+    /// Returns `canonical_bytes` as if the block had the given nonce.
+    fn canonical_bytes_with_nonce(&self, nonce: u64) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8 + self.salt.len() + 8 + self.data.len() + 32 + 8 + 8);
+        if !self.salt.is_empty() {
+            bytes.extend_from_slice(&(self.salt.len() as u64).to_be_bytes());
+            bytes.extend_from_slice(self.salt.as_bytes());
+        }
+        bytes.extend_from_slice(&self.id.to_be_bytes());
+        bytes.extend_from_slice(self.data.as_bytes());
+        bytes.extend_from_slice(&self.prev);
+        bytes.extend_from_slice(&nonce.to_be_bytes());
+        bytes.extend_from_slice(&self.timestamp.to_be_bytes());
+        for transaction in &self.transactions {
+            bytes.extend_from_slice(&(transaction.len() as u64).to_be_bytes());
+            bytes.extend_from_slice(transaction.as_bytes());
+        }
+        bytes
+    }
+
+    /// Returns the SHA256 hash of the block's transactions, which identifies them regardless of
+    /// the block they end up in (see `Message::Ack`).
+    ///
+    /// It's the hash of `data` alone for a block without other transactions, the following
+    /// transactions are hashed with their lengths after it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::Block;
+    ///
+    /// let first = Block::new(0, "Some data".to_owned());
+    /// let second = Block::new(7, "Some data".to_owned());
+    /// assert_eq!(first.data_hash(), second.data_hash());
+    ///
+    /// let mut third = Block::new(7, "Some data".to_owned());
+    /// third.add_transaction("More data".to_owned());
+    /// assert_ne!(first.data_hash(), third.data_hash());
+    /// ```
+    pub fn data_hash(&self) -> [u8; 32] {
         let mut hasher = Sha256::new();
-        hasher.update(self.id.to_be_bytes());
-        hasher.update(self.data.clone());
-        hasher.update(self.prev);
-        hasher.update(self.nonce.to_be_bytes());
-        return hasher
+        hasher.update(self.data.as_bytes());
+        for transaction in &self.transactions {
+            hasher.update((transaction.len() as u64).to_be_bytes());
+            hasher.update(transaction.as_bytes());
+        }
+        hasher
             .finalize()
             .as_slice()
             .try_into()
-            .expect("SHA256 output must be 256 bit");
+            .expect("SHA256 output must be 256 bit")
+    }
+
+    /// Calculates the hash the block would have with the given nonce, without changing the block.
+    ///
+    /// The bytes of `canonical_bytes` with the nonce are hashed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::Block;
+    ///
+    /// let mut block = Block::new(0, "Hello World!".to_owned());
+    /// let hash = block.hash_with_nonce(42);
+    /// assert_eq!(block.nonce, 0);
+    ///
+    /// block.nonce = 42;
+    /// assert_eq!(block.calc_hash(), hash);
+    /// ```
+    pub fn hash_with_nonce(&self, nonce: u64) -> [u8; 32] {
+        self.hash_algo.hash(&self.canonical_bytes_with_nonce(nonce))
     }
 
     /// Recalculate the hash of the current block based on its current data, previous block hash,
@@ -97,6 +333,117 @@ impl Block {
         self.hash == self.calc_hash()
     }
 
+    /// Checks that the block is consistent with itself: its `hash` field is the hash of its
+    /// current `id`, `data`, `prev`, `nonce` and `timestamp`.
+    ///
+    /// Catches blocks which were changed without calling `update_hash`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::Block;
+    ///
+    /// let mut block = Block::new(1, "Hello World".to_string());
+    /// block.update_hash();
+    /// assert!(block.is_self_consistent());
+    ///
+    /// block.id = 2;
+    /// assert!(!block.is_self_consistent());
+    /// ```
+    pub fn is_self_consistent(&self) -> bool {
+        self.validate_hash()
+    }
+
+    /// Drops the data and the transactions of the block, keeping its header (see `header`).
+    ///
+    /// The hash stays, but it can't be checked anymore, so `validate_hash` fails for the block.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::Block;
+    ///
+    /// let mut block = Block::new(1, "Hello World".to_string());
+    /// block.update_hash();
+    /// let header = block.header();
+    ///
+    /// block.prune();
+    /// assert!(block.pruned);
+    /// assert!(block.data.is_empty());
+    /// assert_eq!(block.header(), header);
+    /// assert!(!block.validate_hash());
+    /// ```
+    pub fn prune(&mut self) {
+        self.data = String::new();
+        self.transactions = Vec::new();
+        self.pruned = true;
+    }
+
+    /// Returns the ID of the block.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns the data stored in the block.
+    pub fn data(&self) -> &str {
+        &self.data
+    }
+
+    /// Returns all transactions stored in the block in order: `data` followed by `transactions`.
+    pub fn transactions(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.data.as_str()).chain(self.transactions.iter().map(String::as_str))
+    }
+
+    /// Adds the transaction to the end of the block and updates its hash, so the block has to be
+    /// mined again.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Block;
+    ///
+    /// let mut block = Block::new(1, "Alice".to_owned());
+    /// let hash = block.calc_hash();
+    /// block.add_transaction("Bob".to_owned());
+    ///
+    /// assert_eq!(block.transactions().count(), 2);
+    /// assert_ne!(block.hash, hash);
+    /// assert!(block.validate_hash());
+    /// ```
+    pub fn add_transaction(&mut self, transaction: String) {
+        self.transactions.push(transaction);
+        self.update_hash();
+    }
+
+    /// Returns the hash of the block.
+    pub fn hash(&self) -> &[u8; 32] {
+        &self.hash
+    }
+
+    /// Returns the hash of the previous block in the chain.
+    pub fn prev(&self) -> &[u8; 32] {
+        &self.prev
+    }
+
+    /// Returns the nonce used to mine the block.
+    pub fn nonce(&self) -> u64 {
+        self.nonce
+    }
+
+    /// Returns the header of the block: the block without its data.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Block;
+    ///
+    /// let block = Block::genesis("hash-net");
+    /// assert_eq!(block.header().hash, block.hash);
+    /// ```
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader::from(self)
+    }
+
     /// Returns a hexadecimal string representation of the block's hash.
     ///
     /// # Example
@@ -114,6 +461,112 @@ impl Block {
         hex::encode(self.hash)
     }
 
+    /// Returns the first 8 hex chars of the block's hash, enough to tell blocks apart in logs.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Block;
+    ///
+    /// let block = Block::genesis("hash-net");
+    ///
+    /// assert_eq!(block.short_hash().len(), 8);
+    /// assert!(block.string_hash().starts_with(&block.short_hash()));
+    /// ```
+    pub fn short_hash(&self) -> String {
+        hex::encode(&self.hash[..4])
+    }
+
+    /// Returns a compact summary of the block for API responses, with hashes as hex strings and
+    /// the data truncated.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Block;
+    ///
+    /// let block = Block::genesis("hash-net");
+    /// let summary = block.summary();
+    ///
+    /// assert_eq!(summary.id, 0);
+    /// assert_eq!(summary.hash_hex, block.string_hash());
+    /// assert_eq!(summary.data_preview, "hash-net");
+    /// ```
+    pub fn summary(&self) -> BlockSummary {
+        BlockSummary::from(self)
+    }
+
+    /// Returns the number of leading zero bits of the block's hash.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Block;
+    ///
+    /// let mut block = Block::new(1, "Hello world!".to_string());
+    /// block.hash[0] = 0x0f;
+    ///
+    /// assert_eq!(block.leading_zero_bits(), 4);
+    /// ```
+    pub fn leading_zero_bits(&self) -> u32 {
+        let mut bits = 0;
+        for byte in self.hash {
+            bits += byte.leading_zeros();
+            if byte != 0 {
+                break;
+            }
+        }
+        bits
+    }
+
+    /// Returns the number of trailing zero bits of the block's hash.
+    ///
+    /// It's the work of a block mined for zeros with `DifficultyMode::Suffix`, see `work_bits`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Block;
+    ///
+    /// let mut block = Block::new(1, "Hello world!".to_string());
+    /// block.hash[31] = 0xf0;
+    ///
+    /// assert_eq!(block.trailing_zero_bits(), 4);
+    /// ```
+    pub fn trailing_zero_bits(&self) -> u32 {
+        let mut bits = 0;
+        for byte in self.hash.iter().rev() {
+            bits += byte.trailing_zeros();
+            if *byte != 0 {
+                break;
+            }
+        }
+        bits
+    }
+
+    /// Returns the number of zero bits at the end of the block's hash which is mined in the
+    /// difficulty mode: `leading_zero_bits` for `DifficultyMode::Prefix` and
+    /// `trailing_zero_bits` for `DifficultyMode::Suffix`. It's the work of the block.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Block, DifficultyMode};
+    ///
+    /// let mut block = Block::new(1, "Hello world!".to_string());
+    /// block.hash = [0xff; 32];
+    /// block.hash[0] = 0x0f;
+    ///
+    /// assert_eq!(block.work_bits(DifficultyMode::Prefix), 4);
+    /// assert_eq!(block.work_bits(DifficultyMode::Suffix), 0);
+    /// ```
+    pub fn work_bits(&self, mode: DifficultyMode) -> u32 {
+        match mode {
+            DifficultyMode::Prefix => self.leading_zero_bits(),
+            DifficultyMode::Suffix => self.trailing_zero_bits(),
+        }
+    }
+
     /// Returns a hexadecimal string representation of the previous block's hash.
     ///
     /// # Example
@@ -140,14 +593,14 @@ impl Block {
     /// # Returns
     ///
     /// Returns `true` if the two blocks have equal values for `id`, `data`,
-    /// `hash`, `prev`, and `nonce`. Otherwise, returns `false`.
+    /// `hash`, `prev`, `nonce`, `salt`, `timestamp` and `transactions`. Otherwise, returns `false`.
     /// # Examples
     ///
     /// ```
     /// use rustychain::Block;
     ///
     /// let block1 = Block::new(1, String::from("Data"));
-    /// let block2 = Block::new(1, String::from("Data"));
+    /// let block2 = block1.clone();
     /// let block3 = Block::new(2, String::from("Data"));
     ///
     /// assert!(block1.equals(&block2));
@@ -159,9 +612,13 @@ impl Block {
             && self.hash == other.hash
             && self.prev == other.prev
             && self.nonce == other.nonce
+            && self.salt == other.salt
+            && self.timestamp == other.timestamp
+            && self.transactions == other.transactions
     }
 
-    /// Checks if the `id`, `data` and `prev` fields of two `Block` instances are equal.
+    /// Checks if the `id`, `data`, `prev`, `salt`, `timestamp` and `transactions` fields of two
+    /// `Block` instances are equal: the blocks are the same, but may be mined with different nonces.
     ///
     /// # Arguments
     ///
@@ -179,7 +636,63 @@ impl Block {
     /// assert!(!block1.preequals(&block2));
     /// ```
     pub fn preequals(&self, other: &Self) -> bool {
-        self.id == other.id && self.data == other.data && self.prev == other.prev
+        self.id == other.id
+            && self.data == other.data
+            && self.prev == other.prev
+            && self.salt == other.salt
+            && self.timestamp == other.timestamp
+            && self.transactions == other.transactions
+    }
+
+    /// Returns the creation time of the block as UTC date and time, or the raw `timestamp` if it's
+    /// out of range.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Block;
+    ///
+    /// let block = Block::new_with_timestamp(1, "Hello".to_owned(), 1_700_000_000);
+    /// assert_eq!(block.time(), "2023-11-14 22:13:20 UTC");
+    /// ```
+    pub fn time(&self) -> String {
+        i64::try_from(self.timestamp)
+            .ok()
+            .and_then(|secs| NaiveDateTime::from_timestamp_opt(secs, 0))
+            .map_or_else(|| self.timestamp.to_string(), |time| format!("{} UTC", time))
+    }
+
+    /// Encodes the whole block (including `hash` and `nonce`) as a hexadecimal string.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Block;
+    ///
+    /// let mut block = Block::new(0, "Genesis".to_owned());
+    /// block.update_hash();
+    ///
+    /// let decoded = Block::from_hex(&block.to_hex()).unwrap();
+    /// assert!(decoded.equals(&block));
+    /// ```
+    pub fn to_hex(&self) -> String {
+        hex::encode(serde_json::to_vec(self).expect("Block is serializible"))
+    }
+
+    /// Decodes a block previously encoded with `to_hex`.
+    ///
+    /// Returns `ChainError::Decode` if the string isn't valid hex or doesn't contain a block.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Block;
+    ///
+    /// assert!(Block::from_hex("not hex").is_err());
+    /// ```
+    pub fn from_hex(s: &str) -> Result<Self, ChainError> {
+        let bytes = hex::decode(s.trim()).map_err(|e| ChainError::Decode(e.to_string()))?;
+        serde_json::from_slice(&bytes).map_err(|e| ChainError::Decode(e.to_string()))
     }
 }
 
@@ -187,12 +700,19 @@ impl fmt::Display for Block {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "#{} hash: {}, previous: {}, data \"{}\", nonce {}",
+            "#{} hash: {}, previous: {}, data \"{}\"",
             self.id,
             self.string_hash(),
             self.string_prev(),
             self.data,
-            self.nonce,
-        )
+        )?;
+        for transaction in &self.transactions {
+            write!(f, ", \"{}\"", transaction)?;
+        }
+        write!(f, ", nonce {}, time {}", self.nonce, self.time())?;
+        if let Some(attempts) = self.mining_attempts {
+            write!(f, ", {} attempts", attempts)?;
+        }
+        Ok(())
     }
 }
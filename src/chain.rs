@@ -1,9 +1,28 @@
-use core::fmt;
-use std::collections::VecDeque;
+use core::{
+    cmp::Ordering,
+    fmt::{self, Write},
+};
+use std::{
+    collections::VecDeque,
+    fs,
+    ops::Range,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
-use crate::Block;
+use crate::{
+    ActivitySummary, Block, BlockHeader, ChainDiff, ChainError, ChainStats, DbFormat,
+    DifficultyMode, HashAlgo, TipComparison, ValidationPolicy,
+};
+
+/// The first word of the header line of chain files.
+const FILE_MAGIC: &str = "rustychain-chain";
+
+/// How many of the last block times `Chain::next_difficulty` averages.
+pub const RETARGET_WINDOW: usize = 10;
 
 /// A blockchain that consists of a vector of `Block`s and maintains a queue of `Block`s yet to be
 /// appended to the chain.
@@ -15,6 +34,20 @@ pub struct Chain {
     pub status: bool,
     /// The queue of blocks that are yet to be appended to the blockchain.
     pub queue: VecDeque<Block>,
+    /// The difficulty which blocks mined for the chain must meet, the same for every height (see
+    /// `meets_difficulty`). Recorded by the node mining the chain; empty if unknown.
+    #[serde(default)]
+    pub difficulty: String,
+    /// Which end of hashes must match `difficulty`. It's a setting of the network, so taking a
+    /// chain from another node (see `adopt`) keeps it.
+    #[serde(default)]
+    pub difficulty_mode: DifficultyMode,
+    /// The hash function of blocks, given to every block appended by `try_add` (see
+    /// `Block::hash_algo`). Blocks hashed with another function are invalid in the chain. It's a
+    /// setting of the network like `difficulty_mode`, so taking a chain from another node keeps
+    /// it.
+    #[serde(default)]
+    pub hash_algo: HashAlgo,
 }
 
 impl Chain {
@@ -34,13 +67,150 @@ impl Chain {
             blocks: vec![],
             status: true,
             queue: VecDeque::new(),
+            difficulty: String::new(),
+            difficulty_mode: DifficultyMode::default(),
+            hash_algo: HashAlgo::default(),
+        }
+    }
+
+    /// Constructs a new, empty blockchain `Chain` with space for at least `capacity` blocks.
+    ///
+    /// Useful when the length of the chain is known beforehand, e.g. before taking a remote chain.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::Chain;
+    ///
+    /// let chain = Chain::with_capacity(100);
+    /// assert!(chain.blocks.capacity() >= 100);
+    /// assert!(chain.blocks.is_empty());
+    /// ```
+    pub fn with_capacity(capacity: usize) -> Self {
+        Chain {
+            blocks: Vec::with_capacity(capacity),
+            status: true,
+            queue: VecDeque::new(),
+            difficulty: String::new(),
+            difficulty_mode: DifficultyMode::default(),
+            hash_algo: HashAlgo::default(),
+        }
+    }
+
+    /// Constructs a new blockchain `Chain` which starts with the genesis block of the given network.
+    ///
+    /// Nodes created with the same network name share the genesis block, so their first
+    /// transactions are mined as blocks with id 1 on top of the same chain.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::{Block, Chain};
+    ///
+    /// let mut chain = Chain::with_genesis("hash-net");
+    /// assert_eq!(chain.blocks.len(), 1);
+    /// assert_eq!(chain.have_errors(), None);
+    ///
+    /// chain.add_queue(Block::new(0, "First".to_owned()));
+    /// assert_eq!(chain.queue[0].id, 1);
+    /// ```
+    pub fn with_genesis(network: &str) -> Self {
+        Chain::with_genesis_algo(network, HashAlgo::default())
+    }
+
+    /// Constructs a chain like `with_genesis`, but with blocks hashed by the given function (see
+    /// `hash_algo`). Chains with different functions have different genesis blocks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::{Chain, HashAlgo};
+    ///
+    /// let chain = Chain::with_genesis_algo("hash-net", HashAlgo::Blake3);
+    /// assert_eq!(chain.have_errors(), None);
+    /// assert_ne!(chain.blocks[0].hash, Chain::with_genesis("hash-net").blocks[0].hash);
+    /// ```
+    pub fn with_genesis_algo(network: &str, hash_algo: HashAlgo) -> Self {
+        let mut genesis = Block::genesis(network);
+        genesis.hash_algo = hash_algo;
+        genesis.update_hash();
+        Chain {
+            blocks: vec![genesis],
+            status: true,
+            queue: VecDeque::new(),
+            difficulty: String::new(),
+            difficulty_mode: DifficultyMode::default(),
+            hash_algo,
+        }
+    }
+
+    /// Constructs a chain from blocks in any order, e.g. gathered from several peers.
+    ///
+    /// Blocks are sorted by id and appended one by one with `append_block`, so the first error
+    /// is returned: a missing block is reported as `ChainError::WrongId`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::{Chain, ChainError};
+    ///
+    /// let genesis = Chain::with_genesis("hash-net").blocks;
+    /// let chain = Chain::from_blocks(genesis.clone()).unwrap();
+    /// assert_eq!(chain.blocks[0].hash, genesis[0].hash);
+    ///
+    /// let mut wrong = genesis;
+    /// wrong[0].data = "Tampered".to_owned();
+    /// assert_eq!(Chain::from_blocks(wrong).unwrap_err(), ChainError::InvalidHash(0));
+    /// ```
+    pub fn from_blocks(mut blocks: Vec<Block>) -> Result<Chain, ChainError> {
+        blocks.sort_by_key(|block| block.id);
+        let mut chain = Chain::with_capacity(blocks.len());
+        for block in blocks {
+            chain.append_block(block)?;
+        }
+        Ok(chain)
+    }
+
+    /// Builds a valid chain of `n` blocks for tests: the genesis block of `"test-chain"` followed
+    /// by blocks with data `"Block <id>"` and timestamp `<id>`, linked and hashed.
+    ///
+    /// Blocks aren't mined for any difficulty and their timestamps are fixed, so the chain is
+    /// always the same.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::Chain;
+    ///
+    /// let chain = Chain::test_chain(3);
+    /// assert_eq!(chain.blocks[2].data, "Block 2");
+    /// assert_eq!(chain.have_errors(), None);
+    /// ```
+    #[cfg(any(test, feature = "testing"))]
+    pub fn test_chain(n: usize) -> Chain {
+        if n == 0 {
+            return Chain::new();
         }
+        let mut chain = Chain::with_genesis("test-chain");
+        for id in 1..n {
+            chain.add_queue(Block::new_with_timestamp(
+                0,
+                format!("Block {id}"),
+                id as u64,
+            ));
+            chain.try_add();
+            chain.blocks[id].update_hash();
+        }
+        chain
     }
 
     /// Checks if the chain contains any errors.
     ///
     /// Returns `None` if the chain is valid, or the index of the first invalid block
-    /// encountered in the chain.
+    /// encountered in the chain, i.e. the first one of `find_all_errors`.
+    ///
+    /// Blocks pruned by the chain itself are trusted here, so chains from other nodes or files
+    /// are checked with `verify_with_policy` instead.
     ///
     /// # Examples
     ///
@@ -62,34 +232,53 @@ impl Chain {
     ///
     /// ```
     pub fn have_errors(&self) -> Option<usize> {
-        let mut len = self.blocks.len();
-        if len == 0 {
-            return None;
-        }
-        if !self.status {
-            len -= 1;
-        }
-        for i in 0..len {
-            let block = self.blocks.get(i).expect("Must have block with this id");
-            if block.id != i as u64 || !block.validate_hash() {
-                return Some(i);
-            }
-        }
-        for i in 0..len {
-            if i == 0 {
-                continue;
-            }
-            let block = self.blocks.get(i).expect("Must have block with this id");
-            let prev = self
-                .blocks
-                .get(i - 1)
-                .expect("Must have block with this id");
+        self.find_all_errors().first().copied()
+    }
 
-            if block.prev != prev.hash {
-                return Some(i);
-            }
-        }
-        None
+    /// Returns the indexes of all invalid blocks in the chain, in increasing order: blocks with
+    /// a wrong id or hash, and blocks which don't point to the previous one.
+    ///
+    /// Like `have_errors`, the last block isn't checked while it's being mined.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::Chain;
+    ///
+    /// let mut chain = Chain::test_chain(5);
+    /// assert!(chain.find_all_errors().is_empty());
+    ///
+    /// chain.blocks[1].data = "Tampered".to_owned();
+    /// chain.blocks[4].prev = [0u8; 32];
+    /// chain.blocks[4].update_hash();
+    /// assert_eq!(chain.find_all_errors(), [1, 4]);
+    /// ```
+    pub fn find_all_errors(&self) -> Vec<usize> {
+        self.block_errors()
+            .into_iter()
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Checks only blocks with indexes in the given range, like `have_errors`.
+    ///
+    /// Long chains can be checked in parts this way, e.g. to stop checking in between.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::{Block, Chain};
+    ///
+    /// let mut chain = Chain::with_genesis("hash-net");
+    /// chain.blocks.push(Block::new(1, "Not linked".to_owned()));
+    ///
+    /// assert_eq!(chain.have_errors_in(0..1), None);
+    /// assert_eq!(chain.have_errors_in(1..2), Some(1));
+    /// ```
+    pub fn have_errors_in(&self, range: Range<usize>) -> Option<usize> {
+        // the tip being mined has no final hash yet
+        let mined = self.blocks.len().saturating_sub(usize::from(!self.status));
+        (range.start..range.end.min(mined)).find(|&i| self.block_error(i).is_some())
     }
 
     /// Attempts to add a new block to the chain. If the chain is currently in an invalid state,
@@ -133,6 +322,7 @@ impl Chain {
                 };
                 block.prev = prev;
                 block.id = self.blocks.len() as u64;
+                block.hash_algo = self.hash_algo;
                 self.blocks.push(block);
                 true
             }
@@ -141,12 +331,50 @@ impl Chain {
         }
     }
 
+    /// Returns the block which `try_add` would append next: the front of the queue with the next
+    /// id and the hash of the tip as `prev`, with zero nonce and no hash yet. The queue isn't
+    /// changed.
+    ///
+    /// Returns `None` if `try_add` wouldn't append a block: the queue is empty or the chain is
+    /// busy.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Block, Chain};
+    ///
+    /// let mut chain = Chain::with_genesis("hash-net");
+    /// assert!(chain.next_template().is_none());
+    ///
+    /// chain.add_queue(Block::new(0, "First".to_owned()));
+    /// let template = chain.next_template().unwrap();
+    /// assert_eq!(template.id, 1);
+    /// assert_eq!(template.prev, chain.blocks[0].hash);
+    /// assert_eq!(chain.queue.len(), 1);
+    /// ```
+    pub fn next_template(&self) -> Option<Block> {
+        if !self.status {
+            return None;
+        }
+        let mut block = self.queue.front()?.clone();
+        block.id = self.blocks.len() as u64;
+        block.prev = self.blocks.last().map_or([0u8; 32], |last| last.hash);
+        block.nonce = 0;
+        block.hash = [0u8; 32];
+        block.mining_attempts = None;
+        block.hash_algo = self.hash_algo;
+        Some(block)
+    }
+
     /// Adds a new block to the end of the queue.
     ///
     /// The ID of the block will be set to the sum of the number of blocks in the chain
     /// and the number of blocks in the queue. The block will be added to the back of the
     /// queue, waiting to be added to the chain by calling the `try_add()` method.
     ///
+    /// The ID is the one the block gets in the chain: it only changes if blocks are appended
+    /// ahead of the queue (see `append_block` and `restore_queue`), which renumbers the queue.
+    ///
     /// # Arguments
     ///
     /// * `block` - A `Block` instance representing the block to be added to the queue.
@@ -166,33 +394,1383 @@ impl Chain {
         block.id = (self.blocks.len() + self.queue.len()) as u64;
         self.queue.push_back(block);
     }
-}
 
-impl Default for Chain {
-    fn default() -> Self {
-        Chain::new()
+    /// Panics if the internal state of the chain is corrupted (see `invariant_error`), in debug
+    /// builds only.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Block, Chain};
+    ///
+    /// let mut chain = Chain::with_genesis("hash-net");
+    /// chain.add_queue(Block::new(0, "First".to_owned()));
+    /// chain.assert_invariants();
+    /// ```
+    pub fn assert_invariants(&self) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        if let Some(error) = self.invariant_error() {
+            panic!("{}", error);
+        }
     }
-}
 
-impl fmt::Display for Chain {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match write!(f, "Status: {}\r\n", self.status) {
-            Ok(_) => {}
-            Err(e) => return Err(e),
+    /// Returns the description of the first broken invariant of the internal state of the chain,
+    /// or `None` if the state is consistent.
+    ///
+    /// Checks that ids of blocks match their indices, that ids of queued blocks follow the chain,
+    /// that the genesis block has zero `prev`, and that the status matches the last block: a mined
+    /// last block has a valid hash, and a chain being mined isn't empty. Unlike `have_errors`,
+    /// links between blocks aren't checked.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Chain;
+    ///
+    /// let mut chain = Chain::test_chain(3);
+    /// assert_eq!(chain.invariant_error(), None);
+    ///
+    /// chain.blocks[0].prev = [1; 32];
+    /// assert_eq!(chain.invariant_error().unwrap(), "Genesis block has non-zero prev");
+    /// ```
+    pub fn invariant_error(&self) -> Option<String> {
+        for (index, block) in self.blocks.iter().enumerate() {
+            if block.id != index as u64 {
+                return Some(format!("Block #{} is at index {}", block.id, index));
+            }
         }
-        for block in self.blocks.iter() {
-            match write!(f, "{}\r\n", block) {
-                Ok(_) => {}
-                Err(e) => return Err(e),
+        for (index, block) in self.queue.iter().enumerate() {
+            let expected = (self.blocks.len() + index) as u64;
+            if block.id != expected {
+                return Some(format!(
+                    "Queued block #{} should be #{}",
+                    block.id, expected
+                ));
             }
         }
-        for block in self.queue.iter() {
-            match write!(f, "#-{} \"{}\'\r\n", block.id, block.data) {
-                Ok(_) => {}
-                Err(e) => return Err(e),
+        if let Some(genesis) = self.blocks.first() {
+            if genesis.prev != [0u8; 32] {
+                return Some(String::from("Genesis block has non-zero prev"));
+            }
+        }
+        match self.blocks.last() {
+            Some(last) if self.status && !last.validate_hash() => {
+                Some(format!("Mined last block #{} has wrong hash", last.id))
             }
+            None if !self.status => Some(String::from("Empty chain is being mined")),
+            _ => None,
         }
+    }
 
-        Ok(())
+    /// Moves the blocks starting from the given index back to the front of the queue, keeping
+    /// their order, so they will be mined again. The `status` of the chain isn't changed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Block, Chain};
+    ///
+    /// let mut chain = Chain::with_genesis("hash-net");
+    /// chain.add_queue(Block::new(0, "First".to_owned()));
+    /// assert!(chain.try_add());
+    ///
+    /// chain.rollback(1);
+    /// assert_eq!(chain.blocks.len(), 1);
+    /// assert_eq!(chain.queue[0].data, "First");
+    /// ```
+    pub fn rollback(&mut self, id: usize) {
+        for _ in id..self.blocks.len() {
+            let block = self.blocks.pop().unwrap();
+            self.queue.push_front(block);
+        }
+    }
+
+    /// Returns the ids of blocks waiting in the queue, in the order they are mined.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Block, Chain};
+    ///
+    /// let mut chain = Chain::with_genesis("hash-net");
+    /// chain.add_queue(Block::new(0, "First".to_owned()));
+    /// chain.add_queue(Block::new(0, "Second".to_owned()));
+    ///
+    /// assert_eq!(chain.pending_ids(), [1, 2]);
+    /// assert_eq!(chain.pending_data(), ["First", "Second"]);
+    /// ```
+    pub fn pending_ids(&self) -> Vec<u64> {
+        self.queue.iter().map(|block| block.id).collect()
+    }
+
+    /// Returns the data of blocks waiting in the queue, in the order they are mined.
+    pub fn pending_data(&self) -> Vec<&str> {
+        self.queue.iter().map(|block| block.data.as_str()).collect()
+    }
+
+    /// Empties the queue and returns its blocks in order, e.g. to hand them off on shutdown.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Block, Chain};
+    ///
+    /// let mut chain = Chain::with_genesis("hash-net");
+    /// chain.add_queue(Block::new(0, "First".to_owned()));
+    ///
+    /// let queue = chain.take_queue();
+    /// assert_eq!(queue[0].data, "First");
+    /// assert!(chain.queue.is_empty());
+    /// ```
+    pub fn take_queue(&mut self) -> VecDeque<Block> {
+        std::mem::take(&mut self.queue)
+    }
+
+    /// Puts blocks which weren't mined yet (see `take_queue` and `Node::drain_pending`) back to
+    /// the front of the queue, so they are mined when the chain is used again.
+    ///
+    /// Ids of queued blocks are renumbered to follow the chain.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::collections::VecDeque;
+    ///
+    /// use rustychain::{Block, Chain};
+    ///
+    /// let mut chain = Chain::with_genesis("hash-net");
+    /// chain.add_queue(Block::new(0, "Second".to_owned()));
+    /// chain.restore_queue(VecDeque::from([Block::new(7, "First".to_owned())]));
+    ///
+    /// assert_eq!(chain.queue[0].data, "First");
+    /// assert_eq!(chain.queue[0].id, 1);
+    /// assert_eq!(chain.queue[1].id, 2);
+    /// ```
+    pub fn restore_queue(&mut self, pending: VecDeque<Block>) {
+        for block in pending.into_iter().rev() {
+            self.queue.push_front(block);
+        }
+        let height = self.blocks.len();
+        for (index, block) in self.queue.iter_mut().enumerate() {
+            block.id = (height + index) as u64;
+        }
+    }
+
+    /// Returns the id of the first block in the chain which stores the given data, as `data` or
+    /// one of its other transactions.
+    ///
+    /// Only blocks of the chain are checked, queued blocks aren't recorded yet.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Block, Chain};
+    ///
+    /// let mut chain = Chain::with_genesis("hash-net");
+    /// chain.add_queue(Block::new(0, "First".to_owned()));
+    /// assert_eq!(chain.block_id_for_data("First"), None);
+    /// assert!(chain.try_add());
+    /// assert_eq!(chain.block_id_for_data("First"), Some(1));
+    /// ```
+    pub fn block_id_for_data(&self, data: &str) -> Option<u64> {
+        self.blocks
+            .iter()
+            .find(|b| b.transactions().any(|tx| tx == data))
+            .map(|b| b.id)
+    }
+
+    /// Checks if some block of the chain stores the given data as one of its transactions.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Chain;
+    ///
+    /// let chain = Chain::with_genesis("hash-net");
+    /// assert!(chain.contains_data("hash-net"));
+    /// assert!(!chain.contains_data("First"));
+    /// ```
+    pub fn contains_data(&self, data: &str) -> bool {
+        self.block_id_for_data(data).is_some()
+    }
+
+    /// Returns the block with the given id, or `None` if the chain has no such block.
+    ///
+    /// Blocks are stored at indexes equal to their ids, so a block at the index with another
+    /// id (in a chain with errors) isn't returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::Chain;
+    /// use rustychain::Block;
+    ///
+    /// let mut chain = Chain::new();
+    ///
+    /// assert!(chain.get_block(0).is_none());
+    ///
+    /// chain.blocks.push(Block::genesis("hash-net"));
+    /// assert_eq!(chain.get_block(0).unwrap().data, "hash-net");
+    /// assert!(chain.get_block(1).is_none());
+    /// ```
+    pub fn get_block(&self, id: u64) -> Option<&Block> {
+        let index = usize::try_from(id).ok()?;
+        self.blocks.get(index).filter(|block| block.id == id)
+    }
+
+    /// Returns the first block of the chain with the given hash, or `None` if there is no such
+    /// block.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::Chain;
+    /// use rustychain::Block;
+    ///
+    /// let mut chain = Chain::new();
+    /// let genesis = Block::genesis("hash-net");
+    ///
+    /// assert!(chain.get_block_by_hash(&genesis.hash).is_none());
+    ///
+    /// chain.blocks.push(genesis.clone());
+    /// assert_eq!(chain.get_block_by_hash(&genesis.hash).unwrap().id, 0);
+    /// assert!(chain.get_block_by_hash(&[0u8; 32]).is_none());
+    /// ```
+    pub fn get_block_by_hash(&self, hash: &[u8; 32]) -> Option<&Block> {
+        self.blocks.iter().find(|block| &block.hash == hash)
+    }
+
+    /// Checks that the hash of the block meets the difficulty recorded for the chain. Every block
+    /// meets an unknown (empty) difficulty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Block, Chain};
+    ///
+    /// let mut chain = Chain::with_genesis("hash-net");
+    /// let mut block = Block::new(1, "Data".to_owned());
+    /// block.update_hash();
+    /// assert!(chain.meets_difficulty(&block));
+    ///
+    /// chain.difficulty = block.string_hash()[62..].to_owned();
+    /// assert!(chain.meets_difficulty(&block));
+    /// chain.difficulty = String::from("zz");
+    /// assert!(!chain.meets_difficulty(&block));
+    /// ```
+    pub fn meets_difficulty(&self, block: &Block) -> bool {
+        self.difficulty_mode.matches(&block.hash, &self.difficulty)
+    }
+
+    /// Returns the difficulty for the next block, so blocks are mined every `target_secs` seconds
+    /// on average.
+    ///
+    /// The retargeting window is the last `RETARGET_WINDOW` intervals between timestamps of mined
+    /// blocks; blocks without a timestamp (like the genesis one) and the tip being mined aren't
+    /// counted. The difficulty is whole bytes (see `parse_difficulty`), and every byte makes a
+    /// block 256 times harder. So a byte of zeros is added at the far end of the difficulty
+    /// (see `difficulty_mode`) when the average block time is below `target_secs / 16`, and a
+    /// byte is removed when it's above `target_secs * 16`, but not the last one. Otherwise, or
+    /// with fewer than two timestamps in the window, the difficulty stays.
+    ///
+    /// The difficulty is recorded for the whole chain, so older blocks may not meet the new
+    /// one (see `ValidationPolicy::recorded_difficulty`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Chain;
+    ///
+    /// // blocks come every second
+    /// let mut chain = Chain::test_chain(6);
+    /// chain.difficulty = String::from("00");
+    ///
+    /// assert_eq!(chain.next_difficulty(60), "0000");
+    /// assert_eq!(chain.next_difficulty(1), "00");
+    /// ```
+    pub fn next_difficulty(&self, target_secs: u64) -> String {
+        let mined = self.blocks.len() - usize::from(!self.status && !self.blocks.is_empty());
+        let times: Vec<u64> = self.blocks[..mined]
+            .iter()
+            .map(|block| block.timestamp)
+            .filter(|&timestamp| timestamp > 0)
+            .collect();
+        let window = &times[times.len().saturating_sub(RETARGET_WINDOW + 1)..];
+        let average = match window {
+            [first, .., last] => average_block_time(*first, *last, window.len()),
+            _ => None,
+        };
+        let average = match average {
+            Some(average) => average,
+            None => return self.difficulty.clone(),
+        };
+        let target = target_secs as f64;
+
+        let mut difficulty = self.difficulty.clone();
+        if average < target / 16.0 && difficulty.len() + 2 <= 64 {
+            match self.difficulty_mode {
+                DifficultyMode::Prefix => difficulty.push_str("00"),
+                DifficultyMode::Suffix => difficulty.insert_str(0, "00"),
+            }
+        } else if average > target * 16.0 && difficulty.len() > 2 {
+            match self.difficulty_mode {
+                DifficultyMode::Prefix => difficulty.truncate(difficulty.len() - 2),
+                DifficultyMode::Suffix => difficulty = difficulty.split_off(2),
+            }
+        }
+        difficulty
+    }
+
+    /// Mines the last block of the chain again with a new nonce, so its hash has at least `bits`
+    /// zero bits at the mined end (see `Block::work_bits`) and still meets the recorded
+    /// difficulty. It takes about `2^bits` hashes.
+    ///
+    /// Nothing below the tip points to it, so the chain stays valid. A tip with enough work is
+    /// left as is. The genesis block isn't mined, so there must be a block after it, and the
+    /// chain must not be busy. Fails with `ChainError::ImpossibleWork` if no hash can have `bits`
+    /// zero bits and match the recorded difficulty at the mined end.
+    ///
+    /// This changes the tip under other nodes, so it's only for chains mined alone.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Chain, ChainError};
+    ///
+    /// let mut chain = Chain::test_chain(3);
+    /// chain.remine_tip(8).unwrap();
+    ///
+    /// assert!(chain.blocks[2].work_bits(chain.difficulty_mode) >= 8);
+    /// assert_eq!(chain.have_errors(), None);
+    /// assert_eq!(Chain::test_chain(1).remine_tip(8), Err(ChainError::NoTip));
+    /// ```
+    pub fn remine_tip(&mut self, bits: u32) -> Result<(), ChainError> {
+        if let Some(tip) = self.remine_job(bits)? {
+            let tip = remine(tip, bits, &self.difficulty, self.difficulty_mode);
+            *self.blocks.last_mut().unwrap() = tip;
+        }
+        Ok(())
+    }
+
+    /// Checks that the tip can be mined again for `bits` like `remine_tip` and returns a copy of
+    /// it to mine with `remine`, or `None` if the tip has enough work already.
+    pub(crate) fn remine_job(&self, bits: u32) -> Result<Option<Block>, ChainError> {
+        if !self.status {
+            return Err(ChainError::Busy);
+        }
+        let (difficulty, mode) = (&self.difficulty, self.difficulty_mode);
+        let tip = match self.blocks.last() {
+            Some(tip) if tip.id > 0 => tip,
+            _ => return Err(ChainError::NoTip),
+        };
+        if tip.work_bits(mode) >= bits && mode.matches(&tip.hash, difficulty) {
+            return Ok(None);
+        }
+        // every hex digit of the difficulty covers 4 bits from the mined end of the hash, which
+        // must be zero as far as `bits` reach
+        let digits: Vec<u32> = match mode {
+            DifficultyMode::Prefix => difficulty.chars().filter_map(|c| c.to_digit(16)).collect(),
+            DifficultyMode::Suffix => difficulty
+                .chars()
+                .rev()
+                .filter_map(|c| c.to_digit(16))
+                .collect(),
+        };
+        let reachable = bits <= 256
+            && digits.len() == difficulty.len()
+            && digits.iter().enumerate().all(|(i, digit)| {
+                let zeros = bits.saturating_sub(4 * i as u32).min(4);
+                let mask = match mode {
+                    DifficultyMode::Prefix => (0xf << (4 - zeros)) & 0xf,
+                    DifficultyMode::Suffix => (1 << zeros) - 1,
+                };
+                digit & mask == 0
+            });
+        if !reachable {
+            return Err(ChainError::ImpossibleWork(bits));
+        }
+        Ok(Some(tip.clone()))
+    }
+
+    /// Takes the chain of another node instead of this one, keeping the recorded difficulty if the
+    /// other chain has none, the difficulty mode and the hash function. Queued blocks are
+    /// numbered after the taken chain.
+    pub fn adopt(&mut self, other: Chain) {
+        let difficulty = std::mem::take(&mut self.difficulty);
+        let (mode, hash_algo) = (self.difficulty_mode, self.hash_algo);
+        *self = other;
+        // queued blocks are numbered after the chain here, whatever the other node sent
+        let queue = std::mem::take(&mut self.queue);
+        self.restore_queue(queue);
+        if self.difficulty.is_empty() {
+            self.difficulty = difficulty;
+        }
+        self.difficulty_mode = mode;
+        self.hash_algo = hash_algo;
+    }
+
+    /// Takes the chain of another node like `adopt`, but only if it passes the default
+    /// `ValidationPolicy`, and returns the replaced chain, so it can be kept or restored.
+    ///
+    /// On error this chain is left as is.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Chain, ChainError};
+    ///
+    /// let mut chain = Chain::test_chain(2);
+    /// let mut invalid = Chain::test_chain(4);
+    /// invalid.blocks[2].nonce += 1;
+    /// assert_eq!(chain.swap_remote(invalid).unwrap_err(), ChainError::InvalidHash(2));
+    /// assert_eq!(chain.blocks.len(), 2);
+    ///
+    /// let old = chain.swap_remote(Chain::test_chain(4)).unwrap();
+    /// assert_eq!(chain.blocks.len(), 4);
+    ///
+    /// chain.swap_remote(old).unwrap();
+    /// assert_eq!(chain.blocks.len(), 2);
+    /// ```
+    pub fn swap_remote(&mut self, mut candidate: Chain) -> Result<Chain, ChainError> {
+        // blocks hashed with another function than the local one are invalid
+        candidate.hash_algo = self.hash_algo;
+        candidate.verify_with_policy(&ValidationPolicy::default())?;
+        Ok(self.swap_validated(candidate))
+    }
+
+    /// Takes the chain of another node like `swap_remote`, but without checking it, and returns
+    /// the replaced chain.
+    ///
+    /// It's for a candidate already checked by the caller, e.g. in parts with
+    /// `verify_with_policy_in`, so a long chain isn't verified twice. Queued blocks are numbered
+    /// after the taken chain like in `adopt`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Chain, ValidationPolicy};
+    ///
+    /// let mut chain = Chain::test_chain(2);
+    /// let candidate = Chain::test_chain(4);
+    /// candidate.verify_with_policy(&ValidationPolicy::default()).unwrap();
+    ///
+    /// let old = chain.swap_validated(candidate);
+    /// assert_eq!(chain.blocks.len(), 4);
+    /// assert_eq!(old.blocks.len(), 2);
+    /// ```
+    pub fn swap_validated(&mut self, candidate: Chain) -> Chain {
+        let old = std::mem::replace(self, candidate);
+        // queued blocks are numbered after the chain here, whatever the other node sent
+        let queue = std::mem::take(&mut self.queue);
+        self.restore_queue(queue);
+        if self.difficulty.is_empty() {
+            self.difficulty = old.difficulty.clone();
+        }
+        self.difficulty_mode = old.difficulty_mode;
+        self.hash_algo = old.hash_algo;
+        old
+    }
+
+    /// Returns the blocks of the chain matching the predicate, in the order of the chain.
+    ///
+    /// Queued blocks aren't searched.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Chain;
+    ///
+    /// let chain = Chain::test_chain(4);
+    /// let even = chain.find_blocks(|block| block.id % 2 == 0);
+    /// assert_eq!(even.len(), 2);
+    /// assert_eq!(even[1].data, "Block 2");
+    /// ```
+    pub fn find_blocks(&self, pred: impl Fn(&Block) -> bool) -> Vec<&Block> {
+        self.blocks.iter().filter(|block| pred(block)).collect()
+    }
+
+    /// Drops the bodies of the mined blocks matching the predicate, keeping their headers (see
+    /// `Block::prune`), and returns how many blocks were pruned.
+    ///
+    /// The genesis block and the last mined block are kept whole: the genesis block identifies
+    /// the network and the last mined block commits to the hashes of the pruned ones, so the
+    /// chain still validates.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Chain;
+    ///
+    /// let mut chain = Chain::test_chain(5);
+    /// assert_eq!(chain.prune_bodies(|block| block.id < 3), 2);
+    ///
+    /// assert!(chain.blocks[1].pruned && chain.blocks[1].data.is_empty());
+    /// assert_eq!(chain.blocks[3].data, "Block 3");
+    /// assert_eq!(chain.have_errors(), None);
+    /// assert_eq!(chain.prune_bodies(|_| true), 1);
+    /// ```
+    pub fn prune_bodies(&mut self, pred: impl Fn(&Block) -> bool) -> usize {
+        let mined = self.blocks.len() - usize::from(!self.status && !self.blocks.is_empty());
+        let mut pruned = 0;
+        for block in self.blocks[..mined.saturating_sub(1)].iter_mut().skip(1) {
+            if !block.pruned && pred(block) {
+                block.prune();
+                pruned += 1;
+            }
+        }
+        pruned
+    }
+
+    /// Drops the bodies of the mined blocks created before `cutoff` (in Unix seconds) with
+    /// `prune_bodies`. Blocks without a timestamp are kept.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Chain;
+    ///
+    /// // timestamps of the test chain are the ids
+    /// let mut chain = Chain::test_chain(5);
+    /// assert_eq!(chain.prune_older_than(3), 2);
+    /// assert!(chain.blocks[2].pruned);
+    /// assert!(!chain.blocks[3].pruned);
+    /// ```
+    pub fn prune_older_than(&mut self, cutoff: u64) -> usize {
+        self.prune_bodies(|block| block.timestamp > 0 && block.timestamp < cutoff)
+    }
+
+    /// Returns the blocks of the chain with a transaction (see `Block::transactions`) containing
+    /// the substring, like `find_blocks`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Chain;
+    ///
+    /// let chain = Chain::test_chain(3);
+    /// assert_eq!(chain.search_data("Block").len(), 2);
+    /// assert!(chain.search_data("Nothing").is_empty());
+    /// ```
+    pub fn search_data(&self, needle: &str) -> Vec<&Block> {
+        self.find_blocks(|block| block.transactions().any(|data| data.contains(needle)))
+    }
+
+    /// Splits the chain at the given height.
+    ///
+    /// Returns a new chain with blocks from genesis up to `height` (inclusive) and the rest of
+    /// blocks detached from it. The new chain is ready for new blocks and has an empty queue.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Block, Chain};
+    ///
+    /// let mut chain = Chain::with_genesis("hash-net");
+    /// chain.add_queue(Block::new(0, "First".to_owned()));
+    /// assert!(chain.try_add());
+    ///
+    /// let (lower, rest) = chain.split_at(0);
+    /// assert_eq!(lower.blocks.len(), 1);
+    /// assert_eq!(rest[0].data, "First");
+    /// ```
+    pub fn split_at(&self, height: u64) -> (Chain, Vec<Block>) {
+        let at = (height as usize).saturating_add(1).min(self.blocks.len());
+        let (lower, rest) = self.blocks.split_at(at);
+        let chain = Chain {
+            blocks: lower.to_vec(),
+            status: true,
+            queue: VecDeque::new(),
+            difficulty: self.difficulty.clone(),
+            difficulty_mode: self.difficulty_mode,
+            hash_algo: self.hash_algo,
+        };
+        (chain, rest.to_vec())
+    }
+
+    /// Returns headers of all blocks of the chain, so light clients can check the linkage of blocks
+    /// without downloading their data.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{BlockHeader, Chain};
+    ///
+    /// let chain = Chain::with_genesis("hash-net");
+    /// let headers = chain.export_headers();
+    ///
+    /// assert_eq!(headers.len(), 1);
+    /// assert_eq!(BlockHeader::verify_linkage(&headers), Ok(()));
+    /// ```
+    pub fn export_headers(&self) -> Vec<BlockHeader> {
+        self.blocks.iter().map(BlockHeader::from).collect()
+    }
+
+    /// Checks that blocks of the chain match previously verified headers, one by one.
+    ///
+    /// This completes headers-first sync: headers are downloaded and checked with
+    /// `BlockHeader::verify_linkage` first, then bodies are downloaded and checked here. Every block
+    /// must have the same header and a hash matching its data, so pruned blocks are rejected.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Chain, ChainError};
+    ///
+    /// let chain = Chain::with_genesis("hash-net");
+    /// let headers = chain.export_headers();
+    /// assert_eq!(chain.verify_against_headers(&headers), Ok(()));
+    /// assert_eq!(chain.verify_against_headers(&[]), Err(ChainError::HeaderMismatch(0)));
+    /// ```
+    pub fn verify_against_headers(&self, headers: &[BlockHeader]) -> Result<(), ChainError> {
+        for i in 0..self.blocks.len().max(headers.len()) {
+            let (block, header) = match (self.blocks.get(i), headers.get(i)) {
+                (Some(block), Some(header)) => (block, header),
+                _ => return Err(ChainError::HeaderMismatch(i as u64)),
+            };
+            if &block.header() != header {
+                return Err(ChainError::HeaderMismatch(i as u64));
+            }
+            if block.pruned {
+                return Err(ChainError::PrunedBlock(i as u64));
+            }
+            if !self.is_hashed(block) {
+                return Err(ChainError::InvalidHash(i as u64));
+            }
+        }
+        Ok(())
+    }
+
+    /// Compares the tip of the chain with the tip of a peer, given by its height and hash.
+    ///
+    /// The tip is the last mined block: a block which is mined now isn't final yet. An empty
+    /// chain is behind any peer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Block, Chain, TipComparison};
+    ///
+    /// let chain = Chain::with_genesis("hash-net");
+    /// let genesis = Block::genesis("hash-net");
+    ///
+    /// assert_eq!(chain.tip_matches(0, &genesis.hash), TipComparison::Same);
+    /// assert_eq!(chain.tip_matches(1, &[0u8; 32]), TipComparison::Behind);
+    /// ```
+    pub fn tip_matches(&self, height: u64, hash: &[u8; 32]) -> TipComparison {
+        let mined = self.blocks.len() - usize::from(!self.status && !self.blocks.is_empty());
+        let tip = match mined.checked_sub(1) {
+            Some(i) => &self.blocks[i],
+            None => return TipComparison::Behind,
+        };
+        match tip.id.cmp(&height) {
+            Ordering::Less => TipComparison::Behind,
+            Ordering::Greater => TipComparison::Ahead,
+            Ordering::Equal if &tip.hash == hash => TipComparison::Same,
+            Ordering::Equal => TipComparison::DifferentHashSameHeight,
+        }
+    }
+
+    /// Returns the last `n` blocks of the chain, or all of them if there are fewer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Chain;
+    ///
+    /// let chain = Chain::with_genesis("hash-net");
+    ///
+    /// assert_eq!(chain.tail(5).len(), 1);
+    /// assert!(chain.tail(0).is_empty());
+    /// ```
+    pub fn tail(&self, n: usize) -> &[Block] {
+        &self.blocks[self.blocks.len().saturating_sub(n)..]
+    }
+
+    /// Returns the number of leading blocks which both chains have, i.e. the id of the first block
+    /// where they diverge.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Block, Chain};
+    ///
+    /// let mut chain = Chain::with_genesis("hash-net");
+    /// let other = chain.clone();
+    /// chain.add_queue(Block::new(0, "First".to_owned()));
+    /// assert!(chain.try_add());
+    ///
+    /// assert_eq!(chain.fork_point(&other), 1);
+    /// assert_eq!(chain.fork_point(&Chain::with_genesis("other-net")), 0);
+    /// ```
+    pub fn fork_point(&self, other: &Chain) -> usize {
+        self.blocks
+            .iter()
+            .zip(other.blocks.iter())
+            .take_while(|(local, remote)| local.equals(remote))
+            .count()
+    }
+
+    /// Returns the id of the last block which all the chains have, like `fork_point` for several
+    /// chains, or `None` if they don't even share the genesis block.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Block, Chain};
+    ///
+    /// let genesis = Chain::with_genesis("hash-net");
+    /// let mut chain = genesis.clone();
+    /// chain.add_queue(Block::new(0, "First".to_owned()));
+    /// assert!(chain.try_add());
+    ///
+    /// assert_eq!(Chain::common_ancestor(&[&chain, &genesis]), Some(0));
+    /// assert_eq!(Chain::common_ancestor(&[&chain, &Chain::with_genesis("other-net")]), None);
+    /// assert_eq!(Chain::common_ancestor(&[]), None);
+    /// ```
+    pub fn common_ancestor(chains: &[&Chain]) -> Option<u64> {
+        let (first, others) = chains.split_first()?;
+        let shared = others
+            .iter()
+            .map(|other| first.fork_point(other))
+            .min()
+            .unwrap_or(first.blocks.len());
+        shared.checked_sub(1).map(|id| id as u64)
+    }
+
+    /// Compares the chain with the chain of a peer: where they fork and which blocks each of
+    /// them has after the fork point.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Block, Chain};
+    ///
+    /// let mut chain = Chain::with_genesis("hash-net");
+    /// let other = chain.clone();
+    /// chain.add_queue(Block::new(0, "First".to_owned()));
+    /// assert!(chain.try_add());
+    ///
+    /// let diff = chain.diff(&other);
+    /// assert_eq!(diff.fork_point, 1);
+    /// assert_eq!(diff.only_local[0].data_preview, "First");
+    /// assert!(diff.only_remote.is_empty());
+    /// ```
+    pub fn diff(&self, remote: &Chain) -> ChainDiff {
+        let fork_point = self.fork_point(remote);
+        ChainDiff {
+            fork_point,
+            only_local: self.blocks[fork_point..]
+                .iter()
+                .map(Block::summary)
+                .collect(),
+            only_remote: remote.blocks[fork_point..]
+                .iter()
+                .map(Block::summary)
+                .collect(),
+        }
+    }
+
+    /// Summarizes the last `n` blocks of the chain for dashboards, oldest first.
+    ///
+    /// Every summary has the seconds since the previous block of the chain, even if that block
+    /// isn't summarized, unless one of them has no timestamp (like the genesis block).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Chain;
+    ///
+    /// let chain = Chain::with_genesis("hash-net");
+    /// let activity = chain.recent_activity(10);
+    ///
+    /// assert_eq!(activity.len(), 1);
+    /// assert_eq!(activity[0].data, "hash-net");
+    /// assert_eq!(activity[0].since_previous, None);
+    /// ```
+    pub fn recent_activity(&self, n: usize) -> Vec<ActivitySummary> {
+        let start = self.blocks.len().saturating_sub(n);
+        self.blocks[start..]
+            .iter()
+            .enumerate()
+            .map(|(offset, block)| {
+                let mut summary = ActivitySummary::from(block);
+                summary.since_previous = (start + offset)
+                    .checked_sub(1)
+                    .map(|prev| &self.blocks[prev])
+                    .filter(|prev| prev.timestamp > 0 && block.timestamp > 0)
+                    .map(|prev| block.timestamp.saturating_sub(prev.timestamp));
+                summary
+            })
+            .collect()
+    }
+
+    /// Computes aggregate metrics of the chain in one pass over its blocks.
+    ///
+    /// The average block time is taken between the first and the last mined blocks with
+    /// timestamps, so the genesis block and the tip being mined don't count.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Chain;
+    ///
+    /// let stats = Chain::new().stats();
+    /// assert_eq!(stats.height, 0);
+    /// assert_eq!(stats.total_work, 0.0);
+    ///
+    /// let stats = Chain::with_genesis("hash-net").stats();
+    /// assert_eq!(stats.height, 1);
+    /// assert_eq!(stats.total_data_bytes, "hash-net".len());
+    /// assert_eq!(stats.avg_block_time, None);
+    /// ```
+    pub fn stats(&self) -> ChainStats {
+        let mined = self.blocks.len() - usize::from(!self.status && !self.blocks.is_empty());
+        let mut stats = ChainStats {
+            height: mined,
+            total_blocks: self.blocks.len(),
+            queue_len: self.queue.len(),
+            total_work: self.total_work() as f64,
+            ..Default::default()
+        };
+        let mut timed = (None, 0, 0);
+        for (index, block) in self.blocks.iter().enumerate() {
+            stats.total_data_bytes += block.data.len();
+            if index < mined && block.timestamp > 0 {
+                let (first, _, count) = timed;
+                timed = (first.or(Some(block.timestamp)), block.timestamp, count + 1);
+            }
+        }
+        if let (Some(first), last, count) = timed {
+            stats.avg_block_time = average_block_time(first, last, count);
+        }
+        stats
+    }
+
+    /// Returns an estimate of the number of hashes spent on the mined blocks of the chain: 2 to
+    /// the power of zero bits at the mined end of the hash (see `Block::work_bits`) of every block
+    /// but the genesis one, so a chain of hard blocks outweighs a longer chain of easy ones.
+    ///
+    /// The tip being mined isn't counted, nor pruned blocks, whose work can't be checked. The sum
+    /// saturates at `u128::MAX`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Chain;
+    ///
+    /// let chain = Chain::test_chain(4);
+    /// let work: u128 = chain.blocks[1..]
+    ///     .iter()
+    ///     .map(|block| 1 << block.work_bits(chain.difficulty_mode))
+    ///     .sum();
+    /// assert_eq!(chain.total_work(), work);
+    /// assert_eq!(Chain::with_genesis("hash-net").total_work(), 0);
+    /// ```
+    pub fn total_work(&self) -> u128 {
+        let mined = self.blocks.len() - usize::from(!self.status && !self.blocks.is_empty());
+        self.blocks
+            .iter()
+            .take(mined)
+            .skip(1)
+            .filter(|block| !block.pruned)
+            .map(|block| {
+                1u128
+                    .checked_shl(block.work_bits(self.difficulty_mode))
+                    .unwrap_or(u128::MAX)
+            })
+            .fold(0, u128::saturating_add)
+    }
+
+    /// Returns a multi-line audit of the chain for support requests: the height and the status,
+    /// hashes of the genesis block and the tip, the total work (see `stats`), the recorded
+    /// difficulty, the queue depth and every invalid block with the reason.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Chain;
+    ///
+    /// let mut chain = Chain::test_chain(3);
+    /// assert!(chain.integrity_report().contains("errors: none"));
+    ///
+    /// chain.blocks[1].data = String::from("Tampered");
+    /// assert!(chain.integrity_report().contains("#1: block #1 has invalid hash"));
+    /// ```
+    pub fn integrity_report(&self) -> String {
+        let stats = self.stats();
+        let hash =
+            |block: Option<&Block>| block.map_or_else(|| String::from("none"), |b| b.string_hash());
+        let mut report = String::from("Integrity report\r\n");
+        // writing to a String never fails
+        let _ = write!(report, "height: {}\r\n", stats.height);
+        let _ = match (self.status, self.blocks.last()) {
+            (false, Some(block)) => write!(report, "status: mining #{}\r\n", block.id),
+            _ => write!(report, "status: ready\r\n"),
+        };
+        let _ = write!(report, "genesis: {}\r\n", hash(self.blocks.first()));
+        let tip = self.blocks.get(stats.height.wrapping_sub(1));
+        let _ = write!(report, "tip: {}\r\n", hash(tip));
+        let _ = write!(report, "work: {:.0} hashes\r\n", stats.total_work);
+        let _ = match self.difficulty.as_str() {
+            "" => write!(report, "difficulty: unknown\r\n"),
+            difficulty => write!(
+                report,
+                "difficulty: \"{}\" ({})\r\n",
+                difficulty, self.difficulty_mode
+            ),
+        };
+        let _ = write!(report, "queue: {}\r\n", stats.queue_len);
+        let errors = self.block_errors();
+        if errors.is_empty() {
+            let _ = write!(report, "errors: none\r\n");
+        } else {
+            let _ = write!(report, "errors: {}\r\n", errors.len());
+            for (index, error) in errors {
+                let _ = write!(report, "  #{}: {}\r\n", index, error);
+            }
+        }
+        report
+    }
+
+    /// Returns every invalid mined block by its index with the first reason, see
+    /// `find_all_errors`.
+    fn block_errors(&self) -> Vec<(usize, ChainError)> {
+        let mined = self.blocks.len().saturating_sub(usize::from(!self.status));
+        (0..mined)
+            .filter_map(|index| self.block_error(index).map(|error| (index, error)))
+            .collect()
+    }
+
+    /// Returns true if the block is hashed with the function of the chain and its hash is valid.
+    pub(crate) fn is_hashed(&self, block: &Block) -> bool {
+        block.hash_algo == self.hash_algo && block.validate_hash()
+    }
+
+    /// Returns true like `is_hashed`, or if the body of the block is pruned (see `prune_bodies`)
+    /// and a later mined block commits to its hash, which can't be checked itself.
+    fn is_hashed_or_pruned(&self, block: &Block) -> bool {
+        let mined = self.blocks.len() - usize::from(!self.status && !self.blocks.is_empty());
+        let covered = (block.id as usize).saturating_add(1) < mined;
+        block.hash_algo == self.hash_algo && ((block.pruned && covered) || block.validate_hash())
+    }
+
+    /// Returns the first reason why the block with the index is invalid: a wrong id, a wrong
+    /// hash or a broken link to the previous block.
+    fn block_error(&self, index: usize) -> Option<ChainError> {
+        let block = &self.blocks[index];
+        if block.id != index as u64 {
+            Some(ChainError::WrongId {
+                expected: index as u64,
+                found: block.id,
+            })
+        } else if !self.is_hashed_or_pruned(block) {
+            Some(ChainError::InvalidHash(block.id))
+        } else if index > 0 && block.prev != self.blocks[index - 1].hash {
+            Some(ChainError::InvalidPrev(block.id))
+        } else {
+            None
+        }
+    }
+
+    /// Renders the chain as a Graphviz DOT graph: a node for every block labeled with its id and
+    /// short hash, and an edge from every block to its `prev`. The block being mined is dashed.
+    ///
+    /// A block whose `prev` isn't in the chain has no edge, so a broken link is visible.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::Chain;
+    ///
+    /// let dot = Chain::test_chain(2).to_dot();
+    ///
+    /// assert!(dot.starts_with("digraph chain {"));
+    /// assert!(dot.contains("b1 -> b0;"));
+    /// ```
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph chain {\n    rankdir=RL;\n    node [shape=box];\n");
+        let busy = !self.status && !self.blocks.is_empty();
+        for (index, block) in self.blocks.iter().enumerate() {
+            let style = if busy && index + 1 == self.blocks.len() {
+                ", style=dashed"
+            } else {
+                ""
+            };
+            let label = format!("#{}\\n{}", block.id, block.short_hash());
+            let _ = writeln!(dot, "    b{} [label=\"{}\"{}];", index, label, style);
+        }
+        for (index, block) in self.blocks.iter().enumerate().skip(1) {
+            if let Some(prev) = self.blocks[..index]
+                .iter()
+                .rposition(|b| b.hash == block.prev)
+            {
+                let _ = writeln!(dot, "    b{} -> b{};", index, prev);
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Appends an already mined block to the end of the chain.
+    ///
+    /// Unlike `add_queue`, the block isn't changed: it must already have the next id, point to the
+    /// hash of the last block and have a valid hash. Difficulty isn't checked here. Queued blocks
+    /// are renumbered to follow the appended block.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Block, Chain, ChainError};
+    ///
+    /// let mut chain = Chain::new();
+    /// let mut block = Block::new(0, "Genesis".to_owned());
+    /// block.update_hash();
+    /// assert_eq!(chain.append_block(block.clone()), Ok(()));
+    ///
+    /// let mut next = Block::new(1, "Next".to_owned());
+    /// assert_eq!(chain.append_block(next.clone()), Err(ChainError::InvalidPrev(1)));
+    /// next.prev = block.hash;
+    /// next.update_hash();
+    /// assert_eq!(chain.append_block(next), Ok(()));
+    /// assert_eq!(chain.blocks.len(), 2);
+    /// ```
+    pub fn append_block(&mut self, block: Block) -> Result<(), ChainError> {
+        if !self.status {
+            return Err(ChainError::Busy);
+        }
+        let expected = self.blocks.len() as u64;
+        if block.id != expected {
+            return Err(ChainError::WrongId {
+                expected,
+                found: block.id,
+            });
+        }
+        let prev = match self.blocks.last() {
+            Some(a) => a.hash,
+            None => [0u8; 32],
+        };
+        if block.prev != prev {
+            return Err(ChainError::InvalidPrev(block.id));
+        }
+        if !self.is_hashed(&block) {
+            return Err(ChainError::InvalidHash(block.id));
+        }
+        self.blocks.push(block);
+        self.restore_queue(VecDeque::new());
+        Ok(())
+    }
+
+    /// Replaces the last block with the same block (see `Block::preequals`) which has another hash
+    /// and nonce, if `better(&block, &last)` returns true.
+    ///
+    /// Only the hash, the nonce and `mining_attempts` of the last block change; the hash of the
+    /// block isn't checked, and `status` is left to the caller. Returns true if the last block is
+    /// replaced.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Block, Chain};
+    ///
+    /// let mut chain = Chain::with_genesis("hash-net");
+    /// let mut block = chain.blocks[0].clone();
+    /// block.nonce = 42;
+    /// block.update_hash();
+    ///
+    /// assert!(!chain.replace_tip_if(block.clone(), |_, _| false));
+    /// assert!(chain.replace_tip_if(block, |_, _| true));
+    /// assert_eq!(chain.blocks[0].nonce, 42);
+    /// assert_eq!(chain.have_errors(), None);
+    /// ```
+    pub fn replace_tip_if(
+        &mut self,
+        block: Block,
+        better: impl Fn(&Block, &Block) -> bool,
+    ) -> bool {
+        match self.blocks.last_mut() {
+            Some(last) if block.preequals(last) && better(&block, last) => {
+                last.hash = block.hash;
+                last.nonce = block.nonce;
+                last.mining_attempts = block.mining_attempts;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Checks that the blocks could be appended to the chain one by one with `append_block`,
+    /// without changing the chain.
+    ///
+    /// Only the given range is checked: the first block must follow the tip of the chain, and
+    /// every next block must follow the previous one. The existing blocks aren't revalidated.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Block, Chain, ChainError};
+    ///
+    /// let chain = Chain::with_genesis("hash-net");
+    /// let mut block = Block::new(1, "Next".to_owned());
+    /// assert_eq!(chain.validate_range(&[block.clone()]), Err(ChainError::InvalidPrev(1)));
+    ///
+    /// block.prev = chain.blocks[0].hash;
+    /// block.update_hash();
+    /// assert_eq!(chain.validate_range(&[block]), Ok(()));
+    /// ```
+    pub fn validate_range(&self, range: &[Block]) -> Result<(), ChainError> {
+        if !self.status {
+            return Err(ChainError::Busy);
+        }
+        let mut prev = match self.blocks.last() {
+            Some(a) => a.hash,
+            None => [0u8; 32],
+        };
+        for (i, block) in range.iter().enumerate() {
+            let expected = (self.blocks.len() + i) as u64;
+            if block.id != expected {
+                return Err(ChainError::WrongId {
+                    expected,
+                    found: block.id,
+                });
+            }
+            if block.prev != prev {
+                return Err(ChainError::InvalidPrev(block.id));
+            }
+            if !self.is_hashed(block) {
+                return Err(ChainError::InvalidHash(block.id));
+            }
+            prev = block.hash;
+        }
+        Ok(())
+    }
+
+    /// Runs the checks enabled in the policy on the mined blocks of the chain and returns the first
+    /// error found.
+    ///
+    /// Cheap checks run first: the genesis block and checkpoints, the linkage, timestamps and
+    /// the work of blocks, and hashes of blocks last.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Chain, ChainError, ValidationPolicy};
+    ///
+    /// let mut chain = Chain::test_chain(3);
+    /// let policy = ValidationPolicy::default();
+    /// assert_eq!(chain.verify_with_policy(&policy), Ok(()));
+    ///
+    /// chain.blocks[2].data = String::from("Tampered");
+    /// assert_eq!(chain.verify_with_policy(&policy), Err(ChainError::InvalidHash(2)));
+    ///
+    /// let policy = ValidationPolicy {
+    ///     proof_of_work: false,
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(chain.verify_with_policy(&policy), Ok(()));
+    /// ```
+    pub fn verify_with_policy(&self, policy: &ValidationPolicy) -> Result<(), ChainError> {
+        self.verify_with_policy_in(policy, 0..self.blocks.len())
+    }
+
+    /// Checks only blocks with indexes in the given range, like `verify_with_policy`.
+    ///
+    /// Long chains can be checked in parts this way, e.g. to stop checking in between.
+    pub fn verify_with_policy_in(
+        &self,
+        policy: &ValidationPolicy,
+        range: Range<usize>,
+    ) -> Result<(), ChainError> {
+        // The tip of a busy chain has no final hash yet
+        let mined = self.blocks.len().saturating_sub(usize::from(!self.status));
+        let blocks = &self.blocks[range.start.min(mined)..range.end.min(mined)];
+
+        for block in blocks {
+            if block.id == 0 && policy.genesis.is_some_and(|hash| hash != block.hash) {
+                return Err(ChainError::GenesisMismatch);
+            }
+            if policy
+                .checkpoints
+                .get(&block.id)
+                .is_some_and(|hash| hash != &block.hash)
+            {
+                return Err(ChainError::CheckpointMismatch(block.id));
+            }
+        }
+        if policy.linkage {
+            for (i, block) in (range.start..).zip(blocks) {
+                if block.id != i as u64 {
+                    return Err(ChainError::WrongId {
+                        expected: i as u64,
+                        found: block.id,
+                    });
+                }
+                if i > 0 && block.prev != self.blocks[i - 1].hash {
+                    return Err(ChainError::InvalidPrev(block.id));
+                }
+            }
+        }
+        if let Some(drift) = policy.max_time_drift {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs());
+            if let Some(block) = blocks
+                .iter()
+                .find(|block| block.timestamp > now.saturating_add(drift))
+            {
+                return Err(ChainError::FutureTimestamp(block.id));
+            }
+        }
+        let mut work = blocks.iter().filter(|block| block.id > 0);
+        if let Some(min) = policy.min_difficulty_bits {
+            let mode = self.difficulty_mode;
+            if let Some(block) = work.clone().find(|block| block.work_bits(mode) < min) {
+                return Err(ChainError::LowDifficulty(block.id));
+            }
+        }
+        if policy.recorded_difficulty {
+            if let Some(block) = work.find(|block| !self.meets_difficulty(block)) {
+                return Err(ChainError::LowDifficulty(block.id));
+            }
+        }
+        if policy.proof_of_work {
+            // the body of a block pruned elsewhere might have never existed
+            if let Some(block) = blocks.iter().find(|block| block.pruned) {
+                return Err(ChainError::PrunedBlock(block.id));
+            }
+            if let Some(block) = blocks.iter().find(|block| !self.is_hashed(block)) {
+                return Err(ChainError::InvalidHash(block.id));
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the chain to the file in the given format, preceded by a header line with the
+    /// length and the SHA256 checksum of the encoded chain.
+    ///
+    /// Bincode chains start with a magic byte, so `load_from_file` detects the format itself.
+    /// The chain is written to a temporary file first and then renamed, so the file is never
+    /// left partially written.
+    pub fn save_to_file(&self, path: &Path, format: DbFormat) -> Result<(), ChainError> {
+        let body = match format {
+            DbFormat::Json => serde_json::to_vec(self).expect("Chain is serializible"),
+            DbFormat::Bincode => {
+                let mut body = vec![DbFormat::BINCODE_MAGIC];
+                bincode::serialize_into(&mut body, self).expect("Chain is serializible");
+                body
+            }
+        };
+        let mut content = format!(
+            "{} {} {}\n",
+            FILE_MAGIC,
+            body.len(),
+            hex::encode(Sha256::digest(&body))
+        )
+        .into_bytes();
+        content.extend_from_slice(&body);
+
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, content).map_err(|e| ChainError::Io(e.to_string()))?;
+        fs::rename(&tmp, path).map_err(|e| ChainError::Io(e.to_string()))
+    }
+
+    /// Reads the chain written by `save_to_file` in any format.
+    ///
+    /// The length and the checksum are checked before decoding, so a truncated or corrupted file
+    /// is rejected with `ChainError::ChecksumMismatch`. The decoded chain is checked with
+    /// `verify_with_policy`, so a chain with invalid or pruned blocks is rejected with the error of
+    /// the first one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Chain, DbFormat};
+    ///
+    /// let path = std::env::temp_dir().join("rustychain-doc-chain.json");
+    /// let chain = Chain::with_genesis("hash-net");
+    /// chain.save_to_file(&path, DbFormat::Json).unwrap();
+    ///
+    /// let loaded = Chain::load_from_file(&path).unwrap();
+    /// assert!(loaded.blocks[0].equals(&chain.blocks[0]));
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn load_from_file(path: &Path) -> Result<Chain, ChainError> {
+        let content = fs::read(path).map_err(|e| ChainError::Io(e.to_string()))?;
+        let split = content
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or(ChainError::ChecksumMismatch)?;
+        let (header, body) = (&content[..split], &content[split + 1..]);
+
+        let header = std::str::from_utf8(header).map_err(|_| ChainError::ChecksumMismatch)?;
+        let mut fields = header.split(' ');
+        if fields.next() != Some(FILE_MAGIC) {
+            return Err(ChainError::Decode(String::from("not a chain file")));
+        }
+        let len: Option<usize> = fields.next().and_then(|len| len.parse().ok());
+        let checksum = fields.next();
+        if len != Some(body.len()) || checksum != Some(&hex::encode(Sha256::digest(body))) {
+            return Err(ChainError::ChecksumMismatch);
+        }
+        let chain: Chain = match DbFormat::detect(body) {
+            Some(DbFormat::Json) => {
+                serde_json::from_slice(body).map_err(|e| ChainError::Decode(e.to_string()))?
+            }
+            Some(DbFormat::Bincode) => {
+                bincode::deserialize(&body[1..]).map_err(|e| ChainError::Decode(e.to_string()))?
+            }
+            None => return Err(ChainError::Decode(String::from("unknown chain format"))),
+        };
+        chain.verify_with_policy(&ValidationPolicy::default())?;
+        Ok(chain)
+    }
+}
+
+impl Default for Chain {
+    fn default() -> Self {
+        Chain::new()
+    }
+}
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match write!(f, "Status: {}\r\n", self.status) {
+            Ok(_) => {}
+            Err(e) => return Err(e),
+        }
+        for block in self.blocks.iter() {
+            match write!(f, "{}\r\n", block) {
+                Ok(_) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        for block in self.queue.iter() {
+            match write!(f, "#-{} \"{}\'\r\n", block.id, block.data) {
+                Ok(_) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the average number of seconds between `count` blocks created from `first` to `last`,
+/// or `None` with fewer than two blocks.
+fn average_block_time(first: u64, last: u64, count: usize) -> Option<f64> {
+    (count > 1).then(|| last.saturating_sub(first) as f64 / (count - 1) as f64)
+}
+
+/// Tries nonces of the block in order until its hash has at least `bits` bits of work (see
+/// `Block::work_bits`) and meets the difficulty, see `Chain::remine_job`.
+pub(crate) fn remine(mut tip: Block, bits: u32, difficulty: &str, mode: DifficultyMode) -> Block {
+    for nonce in 0.. {
+        tip.nonce = nonce;
+        tip.hash = tip.hash_with_nonce(nonce);
+        if tip.work_bits(mode) >= bits && mode.matches(&tip.hash, difficulty) {
+            break;
+        }
     }
+    tip
 }
@@ -0,0 +1,34 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Aggregate metrics of a chain, see `Chain::stats`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChainStats {
+    /// The number of mined blocks, including the genesis block.
+    pub height: usize,
+    /// The number of blocks, including the one being mined.
+    pub total_blocks: usize,
+    /// The number of blocks waiting in the queue.
+    pub queue_len: usize,
+    /// The expected number of hashes tried to mine the mined blocks, see `Chain::total_work`.
+    pub total_work: f64,
+    /// The total size of data of the blocks in bytes.
+    pub total_data_bytes: usize,
+    /// The average number of seconds between mined blocks with timestamps, if there are two.
+    pub avg_block_time: Option<f64>,
+}
+
+impl fmt::Display for ChainStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "height: {}\r\n", self.height)?;
+        write!(f, "blocks: {}\r\n", self.total_blocks)?;
+        write!(f, "queue: {}\r\n", self.queue_len)?;
+        write!(f, "work: {:.0} hashes\r\n", self.total_work)?;
+        write!(f, "data: {} bytes\r\n", self.total_data_bytes)?;
+        match self.avg_block_time {
+            Some(secs) => write!(f, "block time: {:.1} s\r\n", secs),
+            None => write!(f, "block time: unknown\r\n"),
+        }
+    }
+}
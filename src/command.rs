@@ -0,0 +1,168 @@
+use core::str::FromStr;
+
+use crate::{Block, CommandError};
+
+/// The commands with their usage and description, as listed by `help`.
+const COMMANDS: &[(&str, &str)] = &[
+    ("help", "list the commands"),
+    ("=<transaction>", "submit the transaction to the network"),
+    ("ls", "print the chain"),
+    (
+        "ls queue",
+        "print ids and data of blocks waiting to be mined",
+    ),
+    ("headers", "print headers of blocks as JSON"),
+    ("dump --report", "print the integrity report of the chain"),
+    ("dump --dot", "print the chain as a Graphviz DOT graph"),
+    ("peers", "print discovered peers and their addresses"),
+    (
+        "compare <peer>",
+        "compare the chain with the chain of the peer",
+    ),
+    ("debug", "print the internal state of the node"),
+    (
+        "stats",
+        "print message counters and the summary of the chain",
+    ),
+    (
+        "cancel-sync",
+        "cancel checking the chain received from another node",
+    ),
+    (
+        "inject <hex>",
+        "append an already mined block encoded by Block::to_hex",
+    ),
+    (
+        "remine <bits>",
+        "mine the tip again for more work, with --solo-mining only",
+    ),
+    ("exit", "stop the node and exit"),
+];
+
+/// A command typed by the host operator into the terminal of the node.
+///
+/// # Examples
+///
+/// ```
+/// use rustychain::{Command, CommandError};
+///
+/// assert!(matches!("ls queue".parse(), Ok(Command::LsQueue)));
+/// assert!(matches!("=Some data".parse(), Ok(Command::Transaction(data)) if data == "Some data"));
+/// assert_eq!(
+///     "rm".parse::<Command>().unwrap_err(),
+///     CommandError::Unknown(String::from("rm"))
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub enum Command {
+    /// Lists the commands, see `Command::help`.
+    Help,
+    /// Submits the transaction to the network.
+    Transaction(String),
+    /// Prints the chain.
+    Ls,
+    /// Prints the blocks waiting to be mined.
+    LsQueue,
+    /// Prints headers of blocks as JSON.
+    Headers,
+    /// Prints the integrity report of the chain, see `Chain::integrity_report`.
+    DumpReport,
+    /// Prints the chain as a Graphviz DOT graph, see `Chain::to_dot`.
+    DumpDot,
+    /// Prints discovered peers.
+    Peers,
+    /// Compares the chain with the chain of the peer with the given id.
+    Compare(String),
+    /// Prints the internal state of the node.
+    Debug,
+    /// Prints message counters and the summary of the chain.
+    Stats,
+    /// Cancels checking the chain received from another node.
+    CancelSync,
+    /// Appends the already mined block to the chain.
+    Inject(Block),
+    /// Mines the tip again for at least this many bits of work (see `Block::work_bits`).
+    Remine(u32),
+    /// Stops the node.
+    Exit,
+}
+
+impl Command {
+    /// Returns the list of commands with their descriptions, one per line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::Command;
+    ///
+    /// assert!(Command::help().contains("compare <peer>"));
+    /// ```
+    pub fn help() -> String {
+        let width = COMMANDS
+            .iter()
+            .map(|(usage, _)| usage.len())
+            .max()
+            .unwrap_or(0);
+        let mut help = String::from("Commands:\r\n");
+        for (usage, description) in COMMANDS {
+            help.push_str(&format!("  {usage:<width$}  {description}\r\n"));
+        }
+        help
+    }
+}
+
+impl FromStr for Command {
+    type Err = CommandError;
+
+    /// Parses the line typed by the operator. The data of a transaction is taken as is, other
+    /// commands may be surrounded by whitespace.
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        if let Some(data) = line.strip_prefix('=') {
+            if data.is_empty() {
+                return Err(invalid("=", "the transaction is empty"));
+            }
+            return Ok(Command::Transaction(data.to_owned()));
+        }
+        let line = line.trim();
+        let (name, arg) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let arg = arg.trim();
+        let command = match (name, arg) {
+            ("help", "") => Command::Help,
+            ("ls", "") => Command::Ls,
+            ("ls", "queue") => Command::LsQueue,
+            ("ls", _) => return Err(invalid("ls", "expected nothing or queue")),
+            ("headers", "") => Command::Headers,
+            ("dump", "--report") => Command::DumpReport,
+            ("dump", "--dot") => Command::DumpDot,
+            ("dump", _) => return Err(invalid("dump", "expected --report or --dot")),
+            ("peers", "") => Command::Peers,
+            ("compare", "") => return Err(invalid("compare", "the peer id is missing")),
+            ("compare", peer) => Command::Compare(peer.to_owned()),
+            ("debug", "") => Command::Debug,
+            ("stats", "") => Command::Stats,
+            ("cancel-sync", "") => Command::CancelSync,
+            ("inject", encoded) => match Block::from_hex(encoded) {
+                Ok(block) => Command::Inject(block),
+                Err(e) => return Err(invalid("inject", &e.to_string())),
+            },
+            ("remine", bits) => match bits.parse() {
+                Ok(bits) => Command::Remine(bits),
+                Err(e) => return Err(invalid("remine", &format!("can't parse bits: {e}"))),
+            },
+            ("exit", "") => Command::Exit,
+            _ if COMMANDS.iter().any(|(usage, _)| usage == &name) => {
+                return Err(invalid(name, "the command takes no arguments"))
+            }
+            _ => return Err(CommandError::Unknown(name.to_owned())),
+        };
+        Ok(command)
+    }
+}
+
+/// Returns the error of the command with invalid arguments.
+fn invalid(command: &str, reason: &str) -> CommandError {
+    CommandError::InvalidArgument {
+        command: command.to_owned(),
+        reason: reason.to_owned(),
+    }
+}
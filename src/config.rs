@@ -0,0 +1,154 @@
+use core::fmt;
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use crate::{Block, DifficultyMode, MemoryLimits, ValidationPolicy};
+
+/// A callback invoked by a `Node` for every block appended to its chain, see
+/// `NodeConfig::on_block`.
+///
+/// # Examples
+///
+/// ```
+/// use rustychain::{Block, BlockHook};
+///
+/// let hook = BlockHook::new(|block: &Block| println!("Block #{}", block.id));
+/// hook.call(&Block::new(1, "Some data".to_owned()));
+/// ```
+#[derive(Clone)]
+pub struct BlockHook(Arc<dyn Fn(&Block) + Send + Sync>);
+
+impl BlockHook {
+    /// Wraps the callback.
+    pub fn new(hook: impl Fn(&Block) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(hook))
+    }
+
+    /// Invokes the callback with the block.
+    pub fn call(&self, block: &Block) {
+        (self.0)(block)
+    }
+}
+
+impl fmt::Debug for BlockHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BlockHook")
+    }
+}
+
+/// What a `Node` does when its worker doesn't mine a block within `NodeConfig::mining_timeout`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StallPolicy {
+    /// Sends the block to the worker again, in case the job was lost.
+    #[default]
+    Redispatch,
+    /// Sends the block to the worker again with the difficulty shortened by one byte (but not
+    /// below one byte). The lowered difficulty is kept for the next blocks.
+    LowerDifficulty,
+}
+
+/// Optional settings of a `Node`. The default config keeps the node's original behaviour.
+///
+/// # Examples
+///
+/// ```
+/// use std::{path::PathBuf, time::Duration};
+///
+/// use rustychain::NodeConfig;
+///
+/// let config = NodeConfig {
+///     archive_dir: Some(PathBuf::from("archive")),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct NodeConfig {
+    /// The directory where every block appended to the chain is written to its own file.
+    pub archive_dir: Option<PathBuf>,
+    /// Whether the node is ready without connected peers, see `Health::is_ready`.
+    pub solo: bool,
+    /// The file every message received and sent by the node is recorded to, see `MessageLog`.
+    pub record: Option<PathBuf>,
+    /// Whether blocks submitted by the host (`Message::SubmitBlock`) are queued when they come
+    /// back from the network as `Message::NewBlock`, or after a couple of seconds if they don't,
+    /// instead of immediately.
+    pub defer_own: bool,
+    /// The minimum interval between appending blocks from the queue to the chain.
+    pub min_block_interval: Option<Duration>,
+    /// The tag of the network which is set as `Block::salt` of mined blocks.
+    pub network_id: String,
+    /// The maximum number of blocks from one peer (see `Message::Envelope`) in the queue.
+    pub max_queued_per_peer: Option<usize>,
+    /// The number of confirmations of blocks submitted by the host to emit `NodeEvent::Confirmed`.
+    pub confirmations: Option<u64>,
+    /// How long the node waits for the chain from other nodes on start before it starts mining.
+    pub initial_sync: Option<Duration>,
+    /// The delay before the chain request is repeated during the initial sync. Every next delay
+    /// is doubled up to a minute, with jitter (see `Backoff`).
+    pub sync_retry: Option<Duration>,
+    /// The number of threads to mine blocks on with `parallel_worker`, instead of `nonce_worker`.
+    pub mining_threads: Option<usize>,
+    /// The number of `nonce_worker` tasks trying random nonces of the same block (see
+    /// `nonce_workers`), one if not set. It's ignored when mining runs on `parallel_worker`.
+    ///
+    /// It's the worker count of the node: like the other mining options it's a setting rather
+    /// than a parameter of `Node::new`, so existing callers of the constructor don't change.
+    pub nonce_workers: Option<usize>,
+    /// The nice level of mining threads (see `set_thread_nice`), e.g. 19 to mine only when the
+    /// CPU is otherwise idle. Mining runs on `parallel_worker` then, with one thread unless
+    /// `mining_threads` is set.
+    pub mining_nice: Option<i32>,
+    /// Whether the node answers every block from `Message::NewBlock` it puts into the queue with
+    /// `Message::Ack`.
+    pub ack_new_blocks: bool,
+    /// How many ids ahead of the tip mined blocks from other nodes are kept in an `OrphanPool`
+    /// until the chain catches up with them, instead of being dropped.
+    pub orphan_window: Option<u64>,
+    /// The maximum size of data of a new block in bytes; larger blocks are rejected.
+    pub max_block_size: Option<usize>,
+    /// Whether the mined last block is kept when another node mines the same block with a lower
+    /// hash, instead of being replaced.
+    pub prefer_first_seen: bool,
+    /// The minimum number of zero bits at the mined end of hashes of mined blocks from other
+    /// nodes (see `Block::work_bits`); blocks and chains with less work are rejected.
+    pub min_difficulty_bits: Option<u32>,
+    /// The checks chains from other nodes must pass to be taken (see `Chain::verify_with_policy`);
+    /// by default the linkage and the proof of work of blocks. Chains are taken with
+    /// `Chain::swap_remote`, which checks those two anyway.
+    pub validation: ValidationPolicy,
+    /// The average time between blocks the node aims for: after every mined block the difficulty
+    /// is recomputed with `Chain::next_difficulty`. The difficulty stays as is if not set.
+    pub target_block_time: Option<Duration>,
+    /// Which end of hashes of mined blocks must match the difficulty. It's recorded in the chain
+    /// (see `Chain::difficulty_mode`), so all nodes of the network must use the same mode.
+    pub difficulty_mode: DifficultyMode,
+    /// Whether the node mines only its own chain: mined blocks and chains from other nodes are
+    /// ignored and the initial sync is skipped. The node is ready without connected peers then.
+    pub solo_mining: bool,
+    /// How long bodies of mined blocks are kept: the node periodically drops data of blocks
+    /// created longer ago, keeping their headers (see `Chain::prune_older_than`). Other nodes and
+    /// chain files don't accept a chain with pruned blocks, since their hashes can't be checked.
+    pub retention: Option<Duration>,
+    /// The interval of `NodeEvent::Heartbeat`, which is also logged as a summary of the node's
+    /// state.
+    pub heartbeat: Option<Duration>,
+    /// The limits of the chain and the queue kept in memory.
+    pub limits: MemoryLimits,
+    /// How long the queue may stay empty before the node mines a block with empty data, which
+    /// keeps the chain growing without transactions.
+    pub empty_block_interval: Option<Duration>,
+    /// The number of connected peers (see `Health::peers`) needed to start mining the next block.
+    pub min_peers_to_mine: Option<usize>,
+    /// The number of blocks to mine before the node stops, as if it's shut down. Blocks with
+    /// empty data are mined when the queue is empty.
+    pub mine_blocks: Option<u64>,
+    /// How long the worker may mine a block before the node considers mining stalled, emits
+    /// `NodeEvent::MiningStalled` and recovers with `stall_policy`.
+    pub mining_timeout: Option<Duration>,
+    /// What the node does when mining is stalled, see `mining_timeout`.
+    pub stall_policy: StallPolicy,
+    /// The callback invoked synchronously in the node loop for every block which gets its final
+    /// hash in the chain: mined by the node, taken from another node or injected. When a chain
+    /// from another node is taken, only blocks after the fork point are reported; the removed
+    /// ones are reported by `NodeEvent::Reorg`.
+    pub on_block: Option<BlockHook>,
+}
@@ -0,0 +1,207 @@
+use log::{info, warn};
+
+use crate::{Block, Chain, ChainError, Message, RejectKind, ValidationPolicy};
+
+impl Chain {
+    /// Applies the message received from another node to the chain and returns the messages to
+    /// send in reply.
+    ///
+    /// This is the consensus core of `Node`: it only decides what happens to the chain, without
+    /// channels, mining or timers, so the protocol can be tested synchronously.
+    ///
+    /// - `Message::NewBlock` and `Message::SubmitBlock` put the block into the queue.
+    /// - `Message::ChainRequest` is answered with the chain.
+    /// - `Message::ChainResponce` replaces the chain if the received one has more work (see
+    ///   `total_work`), or as much work and more blocks; a chain failing the default
+    ///   `ValidationPolicy`, e.g. with pruned blocks, is answered with `Message::Reject`, see
+    ///   `apply_message_with_policy`.
+    /// - `Message::InjectBlock` appends the mined block and announces it as `Message::MinedBlock`.
+    /// - `Message::MinedBlock` replaces the last block with a remote one with the same id, if the
+    ///   last block isn't mined yet or the remote hash is lower. A block which doesn't meet the
+    ///   difficulty of the chain (see `Chain::meets_difficulty`) is answered with
+    ///   `Message::Reject`. A block ahead of the chain or
+    ///   errors in the chain make it request the chain of other nodes.
+    /// - `Message::BlockRequest` is answered with the block with the id, if the chain has it.
+    /// - `Message::BlockResponse` appends the block if it extends the tip (see `append_block`)
+    ///   and meets the difficulty of the chain; other blocks are ignored.
+    /// - `Message::Envelope` applies the wrapped message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::{Block, Chain, Message};
+    ///
+    /// let mut chain = Chain::with_genesis("doc");
+    /// let out = chain.apply_message(&Message::NewBlock(Block::new(0, String::from("Some data"))));
+    /// assert!(out.is_empty());
+    /// assert_eq!(chain.queue.len(), 1);
+    ///
+    /// let out = chain.apply_message(&Message::ChainRequest);
+    /// assert!(matches!(&out[..], [Message::ChainResponce(_)]));
+    /// ```
+    pub fn apply_message(&mut self, msg: &Message) -> Vec<Message> {
+        self.apply_message_with_policy(msg, &ValidationPolicy::default())
+    }
+
+    /// Applies the message like `apply_message`, but checks chains from other nodes with the
+    /// given policy (see `verify_with_policy`) instead of the default one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Chain, Message, ValidationPolicy};
+    ///
+    /// let mut chain = Chain::test_chain(2);
+    /// let policy = ValidationPolicy {
+    ///     genesis: Some(chain.blocks[0].hash),
+    ///     ..Default::default()
+    /// };
+    /// let other = Chain::test_chain(4);
+    /// assert!(chain.apply_message_with_policy(&Message::ChainResponce(other), &policy).is_empty());
+    /// assert_eq!(chain.blocks.len(), 4);
+    /// ```
+    pub fn apply_message_with_policy(
+        &mut self,
+        msg: &Message,
+        policy: &ValidationPolicy,
+    ) -> Vec<Message> {
+        match msg {
+            Message::NewBlock(block) | Message::SubmitBlock(block) => {
+                self.add_queue(block.clone());
+                Vec::new()
+            }
+            Message::ChainRequest => vec![Message::ChainResponce(self.clone())],
+            Message::ChainResponce(chain) => self
+                .apply_chain(chain.clone(), policy, &|| false)
+                .unwrap_or_default(),
+            Message::InjectBlock(block) => match self.append_block(block.clone()) {
+                Ok(()) => {
+                    info!("Injected block #{}", block.id);
+                    vec![Message::MinedBlock(block.clone())]
+                }
+                Err(e) => {
+                    warn!("Injected block rejected: {}", e);
+                    Vec::new()
+                }
+            },
+            Message::MinedBlock(block) => self.apply_mined(block),
+            Message::BlockRequest(id) => vec![Message::BlockResponse(self.get_block(*id).cloned())],
+            Message::BlockResponse(Some(block)) if block.id == self.blocks.len() as u64 => {
+                if block.id > 0 && !self.meets_difficulty(block) {
+                    return vec![Message::Reject {
+                        what: RejectKind::LowDifficulty,
+                        detail: format!(
+                            "block #{} doesn't meet the chain's difficulty {}",
+                            block.id, self.difficulty
+                        ),
+                    }];
+                }
+                match self.append_block(block.clone()) {
+                    Ok(()) => info!("Appended requested block #{}", block.id),
+                    Err(e) => warn!("Requested block rejected: {}", e),
+                }
+                Vec::new()
+            }
+            Message::BlockResponse(_) => Vec::new(),
+            Message::Envelope(_, msg) => self.apply_message_with_policy(msg, policy),
+            Message::DebugRequest
+            | Message::DebugResponse(_)
+            | Message::Reject { .. }
+            | Message::Ack { .. }
+            | Message::RemineTip(_) => Vec::new(),
+        }
+    }
+
+    /// Applies the chain from another node, see `apply_message_with_policy`.
+    ///
+    /// Long chains are checked in parts, and `cancelled` is asked before every part, so a
+    /// `Node` can cancel the sync. Returns `None` if it's cancelled, the chain is left as is then.
+    pub(crate) fn apply_chain(
+        &mut self,
+        mut chain: Chain,
+        policy: &ValidationPolicy,
+        cancelled: &dyn Fn() -> bool,
+    ) -> Option<Vec<Message>> {
+        const PART: usize = 256;
+
+        // work is counted at the end of hashes mined on this network, and blocks hashed with
+        // another function than the local one are invalid
+        chain.difficulty_mode = self.difficulty_mode;
+        chain.hash_algo = self.hash_algo;
+        for start in (0..chain.blocks.len()).step_by(PART) {
+            if cancelled() {
+                return None;
+            }
+            if let Err(e) = chain.verify_with_policy_in(policy, start..start + PART) {
+                let what = match e {
+                    ChainError::LowDifficulty(_) => RejectKind::LowDifficulty,
+                    _ => RejectKind::InvalidChain,
+                };
+                let detail = format!("chain has errors: {}", e);
+                return Some(vec![Message::Reject { what, detail }]);
+            }
+        }
+        if (chain.total_work(), chain.blocks.len()) <= (self.total_work(), self.blocks.len()) {
+            return Some(Vec::new());
+        }
+        let old = self.swap_validated(chain);
+        // a policy may skip checks, but the state of the chain must stay consistent
+        if let Some(error) = self.invariant_error() {
+            self.swap_validated(old);
+            let detail = format!("chain is inconsistent: {}", error);
+            return Some(vec![Message::Reject {
+                what: RejectKind::InvalidChain,
+                detail,
+            }]);
+        }
+        warn!("Taking chain from another node!");
+        Some(Vec::new())
+    }
+
+    /// Applies the block mined by another node, see `apply_message`.
+    fn apply_mined(&mut self, block: &Block) -> Vec<Message> {
+        if !self.is_hashed(block) {
+            warn!("Reciever block with wrong hash field: {}", block);
+            return Vec::new();
+        }
+
+        let last = match self.blocks.last() {
+            Some(last) => last.id,
+            None => {
+                info!("Host chain in empty, requesting remote");
+                return vec![Message::ChainRequest];
+            }
+        };
+        // older blocks are ignored anyway
+        if block.id >= last && !self.meets_difficulty(block) {
+            return vec![Message::Reject {
+                what: RejectKind::LowDifficulty,
+                detail: format!(
+                    "block #{} doesn't meet the chain's difficulty {}",
+                    block.id, self.difficulty
+                ),
+            }];
+        }
+        let mut out = Vec::new();
+
+        if self.status {
+            if self.replace_tip_if(block.clone(), |new, old| new.hash < old.hash) {
+                info!("Replaced host block with remote block");
+            }
+        } else if self.replace_tip_if(block.clone(), |_, _| true) {
+            self.status = true;
+            info!("Took remote block");
+        }
+
+        if block.id > last {
+            out.push(Message::ChainRequest);
+        }
+
+        if let Some(id) = self.have_errors() {
+            warn!("Host chain have erros! Requesting remote");
+            self.rollback(id);
+            out.push(Message::ChainRequest);
+        }
+        out
+    }
+}
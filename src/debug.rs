@@ -0,0 +1,41 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// A snapshot of the internal state of a node, for diagnosing stuck nodes.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeDebug {
+    /// The number of blocks in the chain, including the one being mined.
+    pub height: usize,
+    /// The status of the chain: `false` while its last block is being mined.
+    pub status: bool,
+    /// The number of blocks waiting in the queue.
+    pub queue: usize,
+    /// The id of the block sent to the mining worker.
+    pub in_flight: Option<u64>,
+    /// Seconds since the node received the last chain from another node.
+    pub last_sync_secs: Option<u64>,
+    /// The number of connected peers.
+    pub peers: usize,
+    /// The number of blocks kept ahead of the chain, see `NodeConfig::orphan_window`.
+    pub orphans: usize,
+}
+
+impl fmt::Display for NodeDebug {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = if self.status { "ready" } else { "mining" };
+        write!(f, "height: {}\r\n", self.height)?;
+        write!(f, "status: {}\r\n", status)?;
+        write!(f, "queue: {}\r\n", self.queue)?;
+        match self.in_flight {
+            Some(id) => write!(f, "in flight: #{}\r\n", id)?,
+            None => write!(f, "in flight: none\r\n")?,
+        }
+        match self.last_sync_secs {
+            Some(secs) => write!(f, "last sync: {}s ago\r\n", secs)?,
+            None => write!(f, "last sync: never\r\n")?,
+        }
+        write!(f, "peers: {}\r\n", self.peers)?;
+        write!(f, "orphans: {}\r\n", self.orphans)
+    }
+}
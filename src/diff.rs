@@ -0,0 +1,37 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::BlockSummary;
+
+/// The difference between the local chain and the chain of a peer, see `Chain::diff`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainDiff {
+    /// The number of blocks both chains share, i.e. the id of the first divergent block.
+    pub fork_point: usize,
+    /// Blocks after the fork point which only the local chain has.
+    pub only_local: Vec<BlockSummary>,
+    /// Blocks after the fork point which only the remote chain has.
+    pub only_remote: Vec<BlockSummary>,
+}
+
+impl ChainDiff {
+    /// Returns true if the chains are the same.
+    pub fn is_empty(&self) -> bool {
+        self.only_local.is_empty() && self.only_remote.is_empty()
+    }
+}
+
+impl fmt::Display for ChainDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "fork point: #{}\r\n", self.fork_point)?;
+        for (side, blocks) in [("local", &self.only_local), ("remote", &self.only_remote)] {
+            write!(f, "only {}: {} blocks\r\n", side, blocks.len())?;
+            for block in blocks {
+                let hash = block.hash_hex.get(..8).unwrap_or_default();
+                write!(f, "  #{} {} \"{}\"\r\n", block.id, hash, block.data_preview)?;
+            }
+        }
+        Ok(())
+    }
+}
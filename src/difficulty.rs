@@ -0,0 +1,139 @@
+use core::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// Which end of the hash of a mined block must match the difficulty string.
+///
+/// # Examples
+///
+/// ```
+/// use rustychain::DifficultyMode;
+///
+/// let mut hash = [0xffu8; 32];
+/// hash[0] = 0x00;
+///
+/// assert!(DifficultyMode::Prefix.matches(&hash, "00"));
+/// assert!(!DifficultyMode::Suffix.matches(&hash, "00"));
+/// assert_eq!("prefix".parse::<DifficultyMode>(), Ok(DifficultyMode::Prefix));
+/// assert_eq!(DifficultyMode::default().to_string(), "suffix");
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DifficultyMode {
+    /// The hex of the hash must start with the difficulty, like leading zeros of real
+    /// proof-of-work targets.
+    Prefix,
+    /// The hex of the hash must end with the difficulty.
+    #[default]
+    Suffix,
+}
+
+impl DifficultyMode {
+    /// Returns true if the hex of the hash matches the difficulty at its end of the hash.
+    pub fn matches(self, hash: &[u8; 32], difficulty: &str) -> bool {
+        let hex = hex::encode(hash);
+        match self {
+            DifficultyMode::Prefix => hex.starts_with(difficulty),
+            DifficultyMode::Suffix => hex.ends_with(difficulty),
+        }
+    }
+}
+
+impl FromStr for DifficultyMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "prefix" => Ok(DifficultyMode::Prefix),
+            "suffix" => Ok(DifficultyMode::Suffix),
+            _ => Err(format!(
+                "unknown difficulty mode {s}, expected prefix or suffix"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DifficultyMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DifficultyMode::Prefix => write!(f, "prefix"),
+            DifficultyMode::Suffix => write!(f, "suffix"),
+        }
+    }
+}
+
+/// Parses the difficulty: the hex string which the hash of a mined block must end (or start, see
+/// `DifficultyMode`) with.
+///
+/// The difficulty must be a non-empty hex string not longer than the hash itself (64 chars).
+/// It's returned in lowercase to be compared with `Block::string_hash`.
+///
+/// Every hex char matches a nibble of the hash, so `"0"` and `"00"` differ 16 times in work.
+/// To avoid such mistakes the difficulty must be given in whole bytes: an even number of chars.
+///
+/// # Examples
+///
+/// ```
+/// use rustychain::parse_difficulty;
+///
+/// assert_eq!(parse_difficulty("00FF"), Ok(String::from("00ff")));
+/// assert!(parse_difficulty("xyz").is_err());
+/// assert!(parse_difficulty("").is_err());
+/// assert!(parse_difficulty("0").is_err());
+/// ```
+pub fn parse_difficulty(s: &str) -> Result<String, String> {
+    if s.is_empty() {
+        return Err(String::from("Difficulty shouldn't be empty"));
+    }
+    if !s.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(String::from("Difficulty should be a valid hex string"));
+    }
+    if s.len() > 64 {
+        return Err(String::from(
+            "Difficulty shouldn't be longer than the hash (64 hex chars)",
+        ));
+    }
+    if s.len() % 2 != 0 {
+        return Err(format!(
+            "Difficulty should be whole bytes (an even number of hex chars): \"{}\" matches {} \
+             nibbles ({} bits) of the hash",
+            s,
+            s.len(),
+            s.len() * 4
+        ));
+    }
+    Ok(s.to_lowercase())
+}
+
+/// Returns the expected number of attempts to mine a block with the given difficulty.
+///
+/// Every hex char of the difficulty matches with probability 1/16.
+///
+/// # Examples
+///
+/// ```
+/// use rustychain::expected_attempts;
+///
+/// assert_eq!(expected_attempts("00"), 256.0);
+/// ```
+pub fn expected_attempts(difficulty: &str) -> f64 {
+    16f64.powi(difficulty.len() as i32)
+}
+
+/// Describes how hard it's to mine a block with the given difficulty, e.g. `"~256 attempts"`.
+///
+/// # Examples
+///
+/// ```
+/// use rustychain::describe_difficulty;
+///
+/// assert_eq!(describe_difficulty("0000"), "~65536 attempts");
+/// assert_eq!(describe_difficulty(&"0".repeat(20)), "~1.2e24 attempts");
+/// ```
+pub fn describe_difficulty(difficulty: &str) -> String {
+    let attempts = expected_attempts(difficulty);
+    if attempts < 1e15 {
+        format!("~{:.0} attempts", attempts)
+    } else {
+        format!("~{:.1e} attempts", attempts)
+    }
+}
@@ -0,0 +1,139 @@
+use core::fmt;
+use std::error::Error;
+
+/// Errors returned when a block or a chain fails validation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainError {
+    /// The input could not be decoded into a block.
+    Decode(String),
+    /// The chain is mining its last block and can't accept new blocks yet.
+    Busy,
+    /// The block has an unexpected id.
+    WrongId {
+        /// The id the block should have.
+        expected: u64,
+        /// The id the block actually has.
+        found: u64,
+    },
+    /// The `hash` field of the block with this id doesn't match its calculated hash.
+    InvalidHash(u64),
+    /// The `prev` field of the block with this id doesn't point to the previous block.
+    InvalidPrev(u64),
+    /// The block with this id doesn't match its downloaded header, or one of them is missing.
+    HeaderMismatch(u64),
+    /// The chain file can't be read or written.
+    Io(String),
+    /// The length or the checksum of the chain file doesn't match its content.
+    ChecksumMismatch,
+    /// The genesis block isn't the expected one, see `ValidationPolicy::genesis`.
+    GenesisMismatch,
+    /// The block with this id doesn't match the checkpoint, see `ValidationPolicy::checkpoints`.
+    CheckpointMismatch(u64),
+    /// The timestamp of the block with this id is too far in the future, see
+    /// `ValidationPolicy::max_time_drift`.
+    FutureTimestamp(u64),
+    /// The block with this id doesn't have enough work, see `ValidationPolicy`.
+    LowDifficulty(u64),
+    /// The block with this id came pruned (see `Block::pruned`), so its hash can't be checked.
+    /// Only bodies pruned by the chain itself are trusted.
+    PrunedBlock(u64),
+    /// The chain has no mined block after the genesis one.
+    NoTip,
+    /// No hash has this many zero bits at the mined end and meets the difficulty of the chain too.
+    ImpossibleWork(u32),
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainError::Decode(e) => write!(f, "can't decode block: {}", e),
+            ChainError::Busy => write!(f, "chain is mining its last block"),
+            ChainError::WrongId { expected, found } => {
+                write!(f, "wrong block id: expected {}, found {}", expected, found)
+            }
+            ChainError::InvalidHash(id) => write!(f, "block #{} has invalid hash", id),
+            ChainError::InvalidPrev(id) => {
+                write!(f, "block #{} doesn't point to previous block", id)
+            }
+            ChainError::HeaderMismatch(id) => {
+                write!(f, "block #{} doesn't match its header", id)
+            }
+            ChainError::Io(e) => write!(f, "can't access chain file: {}", e),
+            ChainError::ChecksumMismatch => write!(f, "chain file is corrupted: checksum mismatch"),
+            ChainError::GenesisMismatch => write!(f, "chain has unexpected genesis block"),
+            ChainError::CheckpointMismatch(id) => {
+                write!(f, "block #{} doesn't match the checkpoint", id)
+            }
+            ChainError::FutureTimestamp(id) => {
+                write!(f, "block #{} has timestamp too far in the future", id)
+            }
+            ChainError::LowDifficulty(id) => write!(f, "block #{} doesn't have enough work", id),
+            ChainError::PrunedBlock(id) => {
+                write!(f, "block #{} is pruned and can't be checked", id)
+            }
+            ChainError::NoTip => write!(f, "chain has no mined blocks"),
+            ChainError::ImpossibleWork(bits) => write!(
+                f,
+                "no hash has {} bits of work and meets the chain's difficulty",
+                bits
+            ),
+        }
+    }
+}
+
+impl Error for ChainError {}
+
+/// Errors returned when a message received from the network can't be decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessageError {
+    /// The message is larger than `Message::MAX_SIZE` or a more strict limit.
+    TooLarge(usize),
+    /// The message isn't valid UTF-8.
+    Utf8,
+    /// The message isn't a valid JSON of a known variant.
+    Decode(String),
+    /// The message can't be encoded to be sent.
+    Encode(String),
+}
+
+impl fmt::Display for MessageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MessageError::TooLarge(size) => write!(f, "message is too large: {} bytes", size),
+            MessageError::Utf8 => write!(f, "message isn't valid UTF-8"),
+            MessageError::Decode(e) => write!(f, "can't decode message: {}", e),
+            MessageError::Encode(e) => write!(f, "can't encode message: {}", e),
+        }
+    }
+}
+
+impl Error for MessageError {}
+
+/// Errors returned when a command typed by the host operator can't be parsed, see `Command`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    /// There is no such command.
+    Unknown(String),
+    /// The arguments of the command are missing or invalid.
+    InvalidArgument {
+        /// The name of the command.
+        command: String,
+        /// Why the arguments are invalid.
+        reason: String,
+    },
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Unknown(command) => {
+                write!(f, "unknown command {}, type help to list commands", command)
+            }
+            CommandError::InvalidArgument { command, reason } => {
+                write!(f, "invalid arguments of {}: {}", command, reason)
+            }
+        }
+    }
+}
+
+impl Error for CommandError {}
@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+
+use crate::NodeDebug;
+
+/// An event emitted by a running node, see `Node::subscribe`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NodeEvent {
+    /// The block submitted by the host got the number of confirmations set by
+    /// `NodeConfig::confirmations`: blocks mined on top of it.
+    Confirmed {
+        /// The ID of the block.
+        id: u64,
+        /// The data of the block, as it was submitted.
+        data: String,
+        /// The number of blocks mined on top of the block.
+        confirmations: u64,
+    },
+    /// The chain of the node is reorganized: mined blocks after the fork point are replaced by
+    /// other blocks, e.g. by a longer chain from another node.
+    Reorg {
+        /// Hashes of the removed blocks, in the order of the chain.
+        removed: Vec<[u8; 32]>,
+        /// Hashes of the added blocks, in the order of the chain.
+        added: Vec<[u8; 32]>,
+    },
+    /// The periodic summary of the node's state, emitted every `NodeConfig::heartbeat`.
+    Heartbeat {
+        /// The state of the node, see `Node::debug_info`.
+        state: NodeDebug,
+        /// The number of blocks mined by the node since the previous heartbeat.
+        mined: u64,
+    },
+    /// The worker didn't mine the block within `NodeConfig::mining_timeout`, so the block is sent
+    /// to it again (see `NodeConfig::stall_policy`).
+    MiningStalled {
+        /// The ID of the block.
+        id: u64,
+        /// The difficulty the block is mined with from now on.
+        difficulty: String,
+    },
+}
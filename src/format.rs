@@ -0,0 +1,58 @@
+use core::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// The format of the chain in chain files, see `Chain::save_to_file`.
+///
+/// # Examples
+///
+/// ```
+/// use rustychain::DbFormat;
+///
+/// assert_eq!("bincode".parse::<DbFormat>(), Ok(DbFormat::Bincode));
+/// assert_eq!(DbFormat::Json.to_string(), "json");
+/// assert!("yaml".parse::<DbFormat>().is_err());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DbFormat {
+    /// Human-readable JSON.
+    #[default]
+    Json,
+    /// Compact binary encoding of `bincode`.
+    Bincode,
+}
+
+impl DbFormat {
+    /// The first byte of bincode chains. JSON chains always start with `{`.
+    pub(crate) const BINCODE_MAGIC: u8 = 0xb1;
+
+    /// Detects the format of the encoded chain by its first byte.
+    pub(crate) fn detect(body: &[u8]) -> Option<DbFormat> {
+        match body.first() {
+            Some(b'{') => Some(DbFormat::Json),
+            Some(&DbFormat::BINCODE_MAGIC) => Some(DbFormat::Bincode),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for DbFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(DbFormat::Json),
+            "bincode" => Ok(DbFormat::Bincode),
+            _ => Err(format!("unknown format {s}, expected json or bincode")),
+        }
+    }
+}
+
+impl fmt::Display for DbFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbFormat::Json => write!(f, "json"),
+            DbFormat::Bincode => write!(f, "bincode"),
+        }
+    }
+}
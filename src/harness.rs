@@ -0,0 +1,100 @@
+use std::sync::{Arc, Mutex};
+
+use tokio::{
+    sync::{
+        broadcast,
+        mpsc::{self, Receiver, Sender},
+    },
+    task::AbortHandle,
+};
+
+use crate::{Chain, Message, Node};
+
+/// The capacity of channels between a `Node` and its `NodeHarness`.
+const CAPACITY: usize = 16;
+
+/// The test side of the channels of a `Node` created by `Node::test_harness`.
+#[derive(Debug)]
+pub struct NodeHarness {
+    /// Sends messages to the node, as if they were received from the network or the host.
+    pub tx: Sender<Message>,
+    /// Receives messages sent by the node.
+    pub rx: Receiver<Message>,
+    /// Shuts the node down.
+    pub cancel: broadcast::Sender<()>,
+    /// The task of the mining worker of the node.
+    worker: Arc<Mutex<Option<AbortHandle>>>,
+}
+
+impl NodeHarness {
+    /// Sends the message to the node.
+    ///
+    /// Panics if the node is dropped.
+    pub async fn send(&self, msg: Message) {
+        self.tx.send(msg).await.expect("Node is running");
+    }
+
+    /// Receives the next message sent by the node.
+    ///
+    /// Panics if the node is dropped.
+    pub async fn recv(&mut self) -> Message {
+        self.rx.recv().await.expect("Node is running")
+    }
+
+    /// Stops the running node, see `Node::run`.
+    pub fn shutdown(&self) {
+        // the node may be stopped already
+        let _ = self.cancel.send(());
+    }
+
+    /// Stops the mining worker of the running node as if it died, so the node has to restart it.
+    ///
+    /// Returns false if the worker isn't started yet.
+    pub fn kill_worker(&self) -> bool {
+        match self.worker.lock().unwrap().as_ref() {
+            Some(worker) => {
+                worker.abort();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Node {
+    /// Creates a node with the chain and difficulty, wired to a `NodeHarness` which plays the
+    /// network and the host in tests.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::{Block, Chain, Message, Node};
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let (mut node, mut harness) = Node::test_harness(Chain::new(), "0");
+    /// let handle = tokio::spawn(async move { node.run().await });
+    ///
+    /// harness.send(Message::NewBlock(Block::new(0, "Some data".to_owned()))).await;
+    /// assert!(matches!(harness.recv().await, Message::MinedBlock(_)));
+    ///
+    /// harness.shutdown();
+    /// handle.await.unwrap();
+    /// # });
+    /// ```
+    pub fn test_harness(chain: Chain, difficulty: &str) -> (Node, NodeHarness) {
+        let (tx, rx_node) = mpsc::channel(CAPACITY);
+        let (tx_node, rx) = mpsc::channel(CAPACITY);
+        let (cancel, rx_cancel) = broadcast::channel(1);
+        let node = Node::new(chain, tx_node, rx_node, rx_cancel, difficulty.to_owned());
+        let worker = node.worker.clone();
+        (
+            node,
+            NodeHarness {
+                tx,
+                rx,
+                cancel,
+                worker,
+            },
+        )
+    }
+}
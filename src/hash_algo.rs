@@ -0,0 +1,66 @@
+use core::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sha3::Sha3_256;
+
+/// The hash function of blocks, see `Chain::hash_algo`.
+///
+/// It's a setting of the network like `DifficultyMode`: blocks hashed with another function are
+/// invalid in the chain.
+///
+/// # Examples
+///
+/// ```
+/// use rustychain::HashAlgo;
+///
+/// assert_ne!(HashAlgo::Sha256.hash(b"data"), HashAlgo::Blake3.hash(b"data"));
+/// assert_eq!("sha3-256".parse::<HashAlgo>(), Ok(HashAlgo::Sha3_256));
+/// assert_eq!(HashAlgo::default().to_string(), "sha256");
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HashAlgo {
+    /// SHA-256, the hash of blocks from the start.
+    #[default]
+    Sha256,
+    /// SHA3-256 (Keccak).
+    Sha3_256,
+    /// BLAKE3 with 256-bit output.
+    Blake3,
+}
+
+impl HashAlgo {
+    /// Hashes the bytes, e.g. `Block::canonical_bytes`.
+    pub fn hash(self, bytes: &[u8]) -> [u8; 32] {
+        match self {
+            HashAlgo::Sha256 => Sha256::digest(bytes).into(),
+            HashAlgo::Sha3_256 => Sha3_256::digest(bytes).into(),
+            HashAlgo::Blake3 => blake3::hash(bytes).into(),
+        }
+    }
+}
+
+impl FromStr for HashAlgo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sha256" => Ok(HashAlgo::Sha256),
+            "sha3-256" => Ok(HashAlgo::Sha3_256),
+            "blake3" => Ok(HashAlgo::Blake3),
+            _ => Err(format!(
+                "unknown hash algorithm {s}, expected sha256, sha3-256 or blake3"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for HashAlgo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashAlgo::Sha256 => write!(f, "sha256"),
+            HashAlgo::Sha3_256 => write!(f, "sha3-256"),
+            HashAlgo::Blake3 => write!(f, "blake3"),
+        }
+    }
+}
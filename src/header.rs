@@ -0,0 +1,94 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Block, ChainError};
+
+/// The `BlockHeader` struct is a `Block` without its data.
+///
+/// Headers are enough for light clients to check that blocks are linked together.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct BlockHeader {
+    /// The ID of the block.
+    pub id: u64,
+    /// The hash of the block.
+    pub hash: [u8; 32],
+    /// The hash of the previous block in the chain.
+    pub prev: [u8; 32],
+    /// The nonce used to mine the block.
+    pub nonce: u64,
+    /// When the block was created, in Unix seconds.
+    #[serde(default)]
+    pub timestamp: u64,
+}
+
+impl BlockHeader {
+    /// Checks that headers go one after another and each of them points to the previous one.
+    ///
+    /// Returns the error for the first header which breaks the linkage.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::{Block, BlockHeader};
+    ///
+    /// let genesis = Block::genesis("hash-net");
+    /// let mut next = Block::new(1, "First".to_owned());
+    /// next.prev = genesis.hash;
+    /// next.update_hash();
+    ///
+    /// let headers = vec![genesis.header(), next.header()];
+    /// assert_eq!(BlockHeader::verify_linkage(&headers), Ok(()));
+    /// assert!(BlockHeader::verify_linkage(&headers[1..]).is_ok());
+    /// ```
+    pub fn verify_linkage(headers: &[BlockHeader]) -> Result<(), ChainError> {
+        if let Some(first) = headers.first() {
+            if first.id == 0 && first.prev != [0u8; 32] {
+                return Err(ChainError::InvalidPrev(0));
+            }
+        }
+        for pair in headers.windows(2) {
+            let (prev, header) = (&pair[0], &pair[1]);
+            if header.id != prev.id + 1 {
+                return Err(ChainError::WrongId {
+                    expected: prev.id + 1,
+                    found: header.id,
+                });
+            }
+            if header.prev != prev.hash {
+                return Err(ChainError::InvalidPrev(header.id));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a hexadecimal string representation of the block's hash.
+    pub fn string_hash(&self) -> String {
+        hex::encode(self.hash)
+    }
+}
+
+impl From<&Block> for BlockHeader {
+    fn from(block: &Block) -> Self {
+        Self {
+            id: block.id,
+            hash: block.hash,
+            prev: block.prev,
+            nonce: block.nonce,
+            timestamp: block.timestamp,
+        }
+    }
+}
+
+impl fmt::Display for BlockHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} hash: {}, previous: {}, nonce {}",
+            self.id,
+            self.string_hash(),
+            hex::encode(self.prev),
+            self.nonce,
+        )
+    }
+}
@@ -0,0 +1,96 @@
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+/// Health of a node, used to tell "started but syncing" from "synced and mining".
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Health {
+    /// The number of mined blocks in the chain of the node.
+    pub height: usize,
+    /// Whether the chain of the node has no errors.
+    pub valid: bool,
+    /// The number of connected peers.
+    pub peers: usize,
+    /// Whether the node may be ready without peers.
+    pub solo: bool,
+    /// Whether the node waits for the chain from other nodes before mining, see
+    /// `NodeConfig::initial_sync`.
+    pub syncing: bool,
+}
+
+impl Health {
+    /// Returns `true` if the node has a non-empty chain without errors, isn't in the initial sync
+    /// and has at least one peer (or runs in solo mode).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::Health;
+    ///
+    /// let mut health = Health {
+    ///     height: 1,
+    ///     valid: true,
+    ///     peers: 0,
+    ///     solo: false,
+    ///     syncing: true,
+    /// };
+    /// assert!(!health.is_ready());
+    ///
+    /// health.peers = 1;
+    /// assert!(!health.is_ready());
+    ///
+    /// health.syncing = false;
+    /// assert!(health.is_ready());
+    /// ```
+    pub fn is_ready(&self) -> bool {
+        self.height > 0 && self.valid && !self.syncing && (self.solo || self.peers > 0)
+    }
+}
+
+/// Serves the health of a node over HTTP: every request gets `200 OK` if the node is ready
+/// and `503 Service Unavailable` otherwise, with the health as JSON body.
+///
+/// The request itself isn't parsed, so any path (e.g. `/healthz`) works. A failed connection
+/// (e.g. when the process is out of file descriptors) is logged, and the endpoint keeps serving.
+pub async fn serve_health(listener: TcpListener, health: Arc<Mutex<Health>>) -> io::Result<()> {
+    loop {
+        let mut stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                error!("Health connection error: {:?}", e);
+                continue;
+            }
+        };
+        let health = health.lock().unwrap().clone();
+        tokio::spawn(async move {
+            let mut buf = [0; 1024];
+            if let Err(e) = stream.read(&mut buf).await {
+                error!("Health request error: {:?}", e);
+                return;
+            }
+            let status = if health.is_ready() {
+                "200 OK"
+            } else {
+                "503 Service Unavailable"
+            };
+            let body = serde_json::to_string(&health).expect("Health is serializable");
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            if let Err(e) = stream.write_all(response.as_bytes()).await {
+                error!("Health response error: {:?}", e);
+            }
+        });
+    }
+}
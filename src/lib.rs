@@ -14,16 +14,130 @@ mod block;
 
 pub use block::Block;
 
+mod header;
+
+pub use header::BlockHeader;
+
+mod summary;
+
+pub use summary::BlockSummary;
+
 mod chain;
 
-pub use chain::Chain;
+pub use chain::{Chain, RETARGET_WINDOW};
+
+mod chain_stats;
+
+pub use chain_stats::ChainStats;
+
+mod consensus;
+
+mod format;
+
+pub use format::DbFormat;
+
+mod diff;
+
+pub use diff::ChainDiff;
+
+mod tip;
+
+pub use tip::TipComparison;
+
+mod activity;
+
+pub use activity::ActivitySummary;
+
+mod error;
+
+pub use error::{ChainError, CommandError, MessageError};
+
+mod difficulty;
+
+pub use difficulty::{describe_difficulty, expected_attempts, parse_difficulty, DifficultyMode};
+
+mod hash_algo;
+
+pub use hash_algo::HashAlgo;
+
+mod command;
+
+pub use command::Command;
 
 mod message;
 
-pub use message::Message;
+pub use message::{encode_json, Message, RejectKind};
+
+mod archive;
+
+pub use archive::{archive_block, archive_path};
+
+mod record;
+
+pub use record::{Direction, LogEntry, MessageLog};
+
+mod validator;
+
+pub use validator::{AcceptAll, TransactionValidator};
+
+mod backoff;
+
+pub use backoff::Backoff;
+
+mod peers;
+
+pub use peers::PeerBook;
+
+mod orphans;
+
+pub use orphans::OrphanPool;
+
+mod limits;
+
+pub use limits::MemoryLimits;
+
+mod policy;
+
+pub use policy::ValidationPolicy;
+
+mod config;
+
+pub use config::{BlockHook, NodeConfig, StallPolicy};
+
+mod health;
+
+pub use health::{serve_health, Health};
+
+mod debug;
+
+pub use debug::NodeDebug;
+
+mod event;
+
+pub use event::NodeEvent;
+
+mod mining;
+
+pub use mining::{mine_blocking, mining_threads, parallel_worker, set_thread_nice, thread_nonces};
+
+mod stats;
+
+pub use stats::MessageStats;
 
 mod node;
 
 pub use node::Node;
 
-pub use node::nonce_worker;
\ No newline at end of file
+#[cfg(any(test, feature = "testing"))]
+mod harness;
+
+#[cfg(any(test, feature = "testing"))]
+pub use harness::NodeHarness;
+
+pub use node::MiningJob;
+
+pub use node::SyncControl;
+
+pub use node::{nonce_worker, nonce_workers};
+
+pub use node::calibrate_difficulty;
\ No newline at end of file
@@ -0,0 +1,46 @@
+/// Limits of the memory used by a `Node`, see `NodeConfig::limits`. No limits are set by default.
+///
+/// The chain isn't frozen at a limit: bodies of old blocks are pruned, and the farthest orphan
+/// blocks are evicted, so the node keeps following the network.
+///
+/// # Examples
+///
+/// ```
+/// use rustychain::MemoryLimits;
+///
+/// let limits = MemoryLimits {
+///     max_chain_blocks: Some(100),
+///     max_queue: Some(1000),
+///     ..Default::default()
+/// };
+/// assert!(!limits.queue_full(999));
+/// assert!(limits.queue_full(1000));
+/// assert_eq!(limits.excess_bodies(99), 0);
+/// assert_eq!(limits.excess_bodies(150), 50);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryLimits {
+    /// The maximum number of blocks in the chain which keep their bodies. Over it the bodies of
+    /// the oldest blocks are pruned (see `Chain::prune_bodies`), so the chain keeps growing with
+    /// their headers, and longer chains from other nodes are taken and pruned the same way.
+    pub max_chain_blocks: Option<usize>,
+    /// The maximum number of blocks in the queue. New blocks over it are rejected.
+    pub max_queue: Option<usize>,
+    /// The maximum number of mined blocks kept ahead of the chain (see
+    /// `NodeConfig::orphan_window`). Over it the blocks farthest from the tip are evicted.
+    pub max_orphans: Option<usize>,
+}
+
+impl MemoryLimits {
+    /// Returns how many of the oldest blocks of a chain of `len` blocks are over
+    /// `max_chain_blocks`, so their bodies should be pruned.
+    pub fn excess_bodies(&self, len: usize) -> usize {
+        self.max_chain_blocks
+            .map_or(0, |max| len.saturating_sub(max))
+    }
+
+    /// Returns true if a queue of `len` blocks can't take more blocks.
+    pub fn queue_full(&self, len: usize) -> bool {
+        self.max_queue.is_some_and(|max| len >= max)
+    }
+}
@@ -1,8 +1,12 @@
 use core::fmt;
 
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{self, IgnoredAny, MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use sha2::{Digest, Sha256};
 
-use crate::{Block, Chain};
+use crate::{Block, Chain, MessageError, NodeDebug};
 
 /// A message sent between nodes in the blockchain network.
 ///
@@ -17,6 +21,276 @@ pub enum Message {
     ChainRequest,
     /// A response to a `ChainRequest`, containing the current state of the blockchain.
     ChainResponce(Chain),
+    /// An already mined block which the host operator wants to append to the chain as is.
+    InjectBlock(Block),
+    /// A new block submitted by the host operator, which is gossiped to other nodes as `NewBlock`.
+    SubmitBlock(Block),
+    /// A request for the internal state of the host node.
+    DebugRequest,
+    /// A response to a `DebugRequest`, containing the internal state of the node.
+    DebugResponse(NodeDebug),
+    /// A message received from the peer with the given id, wrapped by the host for its node.
+    Envelope(String, Box<Message>),
+    /// A feedback that a message from the peer was rejected, so it learns why.
+    Reject {
+        /// What was rejected.
+        what: RejectKind,
+        /// The details of the rejection.
+        detail: String,
+    },
+    /// A confirmation that a node put the block from `Message::NewBlock` into its queue, so the
+    /// client which submitted the transaction learns it was taken.
+    Ack {
+        /// The hash of the block's transactions, see `Block::data_hash`.
+        data_hash: [u8; 32],
+    },
+    /// A request of the host operator to mine the last block again for at least this many
+    /// zero bits at the mined end of its hash, see `Chain::remine_tip`.
+    RemineTip(u32),
+    /// A request for the block with the given id, so a node can sync a part of the chain without
+    /// a `ChainRequest`.
+    BlockRequest(u64),
+    /// A response to a `BlockRequest`, containing the block or `None` if the node doesn't have it.
+    BlockResponse(Option<Block>),
+}
+
+/// The reason of `Message::Reject`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RejectKind {
+    /// The block is larger than the node accepts.
+    TooLarge,
+    /// The peer has too many blocks in the queue.
+    QueueFull,
+    /// The chain has errors.
+    InvalidChain,
+    /// The block has less work than the node accepts.
+    LowDifficulty,
+    /// The transaction is rejected by the `TransactionValidator` of the node.
+    InvalidTransaction,
+}
+
+impl fmt::Display for RejectKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RejectKind::TooLarge => write!(f, "too large"),
+            RejectKind::QueueFull => write!(f, "queue is full"),
+            RejectKind::InvalidChain => write!(f, "invalid chain"),
+            RejectKind::LowDifficulty => write!(f, "low difficulty"),
+            RejectKind::InvalidTransaction => write!(f, "invalid transaction"),
+        }
+    }
+}
+
+/// Encodes the value as JSON to be sent over the network, the counterpart of
+/// `Message::from_slice`.
+///
+/// Failures are returned instead of panicking, so a value which can't be encoded (or is larger
+/// than `Message::MAX_SIZE`, so peers would drop it) is skipped without stopping the node.
+///
+/// # Examples
+///
+/// ```
+/// use rustychain::{encode_json, Message};
+///
+/// assert_eq!(encode_json(&Message::ChainRequest).unwrap(), b"\"ChainRequest\"");
+/// ```
+pub fn encode_json<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, MessageError> {
+    let data = serde_json::to_vec(value).map_err(|e| MessageError::Encode(e.to_string()))?;
+    if data.len() > Message::MAX_SIZE {
+        return Err(MessageError::TooLarge(data.len()));
+    }
+    Ok(data)
+}
+
+/// Reads the name of the variant of an encoded `Message`, skipping its content.
+struct KindVisitor;
+
+impl<'de> Visitor<'de> for KindVisitor {
+    type Value = String;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a message")
+    }
+
+    fn visit_str<E: de::Error>(self, kind: &str) -> Result<String, E> {
+        Ok(kind.to_owned())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<String, A::Error> {
+        let kind = map
+            .next_key::<String>()?
+            .ok_or_else(|| de::Error::custom("empty message"))?;
+        map.next_value::<IgnoredAny>()?;
+        if map.next_key::<IgnoredAny>()?.is_some() {
+            return Err(de::Error::custom("message has several variants"));
+        }
+        Ok(kind)
+    }
+}
+
+impl Message {
+    /// The maximum size of a message received from the network, in bytes.
+    pub const MAX_SIZE: usize = 16 * 1024 * 1024;
+
+    /// Decodes the message received from the network.
+    ///
+    /// It never panics on arbitrary input, so it's the single entry point for untrusted bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::{Message, MessageError};
+    ///
+    /// assert!(matches!(Message::from_slice(b"\"ChainRequest\""), Ok(Message::ChainRequest)));
+    /// assert_eq!(Message::from_slice(&[0xff]).unwrap_err(), MessageError::Utf8);
+    /// ```
+    pub fn from_slice(data: &[u8]) -> Result<Message, MessageError> {
+        if data.len() > Self::MAX_SIZE {
+            return Err(MessageError::TooLarge(data.len()));
+        }
+        let text = std::str::from_utf8(data).map_err(|_| MessageError::Utf8)?;
+        serde_json::from_str(text).map_err(|e| MessageError::Decode(e.to_string()))
+    }
+
+    /// Encodes the message to be sent over the network, see `encode_json`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::Message;
+    ///
+    /// let data = Message::ChainRequest.to_bytes().unwrap();
+    /// assert!(matches!(Message::from_slice(&data), Ok(Message::ChainRequest)));
+    /// ```
+    pub fn to_bytes(&self) -> Result<Vec<u8>, MessageError> {
+        encode_json(self)
+    }
+
+    /// Decodes the message like `from_slice`, but rejects a `Message::ChainResponce` larger than
+    /// `max_chain` bytes before the chain is decoded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::{Chain, Message, MessageError};
+    ///
+    /// let msg = Message::ChainResponce(Chain::with_genesis("hash-net"));
+    /// let data = serde_json::to_vec(&msg).unwrap();
+    ///
+    /// assert!(Message::from_slice_with_chain_limit(&data, data.len()).is_ok());
+    /// assert_eq!(
+    ///     Message::from_slice_with_chain_limit(&data, 16).unwrap_err(),
+    ///     MessageError::TooLarge(data.len())
+    /// );
+    /// ```
+    pub fn from_slice_with_chain_limit(
+        data: &[u8],
+        max_chain: usize,
+    ) -> Result<Message, MessageError> {
+        if data.len() > max_chain && Message::peek_kind(data)? == "ChainResponce" {
+            return Err(MessageError::TooLarge(data.len()));
+        }
+        Message::from_slice(data)
+    }
+
+    /// Returns the name of the variant of the encoded message without decoding its content.
+    ///
+    /// The content is only scanned, so nothing of it is kept in memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::Message;
+    ///
+    /// assert_eq!(Message::peek_kind(b"\"ChainRequest\"").unwrap(), "ChainRequest");
+    /// assert_eq!(Message::peek_kind(br#"{"ChainResponce": [1, 2]}"#).unwrap(), "ChainResponce");
+    /// assert!(Message::peek_kind(b"42").is_err());
+    /// ```
+    pub fn peek_kind(data: &[u8]) -> Result<String, MessageError> {
+        if data.len() > Self::MAX_SIZE {
+            return Err(MessageError::TooLarge(data.len()));
+        }
+        let text = std::str::from_utf8(data).map_err(|_| MessageError::Utf8)?;
+        let mut deserializer = serde_json::Deserializer::from_str(text);
+        let kind = deserializer
+            .deserialize_any(KindVisitor)
+            .and_then(|kind| deserializer.end().map(|_| kind));
+        kind.map_err(|e| MessageError::Decode(e.to_string()))
+    }
+
+    /// Returns the id of the message derived from its content, not from its serialized form.
+    ///
+    /// Messages with blocks are identified by the variant and the hash of the block, so the same
+    /// block serialized in different ways gets the same id.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::{Block, Message};
+    ///
+    /// let block = Block::genesis("hash-net");
+    /// let mined = Message::MinedBlock(block.clone());
+    ///
+    /// assert_eq!(mined.canonical_id(), Message::MinedBlock(block.clone()).canonical_id());
+    /// assert_ne!(mined.canonical_id(), Message::InjectBlock(block).canonical_id());
+    /// ```
+    pub fn canonical_id(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        match self {
+            Message::MinedBlock(block) => {
+                hasher.update("MinedBlock");
+                hasher.update(block.hash);
+            }
+            Message::NewBlock(block) => {
+                hasher.update("NewBlock");
+                hasher.update(block.calc_hash());
+            }
+            Message::InjectBlock(block) => {
+                hasher.update("InjectBlock");
+                hasher.update(block.hash);
+            }
+            other => {
+                // serializing messages doesn't fail, but the id must never panic
+                hasher.update(serde_json::to_vec(other).unwrap_or_default());
+            }
+        }
+        hasher.finalize().into()
+    }
+
+    /// Returns the gossip id for the serialized message.
+    ///
+    /// Only messages with a single block (`MinedBlock`, `NewBlock` and `InjectBlock`) are decoded
+    /// to get their `canonical_id`. Other messages, e.g. whole chains, are identified by the
+    /// SHA256 hash of their kind (see `peek_kind`) and raw bytes, and data which isn't a message
+    /// by the hash of its bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::{Block, Message};
+    ///
+    /// let msg = Message::NewBlock(Block::new(1, String::from("Data")));
+    /// let compact = serde_json::to_vec(&msg).unwrap();
+    /// let pretty = serde_json::to_vec_pretty(&msg).unwrap();
+    /// assert_eq!(Message::gossip_id(&compact), Message::gossip_id(&pretty));
+    /// assert_eq!(Message::gossip_id(b"\"ChainRequest\"").len(), 64);
+    /// assert_eq!(Message::gossip_id(b"garbage").len(), 64);
+    /// ```
+    pub fn gossip_id(data: &[u8]) -> String {
+        let kind = match Message::peek_kind(data) {
+            Ok(kind) => kind,
+            Err(_) => return hex::encode(Sha256::digest(data)),
+        };
+        if matches!(kind.as_str(), "MinedBlock" | "NewBlock" | "InjectBlock") {
+            if let Ok(msg) = Message::from_slice(data) {
+                return hex::encode(msg.canonical_id());
+            }
+        }
+        let mut hasher = Sha256::new();
+        hasher.update(kind);
+        hasher.update(data);
+        hex::encode(hasher.finalize())
+    }
 }
 
 impl fmt::Display for Message {
@@ -26,6 +300,17 @@ impl fmt::Display for Message {
             Message::NewBlock(block) => write!(f, "New Transaction({})", block.data),
             Message::ChainRequest => write!(f, "ChainRequest"),
             Message::ChainResponce(chain) => write!(f, "Chain Response:\r\n {}", chain),
+            Message::InjectBlock(block) => write!(f, "InjectBlock({})", block),
+            Message::SubmitBlock(block) => write!(f, "Submitted Transaction({})", block.data),
+            Message::DebugRequest => write!(f, "DebugRequest"),
+            Message::DebugResponse(debug) => write!(f, "Debug Response:\r\n{}", debug),
+            Message::Envelope(peer, msg) => write!(f, "{} from {}", msg, peer),
+            Message::Reject { what, detail } => write!(f, "Reject({}: {})", what, detail),
+            Message::Ack { data_hash } => write!(f, "Ack({})", hex::encode(data_hash)),
+            Message::RemineTip(bits) => write!(f, "RemineTip({} bits)", bits),
+            Message::BlockRequest(id) => write!(f, "BlockRequest(#{})", id),
+            Message::BlockResponse(Some(block)) => write!(f, "BlockResponse({})", block),
+            Message::BlockResponse(None) => write!(f, "BlockResponse(none)"),
         }
     }
 }
@@ -0,0 +1,258 @@
+use std::{
+    io,
+    iter::StepBy,
+    ops::RangeFrom,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use log::{debug, error, warn};
+use tokio::{
+    sync::{
+        broadcast,
+        mpsc::{Receiver, Sender},
+    },
+    task::{self, JoinHandle},
+};
+
+use crate::{Block, DifficultyMode};
+
+/// The maximum number of mining threads which makes sense.
+const MAX_THREADS: usize = 1024;
+
+/// The mined hash and nonce of a block, and the number of nonces tried to find them.
+pub(crate) type Solution = ([u8; 32], u64, u64);
+
+/// Returns the number of threads for mining: the requested one, or the number of CPUs.
+///
+/// Falls back to a single thread (with a warning) if the number is implausible or the number
+/// of CPUs is unknown.
+///
+/// # Examples
+///
+/// ```
+/// use rustychain::mining_threads;
+///
+/// assert_eq!(mining_threads(Some(4)), 4);
+/// assert_eq!(mining_threads(Some(0)), 1);
+/// assert!(mining_threads(None) >= 1);
+/// ```
+pub fn mining_threads(requested: Option<usize>) -> usize {
+    let threads = match requested {
+        Some(threads) => threads,
+        None => match thread::available_parallelism() {
+            Ok(threads) => threads.get(),
+            Err(e) => {
+                warn!("Can't get the number of CPUs, mining on one thread: {e}");
+                return 1;
+            }
+        },
+    };
+    if threads == 0 || threads > MAX_THREADS {
+        warn!("Implausible number of mining threads {threads}, mining on one thread");
+        return 1;
+    }
+    threads
+}
+
+/// Returns the nonces tried by the mining thread with the given index out of `threads`: its residue
+/// class `nonce ≡ thread (mod threads)` in increasing order.
+///
+/// Classes of different threads don't overlap and together cover the whole nonce space.
+///
+/// # Examples
+///
+/// ```
+/// use rustychain::thread_nonces;
+///
+/// assert_eq!(thread_nonces(1, 4).take(3).collect::<Vec<_>>(), [1, 5, 9]);
+/// assert_eq!(thread_nonces(0, 1).take(3).collect::<Vec<_>>(), [0, 1, 2]);
+/// ```
+pub fn thread_nonces(thread: usize, threads: usize) -> StepBy<RangeFrom<u64>> {
+    (thread as u64..).step_by(threads.max(1))
+}
+
+/// Sets the nice level of the current thread, so the OS schedules it with a lower (or, with
+/// privileges, a higher) priority.
+///
+/// Only Linux has per-thread nice levels; elsewhere it fails with `io::ErrorKind::Unsupported`
+/// and the priority is left unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use rustychain::set_thread_nice;
+///
+/// std::thread::spawn(|| {
+///     if cfg!(target_os = "linux") {
+///         set_thread_nice(10).unwrap();
+///     } else {
+///         assert!(set_thread_nice(10).is_err());
+///     }
+/// })
+/// .join()
+/// .unwrap();
+/// ```
+pub fn set_thread_nice(nice: i32) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        // SAFETY: the calls only read and change the scheduling attributes of the current thread
+        let tid = unsafe { libc::syscall(libc::SYS_gettid) } as libc::id_t;
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, nice) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = nice;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "per-thread nice levels are supported only on Linux",
+        ))
+    }
+}
+
+/// Searches the nonce for the block on several threads until one is found or `stop` is set.
+///
+/// Every thread tries the nonces of its own residue class (see `thread_nonces`), so no nonce is
+/// tried twice. If not all threads can be spawned (or `threads` is 0), the search falls back to
+/// the current thread trying every nonce, so a block is mined anyway.
+///
+/// With `nice` the spawned threads set their nice level with `set_thread_nice`; if it fails, they
+/// mine with the normal priority.
+///
+/// Returns the hash and the nonce with the number of nonces tried by all threads.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::atomic::AtomicBool;
+///
+/// use rustychain::{mine_blocking, Block, DifficultyMode};
+///
+/// let block = Block::new(0, "Some data".to_owned());
+/// let stop = AtomicBool::new(false);
+/// let (hash, nonce, attempts) =
+///     mine_blocking(&block, "0", DifficultyMode::Suffix, 2, None, &stop).unwrap();
+///
+/// assert_eq!(block.hash_with_nonce(nonce), hash);
+/// assert!(hex::encode(hash).ends_with('0'));
+/// assert!(attempts > 0);
+/// ```
+pub fn mine_blocking(
+    block: &Block,
+    difficulty: &str,
+    mode: DifficultyMode,
+    threads: usize,
+    nice: Option<i32>,
+    stop: &AtomicBool,
+) -> Option<Solution> {
+    let found = AtomicBool::new(false);
+    let tried = AtomicU64::new(0);
+    let search = |thread: usize, threads: usize| {
+        let mut attempts = 0;
+        let mut solution = None;
+        for nonce in thread_nonces(thread, threads) {
+            if stop.load(Ordering::Relaxed) || found.load(Ordering::Relaxed) {
+                break;
+            }
+            attempts += 1;
+            let hash = block.hash_with_nonce(nonce);
+            if mode.matches(&hash, difficulty) {
+                found.store(true, Ordering::Relaxed);
+                solution = Some((hash, nonce));
+                break;
+            }
+        }
+        // counted once per thread, so threads don't contend on every hash
+        tried.fetch_add(attempts, Ordering::Relaxed);
+        solution
+    };
+
+    let threads = threads.min(MAX_THREADS);
+    let solution = thread::scope(|scope| {
+        let mut handles = Vec::new();
+        for i in 0..threads {
+            match thread::Builder::new()
+                .name(format!("miner-{i}"))
+                .spawn_scoped(scope, move || {
+                    if let Some(nice) = nice {
+                        if let Err(e) = set_thread_nice(nice) {
+                            debug!("Can't set nice level {nice} of mining thread: {e}");
+                        }
+                    }
+                    search(i, threads)
+                }) {
+                Ok(handle) => handles.push(handle),
+                Err(e) => {
+                    warn!("Can't spawn mining thread: {e}");
+                    // the residue classes of missing threads wouldn't be searched
+                    found.store(true, Ordering::Relaxed);
+                    break;
+                }
+            }
+        }
+        let spawned = handles.len();
+        let solution = handles
+            .into_iter()
+            .filter_map(|handle| handle.join().ok().flatten())
+            .next();
+        if solution.is_some() || (spawned > 0 && spawned == threads) {
+            return solution;
+        }
+        warn!("Not all mining threads are spawned, mining on the current thread");
+        found.store(false, Ordering::Relaxed);
+        search(0, 1)
+    });
+    solution.map(|(hash, nonce)| (hash, nonce, tried.load(Ordering::Relaxed)))
+}
+
+/// Mines blocks like `nonce_worker`, but on several threads with `mine_blocking`.
+///
+/// A new job replaces the current one, which is stopped. Mining threads get the `nice` level (see
+/// `mine_blocking`).
+pub async fn parallel_worker(
+    mut rx: Receiver<(Block, String)>,
+    tx: Sender<Solution>,
+    mut cancel_rx: broadcast::Receiver<()>,
+    threads: usize,
+    nice: Option<i32>,
+    mode: DifficultyMode,
+) {
+    let mut job: Option<(Arc<AtomicBool>, JoinHandle<Option<Solution>>)> = None;
+    loop {
+        tokio::select! {
+            _ = cancel_rx.recv() => break,
+            m = rx.recv() => {
+                if let Some((stop, _)) = job.take() {
+                    stop.store(true, Ordering::Relaxed);
+                }
+                let (block, diff) = match m {
+                    Some(m) => m,
+                    None => break,
+                };
+                let stop = Arc::new(AtomicBool::new(false));
+                let flag = stop.clone();
+                let handle = task::spawn_blocking(move || {
+                    mine_blocking(&block, &diff, mode, threads, nice, &flag)
+                });
+                job = Some((stop, handle));
+            },
+            res = async { (&mut job.as_mut().unwrap().1).await }, if job.is_some() => {
+                job = None;
+                if let Ok(Some(result)) = res {
+                    if let Err(e) = tx.send(result).await {
+                        error!("Error around worker {:?}", e);
+                    }
+                }
+            },
+        }
+    }
+    if let Some((stop, _)) = job {
+        stop.store(true, Ordering::Relaxed);
+    }
+}
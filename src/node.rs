@@ -1,14 +1,53 @@
-use log::{error, info, warn};
+use std::{
+    collections::{HashMap, VecDeque},
+    slice,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use futures::{Stream, StreamExt};
+use log::{debug, error, info, warn};
 
 use tokio::{
     sync::{
         broadcast,
-        mpsc::{self, Receiver, Sender},
+        mpsc::{self, error::SendError, Receiver, Sender},
     },
-    task::{self},
+    task::{self, AbortHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    archive_block, expected_attempts, mining_threads, parallel_worker, AcceptAll, Backoff, Block,
+    Chain, DifficultyMode, Direction, Health, LogEntry, Message, MessageLog, MessageStats,
+    NodeConfig, NodeDebug, NodeEvent, OrphanPool, RejectKind, StallPolicy, TransactionValidator,
+    ValidationPolicy,
 };
 
-use crate::{Block, Chain, Message};
+use crate::{chain::remine, mining::Solution};
+
+/// The maximum delay between repeated chain requests, see `NodeConfig::sync_retry`.
+const SYNC_RETRY_MAX: Duration = Duration::from_secs(60);
+
+/// How often bodies of old blocks are pruned with `NodeConfig::retention`.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How many bits of work more than the difficulty has the tip can be mined again for, see
+/// `Message::RemineTip`.
+const REMINE_EXTRA_BITS: u32 = 8;
+
+/// How long a block submitted with `NodeConfig::defer_own` waits to be relayed before it's queued.
+const DEFER_OWN_DELAY: Duration = Duration::from_secs(2);
+
+/// How often the number of peers is checked while waiting for `NodeConfig::min_peers_to_mine`.
+const PEERS_POLL: Duration = Duration::from_millis(500);
+
+/// How many times the expected number of attempts `Node::replay` tries to mine a block before
+/// giving up, so a difficulty which can't be met doesn't hang it.
+const REPLAY_ATTEMPTS_FACTOR: f64 = 64.0;
 
 /// Represents a node in the blockchain network.
 #[derive(Debug)]
@@ -21,8 +60,52 @@ pub struct Node {
     rx: Receiver<Message>,
     /// The receiver used to make graceful shutdown of thread.
     rx_cancel: broadcast::Receiver<()>,
-    /// The difficulty level for mining blocks in the blockchain.
+    /// The initial difficulty level for mining blocks, used until the chain records one (see
+    /// `difficulty`).
     difficult: String,
+    /// The mining job sent to the worker.
+    job: MiningJob,
+    /// The counters of received and sent messages.
+    stats: Arc<Mutex<MessageStats>>,
+    /// The optional settings of the node.
+    config: NodeConfig,
+    /// The health of the node, updated while it runs.
+    health: Arc<Mutex<Health>>,
+    /// Canonical ids of `NewBlock` echoes of blocks submitted by the host, with their counts.
+    own: HashMap<[u8; 32], usize>,
+    /// Blocks submitted by the host with `NodeConfig::defer_own`, with their canonical ids and
+    /// when they're queued if nobody relays them.
+    deferred: VecDeque<(Instant, [u8; 32], Block)>,
+    /// When the last block was appended from the queue.
+    last_added: Option<Instant>,
+    /// When the last chain from another node was received.
+    last_sync: Option<Instant>,
+    /// Until when the node waits for the chain from other nodes before mining, see
+    /// `NodeConfig::initial_sync`.
+    sync_deadline: Option<Instant>,
+    /// Control of the chain sync, which can be cancelled without stopping the node.
+    sync: Arc<SyncControl>,
+    /// Peers which sent blocks to the queue, by `Block::data_hash` of the blocks.
+    origins: HashMap<[u8; 32], String>,
+    /// `Block::data_hash` of blocks submitted by the host which wait for confirmations.
+    watched: Vec<[u8; 32]>,
+    /// The sender of events of the node.
+    events: broadcast::Sender<NodeEvent>,
+    /// The application rules for transactions of new blocks.
+    validator: Box<dyn TransactionValidator>,
+    /// The number of blocks mined by the node since the last heartbeat.
+    mined_since_heartbeat: u64,
+    /// The number of blocks mined by the node.
+    mined_total: u64,
+    /// Mined blocks ahead of the chain, see `NodeConfig::orphan_window`.
+    orphans: OrphanPool,
+    /// The task of the running mining worker, so tests can stop it.
+    pub(crate) worker: Arc<Mutex<Option<AbortHandle>>>,
+    /// The log of received and sent messages, see `NodeConfig::record`.
+    log: Option<Mutex<MessageLog>>,
+    /// The search for a new nonce of the tip requested with `Message::RemineTip`, which returns
+    /// the previous hash of the tip and the mined tip.
+    remine: Option<task::JoinHandle<([u8; 32], Block)>>,
 }
 
 impl Node {
@@ -38,167 +121,1349 @@ impl Node {
     ///
     /// # Returns
     ///
-    /// A new Node instance with the given parameters.
+    /// A new Node instance with the given parameters. Unless the chain has a recorded difficulty
+    /// (see `Chain::difficulty`), the node's difficulty is recorded. Blocks are mined with the
+    /// difficulty of the chain from then on, so nodes which take the same chain agree on it.
+    ///
+    /// Other settings are given with `with_config`, including how mining runs: there's no worker
+    /// count parameter here, the number of workers racing on a block is
+    /// `NodeConfig::nonce_workers` (or `NodeConfig::mining_threads` for threads).
     pub fn new(
-        chain: Chain,
+        mut chain: Chain,
         tx: Sender<Message>,
         rx: Receiver<Message>,
         rx_cancel: broadcast::Receiver<()>,
         difficult: String,
     ) -> Self {
+        if chain.difficulty.is_empty() {
+            chain.difficulty = difficult.clone();
+        }
         Self {
             chain,
             tx,
             rx,
             rx_cancel,
             difficult,
+            job: MiningJob::default(),
+            stats: Arc::new(Mutex::new(MessageStats::default())),
+            config: NodeConfig::default(),
+            health: Arc::new(Mutex::new(Health::default())),
+            own: HashMap::new(),
+            deferred: VecDeque::new(),
+            last_added: None,
+            last_sync: None,
+            sync_deadline: None,
+            sync: Arc::new(SyncControl::default()),
+            origins: HashMap::new(),
+            watched: Vec::new(),
+            events: broadcast::channel(16).0,
+            validator: Box::new(AcceptAll),
+            mined_since_heartbeat: 0,
+            mined_total: 0,
+            orphans: OrphanPool::default(),
+            worker: Arc::new(Mutex::new(None)),
+            log: None,
+            remine: None,
+        }
+    }
+
+    /// Sets optional settings of the node.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::{Chain, Node, NodeConfig};
+    /// use tokio::sync::{broadcast, mpsc};
+    ///
+    /// let (tx, _rx_test) = mpsc::channel(1);
+    /// let (_tx_test, rx) = mpsc::channel(1);
+    /// let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+    ///
+    /// let node = Node::new(Chain::new(), tx, rx, rx_cancel, String::from("00"))
+    ///     .with_config(NodeConfig::default());
+    /// ```
+    pub fn with_config(mut self, config: NodeConfig) -> Self {
+        self.health.lock().unwrap().solo = config.solo || config.solo_mining;
+        let orphans = OrphanPool::new(config.orphan_window.unwrap_or(0));
+        self.orphans = match config.limits.max_orphans {
+            Some(max) => orphans.with_max(max),
+            None => orphans,
+        };
+        self.chain.difficulty_mode = config.difficulty_mode;
+        self.log = config
+            .record
+            .as_deref()
+            .and_then(|path| match MessageLog::create(path) {
+                Ok(log) => Some(Mutex::new(log)),
+                Err(e) => {
+                    error!("Can't record messages to {}: {e}", path.display());
+                    None
+                }
+            });
+        self.config = config;
+        self
+    }
+
+    /// Sets the application rules for transactions: new blocks are queued only if the validator
+    /// accepts their data. By default every transaction is accepted (see `AcceptAll`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::{AcceptAll, Chain, Node};
+    /// use tokio::sync::{broadcast, mpsc};
+    ///
+    /// let (tx, _rx_test) = mpsc::channel(1);
+    /// let (_tx_test, rx) = mpsc::channel(1);
+    /// let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+    ///
+    /// let node = Node::new(Chain::new(), tx, rx, rx_cancel, String::from("00"))
+    ///     .with_validator(AcceptAll);
+    /// ```
+    pub fn with_validator(mut self, validator: impl TransactionValidator + 'static) -> Self {
+        self.validator = Box::new(validator);
+        self
+    }
+
+    /// Returns the counters of messages received and sent by the node.
+    ///
+    /// The counters are shared, so they can be read while the node is running.
+    pub fn stats(&self) -> Arc<Mutex<MessageStats>> {
+        self.stats.clone()
+    }
+
+    /// Returns the health of the node.
+    ///
+    /// The health is shared, so it can be read while the node is running. The number of peers
+    /// isn't known to the node and should be updated by the owner of the network.
+    pub fn health(&self) -> Arc<Mutex<Health>> {
+        self.health.clone()
+    }
+
+    /// Returns how long the node should wait before appending the next block from the queue,
+    /// see `NodeConfig::min_block_interval`.
+    fn interval_delay(&self) -> Duration {
+        match (self.config.min_block_interval, self.last_added) {
+            (Some(interval), Some(last)) => interval.saturating_sub(last.elapsed()),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Returns a receiver of events of the node.
+    pub fn subscribe(&self) -> broadcast::Receiver<NodeEvent> {
+        self.events.subscribe()
+    }
+
+    /// Emits `NodeEvent::Confirmed` for watched blocks with enough blocks mined on top of them.
+    fn check_confirmations(&mut self) {
+        let target = match self.config.confirmations {
+            Some(target) => target,
+            None => return,
+        };
+        let mined = self.chain.blocks.len() - usize::from(!self.chain.status);
+        let blocks = &self.chain.blocks[..mined];
+        let events = &self.events;
+        self.watched.retain(|data_hash| {
+            let block = match blocks
+                .iter()
+                .rev()
+                .find(|block| &block.data_hash() == data_hash)
+            {
+                Some(block) => block,
+                None => return true,
+            };
+            let confirmations = mined as u64 - block.id - 1;
+            if confirmations < target {
+                return true;
+            }
+            // nobody may listen
+            let _ = events.send(NodeEvent::Confirmed {
+                id: block.id,
+                data: block.data.clone(),
+                confirmations,
+            });
+            false
+        });
+    }
+
+    /// Returns the chain of the node.
+    pub fn chain(&self) -> &Chain {
+        &self.chain
+    }
+
+    /// Returns the difficulty blocks are mined with: the one recorded in the chain, which is
+    /// taken from other nodes with their chain, or the initial difficulty of the node.
+    pub fn difficulty(&self) -> &str {
+        if self.chain.difficulty.is_empty() {
+            &self.difficult
+        } else {
+            &self.chain.difficulty
+        }
+    }
+
+    /// Returns the control of the chain sync, so the sync can be cancelled while the node is running.
+    pub fn sync_control(&self) -> Arc<SyncControl> {
+        self.sync.clone()
+    }
+
+    /// Returns the policy chains from other nodes are checked with: `NodeConfig::validation`,
+    /// with `NodeConfig::min_difficulty_bits` unless the policy sets its own minimum.
+    fn policy(&self) -> ValidationPolicy {
+        let mut policy = self.config.validation.clone();
+        policy.min_difficulty_bits = policy
+            .min_difficulty_bits
+            .or(self.config.min_difficulty_bits);
+        policy
+    }
+
+    /// Sends the feedback that a message was rejected.
+    async fn reject(&self, what: RejectKind, detail: String) {
+        warn!("Rejecting message, {}: {}", what, detail);
+        if let Err(e) = self.send(Message::Reject { what, detail }).await {
+            error!("Sending reject error: {:?}", e);
+        }
+    }
+
+    /// Returns the detail of rejection if any of the mined blocks has less work than
+    /// `NodeConfig::min_difficulty_bits`. The genesis block isn't mined, so it's skipped.
+    fn low_difficulty(&self, blocks: &[Block]) -> Option<String> {
+        let min = self.config.min_difficulty_bits?;
+        let mode = self.chain.difficulty_mode;
+        let block = blocks
+            .iter()
+            .find(|block| block.id > 0 && block.work_bits(mode) < min)?;
+        Some(format!(
+            "block #{} has {} bits of work, the minimum is {}",
+            block.id,
+            block.work_bits(mode),
+            min
+        ))
+    }
+
+    /// Returns the detail of rejection if the transactions of the block are larger than
+    /// `NodeConfig::max_block_size` together.
+    fn oversized(&self, block: &Block) -> Option<String> {
+        let max = self.config.max_block_size?;
+        let size: usize = block.transactions().map(str::len).sum();
+        if size <= max {
+            return None;
+        }
+        Some(format!(
+            "block data is {} bytes, the limit is {} bytes",
+            size, max
+        ))
+    }
+
+    /// Returns the detail of rejection if the validator of the node rejects some transaction of
+    /// the block.
+    fn invalid(&self, block: &Block) -> Option<String> {
+        block.transactions().find_map(|tx| {
+            let reason = self.validator.validate(tx, &self.chain).err()?;
+            Some(format!("\"{}\": {}", tx, reason))
+        })
+    }
+
+    /// Returns false if the peer already has `NodeConfig::max_queued_per_peer` blocks in the queue.
+    fn accepts_from(&self, peer: &str) -> bool {
+        let max = match self.config.max_queued_per_peer {
+            Some(max) => max,
+            None => return true,
+        };
+        let queued = self
+            .chain
+            .queue
+            .iter()
+            .filter(|block| self.origins.get(&block.data_hash()).map(String::as_str) == Some(peer))
+            .count();
+        queued < max
+    }
+
+    /// Returns true if the block competes with the mined last block, which is kept with
+    /// `NodeConfig::prefer_first_seen`.
+    fn keeps_tip(&self, block: &Block) -> bool {
+        let last = self.chain.blocks.last();
+        self.config.prefer_first_seen && self.chain.status && last.map(|b| b.id) == Some(block.id)
+    }
+
+    /// Appends the kept blocks which follow the chain now, see `NodeConfig::orphan_window`.
+    fn connect_orphans(&mut self) {
+        let connected = self.orphans.connect(&mut self.chain);
+        if connected.is_empty() {
+            return;
+        }
+        info!(
+            "Connected {} blocks kept ahead of the chain",
+            connected.len()
+        );
+        for block in connected.iter() {
+            appended(&self.config, block);
+        }
+    }
+
+    /// Emits `NodeEvent::Reorg` if any mined blocks are removed from the chain.
+    fn reorg(&self, removed: Vec<[u8; 32]>, added: Vec<[u8; 32]>) {
+        if removed.is_empty() {
+            return;
+        }
+        warn!(
+            "Reorg: {} blocks removed, {} added",
+            removed.len(),
+            added.len()
+        );
+        // nobody may be subscribed
+        let _ = self.events.send(NodeEvent::Reorg { removed, added });
+    }
+
+    /// Sends the block which isn't mined within `NodeConfig::mining_timeout` to the worker again,
+    /// lowering the difficulty with `StallPolicy::LowerDifficulty`.
+    async fn stalled(&mut self, worker: &Sender<(Block, String)>) {
+        self.job.finish();
+        let block = match self.chain.blocks.last() {
+            Some(block) if !self.chain.status => block.clone(),
+            _ => return,
+        };
+        let mut difficulty = self.difficulty().to_owned();
+        if self.config.stall_policy == StallPolicy::LowerDifficulty && difficulty.len() > 2 {
+            difficulty.drain(..2);
+            self.chain.difficulty = difficulty.clone();
+        }
+        warn!(
+            "Block #{} isn't mined in time, mining it again with difficulty {}",
+            block.id, difficulty
+        );
+        // nobody may be subscribed
+        let _ = self.events.send(NodeEvent::MiningStalled {
+            id: block.id,
+            difficulty: difficulty.clone(),
+        });
+        self.job.start(block.id);
+        if let Err(e) = worker.send((block, difficulty)).await {
+            warn!("Can't send data to worker: {e}");
+        }
+    }
+
+    /// Spawns the mining worker: `parallel_worker` with `NodeConfig::mining_threads` or
+    /// `NodeConfig::mining_nice`, `nonce_workers` with `NodeConfig::nonce_workers`, `nonce_worker`
+    /// otherwise. Returns the channels to send jobs to
+    /// the worker and to receive its results.
+    fn spawn_worker(
+        &self,
+        cancel_rx: broadcast::Receiver<()>,
+    ) -> (Sender<(Block, String)>, Receiver<Solution>) {
+        let (tx_node, rx) = mpsc::channel::<(Block, String)>(16);
+        let (tx, rx_node) = mpsc::channel::<Solution>(16);
+        let nice = self.config.mining_nice;
+        let threads = match (self.config.mining_threads, nice) {
+            (Some(n), _) => Some(mining_threads(Some(n))),
+            // the nice level can be set only on own mining threads
+            (None, Some(_)) => Some(1),
+            (None, None) => None,
+        };
+        let mode = self.chain.difficulty_mode;
+        let workers = self.config.nonce_workers.unwrap_or(1);
+        let task = task::spawn(async move {
+            match threads {
+                Some(threads) => parallel_worker(rx, tx, cancel_rx, threads, nice, mode).await,
+                None if workers > 1 => nonce_workers(rx, tx, cancel_rx, workers, mode).await,
+                None => nonce_worker(rx, tx, cancel_rx, mode).await,
+            }
+        });
+        *self.worker.lock().unwrap() = Some(task.abort_handle());
+        (tx_node, rx_node)
+    }
+
+    /// Sends the last block to the restarted worker again if it isn't mined yet.
+    async fn redispatch(&mut self, worker: &Sender<(Block, String)>) {
+        self.job.finish();
+        let block = match self.chain.blocks.last() {
+            Some(block) if !self.chain.status => block.clone(),
+            _ => return,
+        };
+        info!("Mining block #{} again", block.id);
+        self.job.start(block.id);
+        if let Err(e) = worker.send((block, self.difficulty().to_owned())).await {
+            warn!("Can't send data to worker: {e}");
+        }
+    }
+
+    /// Logs the summary of the node's state and emits it as `NodeEvent::Heartbeat`.
+    fn heartbeat(&mut self) {
+        let state = self.debug_info();
+        let mined = std::mem::take(&mut self.mined_since_heartbeat);
+        info!(
+            "Heartbeat: height {}, {}, queue {}, peers {}, mined {} blocks",
+            state.height,
+            if state.status { "ready" } else { "mining" },
+            state.queue,
+            state.peers,
+            mined
+        );
+        // nobody may be subscribed
+        let _ = self.events.send(NodeEvent::Heartbeat { state, mined });
+    }
+
+    /// Returns false while fewer peers than `NodeConfig::min_peers_to_mine` are connected.
+    fn enough_peers(&self) -> bool {
+        match self.config.min_peers_to_mine {
+            Some(min) => self.health.lock().unwrap().peers >= min,
+            None => true,
+        }
+    }
+
+    /// Returns a snapshot of the internal state of the node.
+    pub fn debug_info(&self) -> NodeDebug {
+        NodeDebug {
+            height: self.chain.blocks.len(),
+            status: self.chain.status,
+            queue: self.chain.queue.len(),
+            in_flight: self.job.in_flight(),
+            last_sync_secs: self.last_sync.map(|t| t.elapsed().as_secs()),
+            peers: self.health.lock().unwrap().peers,
+            orphans: self.orphans.len(),
+        }
+    }
+
+    /// Updates the health from the current chain.
+    fn update_health(&self) {
+        let mut health = self.health.lock().unwrap();
+        health.height = self.chain.blocks.len() - usize::from(!self.chain.status);
+        health.valid = self.chain.have_errors().is_none();
+        health.syncing = self.sync_deadline.is_some();
+    }
+
+    /// Records the message with `NodeConfig::record`.
+    fn record(&self, direction: Direction, msg: &Message) {
+        if let Some(log) = &self.log {
+            if let Err(e) = log.lock().unwrap().record(direction, msg) {
+                error!("Can't record message: {e}");
+            }
+        }
+    }
+
+    /// Sends the message to the network, counting it.
+    async fn send(&self, msg: Message) -> Result<(), SendError<Message>> {
+        self.stats.lock().unwrap().count_out(&msg);
+        self.record(Direction::Out, &msg);
+        self.tx.send(msg).await
+    }
+
+    /// Handles the message received from the network or the host.
+    async fn handle(&mut self, message: Message) {
+        self.stats.lock().unwrap().count_in(&message);
+        self.record(Direction::In, &message);
+        let (peer, message) = match message {
+            Message::Envelope(peer, msg) => (Some(peer), *msg),
+            message => (None, message),
+        };
+        match message {
+            Message::SubmitBlock(_) | Message::InjectBlock(_) if peer.is_some() => {
+                warn!(
+                    "Only the host can submit or inject blocks, dropping {} from {}",
+                    message,
+                    peer.unwrap_or_default()
+                );
+            }
+            Message::NewBlock(mut block) => {
+                if let Some(detail) = self.oversized(&block) {
+                    self.reject(RejectKind::TooLarge, detail).await;
+                    return;
+                }
+                if let Some(detail) = self.invalid(&block) {
+                    self.reject(RejectKind::InvalidTransaction, detail).await;
+                    return;
+                }
+                let id = Message::NewBlock(block.clone()).canonical_id();
+                block.salt = self.config.network_id.clone();
+                if let Some(index) = self.deferred.iter().position(|(_, own, _)| *own == id) {
+                    debug!("Own transaction \"{}\" is relayed, queueing it", block.data);
+                    self.deferred.remove(index);
+                    self.queue_submitted(block).await;
+                    return;
+                }
+                match self.own.get_mut(&id) {
+                    Some(count) => {
+                        *count -= 1;
+                        if *count == 0 {
+                            self.own.remove(&id);
+                        }
+                        debug!("Ignoring echo of own transaction \"{}\"", block.data);
+                    }
+                    None => match peer {
+                        _ if self.config.limits.queue_full(self.chain.queue.len()) => {
+                            let detail = format!("queue is full, dropping \"{}\"", block.data);
+                            self.reject(RejectKind::QueueFull, detail).await;
+                        }
+                        Some(peer) if !self.accepts_from(&peer) => {
+                            let detail = format!(
+                                "peer {} has too many queued blocks, dropping \"{}\"",
+                                peer, block.data
+                            );
+                            self.reject(RejectKind::QueueFull, detail).await;
+                        }
+                        Some(peer) => {
+                            let queue = &self.chain.queue;
+                            self.origins.retain(|data_hash, _| {
+                                queue.iter().any(|b| &b.data_hash() == data_hash)
+                            });
+                            self.origins.insert(block.data_hash(), peer);
+                            self.queue_new(block).await;
+                        }
+                        None => self.queue_new(block).await,
+                    },
+                }
+            }
+            Message::SubmitBlock(mut block) => {
+                if let Some(detail) = self.oversized(&block) {
+                    self.reject(RejectKind::TooLarge, detail).await;
+                    return;
+                }
+                if let Some(detail) = self.invalid(&block) {
+                    self.reject(RejectKind::InvalidTransaction, detail).await;
+                    return;
+                }
+                if self.config.limits.queue_full(self.chain.queue.len()) {
+                    let detail = format!("queue is full, dropping \"{}\"", block.data);
+                    self.reject(RejectKind::QueueFull, detail).await;
+                    return;
+                }
+                let id = Message::NewBlock(block.clone()).canonical_id();
+                block.salt = self.config.network_id.clone();
+                if self.config.defer_own {
+                    // gossip doesn't deliver own messages back, so the block is queued anyway
+                    // after a while
+                    let at = Instant::now() + DEFER_OWN_DELAY;
+                    self.deferred.push_back((at, id, block));
+                    return;
+                }
+                *self.own.entry(id).or_default() += 1;
+                self.queue_submitted(block).await;
+            }
+            Message::ChainRequest => {
+                self.apply(&message).await;
+                info!("Serve chain request");
+            }
+            Message::BlockRequest(id) => {
+                self.apply(&message).await;
+                debug!("Serve request of block #{}", id);
+            }
+            Message::DebugRequest => {
+                if let Err(e) = self.send(Message::DebugResponse(self.debug_info())).await {
+                    error!("Sending debug info error: {:?}", e);
+                }
+            }
+            Message::DebugResponse(_) => {}
+            Message::Reject { what, detail } => info!("Message is rejected, {}: {}", what, detail),
+            Message::Ack { data_hash } => {
+                debug!("Transaction {} is queued by a node", hex::encode(data_hash));
+            }
+            Message::Envelope(peer, _) => warn!("Dropping nested envelope from {}", peer),
+            Message::RemineTip(_) if peer.is_some() || !self.config.solo_mining => {
+                warn!("Re-mining the tip is allowed only for the host in solo mining mode");
+            }
+            Message::RemineTip(_) if self.remine.is_some() => {
+                warn!("The tip is being re-mined already");
+            }
+            Message::RemineTip(bits) => {
+                let max = 4 * self.difficulty().len() as u32 + REMINE_EXTRA_BITS;
+                if bits > max {
+                    warn!(
+                        "Can't re-mine the tip for {} bits, the limit is {}",
+                        bits, max
+                    );
+                    return;
+                }
+                match self.chain.remine_job(bits) {
+                    Ok(Some(tip)) => {
+                        info!("Re-mining the tip for {} bits", bits);
+                        let (difficulty, mode) =
+                            (self.difficulty().to_owned(), self.chain.difficulty_mode);
+                        self.remine = Some(task::spawn_blocking(move || {
+                            (tip.hash, remine(tip, bits, &difficulty, mode))
+                        }));
+                    }
+                    Ok(None) => info!("The tip has {} bits of work already", bits),
+                    Err(e) => warn!("Can't re-mine the tip: {}", e),
+                }
+            }
+            Message::ChainResponce(_) if self.config.solo_mining => {
+                debug!("Ignoring chain from another node in solo mining mode");
+            }
+            Message::MinedBlock(block) if self.config.solo_mining => {
+                debug!("Ignoring mined block #{} in solo mining mode", block.id);
+            }
+            Message::BlockResponse(_) if self.config.solo_mining => {
+                debug!("Ignoring requested block in solo mining mode");
+            }
+            Message::BlockResponse(None) => debug!("Requested block isn't found"),
+            Message::BlockResponse(Some(block)) => {
+                if let Some(detail) = self.low_difficulty(slice::from_ref(&block)) {
+                    self.reject(RejectKind::LowDifficulty, detail).await;
+                    return;
+                }
+                if self.orphans.insert(block.clone(), &self.chain) {
+                    debug!("Keeping block #{} ahead of the chain", block.id);
+                }
+                self.apply(&Message::BlockResponse(Some(block))).await;
+                self.connect_orphans();
+            }
+            Message::ChainResponce(chain) => {
+                // the blocks a taken chain replaces are reported, so they're found beforehand
+                let fork = self.chain.fork_point(&chain);
+                let own = self.chain.blocks.len() - usize::from(!self.chain.status);
+                let removed = hashes(&self.chain.blocks[fork.min(own)..own]);
+                let tip = self.chain.blocks.last().map(|block| block.hash);
+                let len = self.chain.blocks.len();
+
+                let policy = self.policy();
+                let sync = &self.sync;
+                sync.begin();
+                let replies = self
+                    .chain
+                    .apply_chain(chain, &policy, &|| sync.is_cancelled());
+                sync.end();
+                let replies = match replies {
+                    Some(replies) => replies,
+                    None => {
+                        warn!("Sync with chain from another node is cancelled");
+                        return;
+                    }
+                };
+                if replies.is_empty() {
+                    self.last_sync = Some(Instant::now());
+                    self.sync_deadline = None;
+                }
+                for reply in replies {
+                    if let Message::Reject { what, detail } = reply {
+                        self.reject(what, detail).await;
+                    }
+                }
+                if self.chain.blocks.len() == len && self.chain.blocks.last().map(|b| b.hash) == tip
+                {
+                    return;
+                }
+                self.job.finish();
+                // The tip of a busy chain has no final hash yet
+                let unmined = usize::from(!self.chain.status);
+                let mined = self.chain.blocks.len() - unmined;
+                for block in self.chain.blocks[fork.min(mined)..mined].iter() {
+                    appended(&self.config, block);
+                }
+                let added = hashes(&self.chain.blocks[fork.min(mined)..mined]);
+                self.reorg(removed, added);
+                self.connect_orphans();
+            }
+            Message::InjectBlock(block) => {
+                if block.salt != self.config.network_id {
+                    warn!("Injected block is mined for other network: {}", block);
+                    return;
+                }
+                if !self
+                    .chain
+                    .difficulty_mode
+                    .matches(&block.hash, self.difficulty())
+                {
+                    warn!("Injected block doesn't match difficulty: {}", block);
+                    return;
+                }
+                self.apply(&Message::InjectBlock(block)).await;
+            }
+            Message::MinedBlock(block) => {
+                if let Some(detail) = self.low_difficulty(slice::from_ref(&block)) {
+                    self.reject(RejectKind::LowDifficulty, detail).await;
+                    return;
+                }
+                if self.keeps_tip(&block) {
+                    debug!("Keeping first seen block #{}", block.id);
+                    return;
+                }
+                if self.orphans.insert(block.clone(), &self.chain) {
+                    debug!("Keeping block #{} ahead of the chain", block.id);
+                }
+                self.apply(&Message::MinedBlock(block)).await;
+            }
+        }
+    }
+
+    /// Puts the block submitted by the host into the queue, watching it with
+    /// `NodeConfig::confirmations`.
+    async fn queue_submitted(&mut self, block: Block) {
+        if self.config.confirmations.is_some() {
+            self.watched.push(block.data_hash());
+        }
+        self.apply(&Message::SubmitBlock(block)).await;
+    }
+
+    /// Queues the blocks deferred with `NodeConfig::defer_own` until `now` which nobody relayed,
+    /// ignoring their later echoes.
+    async fn queue_deferred(&mut self, now: Instant) {
+        while self.deferred.front().is_some_and(|(at, ..)| *at <= now) {
+            let (_, id, block) = self.deferred.pop_front().unwrap();
+            debug!(
+                "Own transaction \"{}\" isn't relayed, queueing it",
+                block.data
+            );
+            *self.own.entry(id).or_default() += 1;
+            self.queue_submitted(block).await;
+        }
+    }
+
+    /// Puts the new block into the queue, acknowledging it with `NodeConfig::ack_new_blocks`.
+    async fn queue_new(&mut self, block: Block) {
+        let data_hash = block.data_hash();
+        self.apply(&Message::NewBlock(block)).await;
+        if self.config.ack_new_blocks {
+            if let Err(e) = self.send(Message::Ack { data_hash }).await {
+                error!("Sending ack error: {:?}", e);
+            }
+        }
+    }
+
+    /// Applies the message to the chain with `Chain::apply_message` and sends the replies.
+    ///
+    /// The tip which got its final hash is archived, and the mining job is finished if the tip
+    /// was mined by another node.
+    async fn apply(&mut self, msg: &Message) {
+        let busy = !self.chain.status;
+        let tip = self.chain.blocks.last().map(|block| (block.id, block.hash));
+        for reply in self.chain.apply_message(msg) {
+            if let Err(e) = self.send(reply).await {
+                error!("Sending error: {:?}", e);
+            }
+        }
+        if busy && self.chain.status {
+            self.job.finish();
+        }
+        if let (true, Some(last)) = (self.chain.status, self.chain.blocks.last()) {
+            let changed = match tip {
+                Some((id, hash)) => last.id > id || (last.id == id && (busy || last.hash != hash)),
+                None => true,
+            };
+            if changed {
+                appended(&self.config, last);
+            }
+            match tip {
+                Some((id, hash)) if !busy && last.id == id && last.hash != hash => {
+                    let added = vec![last.hash];
+                    self.reorg(vec![hash], added);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Takes the hash and nonce found by the worker for the last block of the chain, recording
+    /// the number of nonces tried in `Block::mining_attempts`.
+    async fn mined(&mut self, nonce: Solution) {
+        self.job.finish();
+        if !self.chain.status {
+            let mut cloned_block = self.chain.blocks.last().unwrap().clone();
+            cloned_block.hash = nonce.0;
+            cloned_block.nonce = nonce.1;
+            cloned_block.mining_attempts = Some(nonce.2);
+            if cloned_block.hash == cloned_block.calc_hash() {
+                self.chain.replace_tip_if(cloned_block.clone(), |_, _| true);
+                self.chain.status = true;
+                self.mined_since_heartbeat += 1;
+                self.mined_total += 1;
+                appended(&self.config, &cloned_block);
+
+                if let Err(e) = self.send(Message::MinedBlock(cloned_block)).await {
+                    error!("Sending error: {:?}", e)
+                }
+                info!("Mined!");
+                self.retarget();
+            }
+        }
+    }
+
+    /// Takes the tip mined again for `Message::RemineTip` if the chain still ends with the
+    /// previous one, archiving it and sending it to other nodes.
+    async fn remined(&mut self, old: [u8; 32], tip: Block) {
+        match self.chain.blocks.last() {
+            Some(last) if self.chain.status && last.hash == old => {}
+            _ => {
+                warn!(
+                    "The tip is changed while it was re-mined, dropping #{}",
+                    tip.id
+                );
+                return;
+            }
+        }
+        info!("Re-mined the tip: {}", tip);
+        *self.chain.blocks.last_mut().unwrap() = tip.clone();
+        appended(&self.config, &tip);
+        self.reorg(vec![old], vec![tip.hash]);
+        if let Err(e) = self.send(Message::MinedBlock(tip)).await {
+            error!("Sending error: {:?}", e);
+        }
+    }
+
+    /// Drops bodies of blocks older than `NodeConfig::retention`.
+    fn prune_old_bodies(&mut self) {
+        let retention = match self.config.retention {
+            Some(retention) => retention,
+            None => return,
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let pruned = self
+            .chain
+            .prune_older_than(now.saturating_sub(retention.as_secs()));
+        if pruned > 0 {
+            info!("Bodies of {pruned} blocks older than {retention:?} are pruned");
+        }
+    }
+
+    /// Returns false if a block from the queue would be appended after a last block with a stale
+    /// hash (see `Block::is_self_consistent`), so it would point to the wrong hash.
+    fn can_build_on_tip(&self) -> bool {
+        match self.chain.blocks.last() {
+            Some(tip) if !self.chain.queue.is_empty() && !tip.is_self_consistent() => {
+                error!(
+                    "Block #{} has a stale hash, not mining on top of it",
+                    tip.id
+                );
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Drops bodies of the oldest blocks over `MemoryLimits::max_chain_blocks`, keeping their
+    /// headers.
+    fn prune_over_limit(&mut self) {
+        let excess = self.config.limits.excess_bodies(self.chain.blocks.len());
+        // bodies are pruned from the oldest blocks, so the newest of them tells if it's done
+        if excess < 2 || self.chain.blocks[excess - 1].pruned {
+            return;
+        }
+        let pruned = self
+            .chain
+            .prune_bodies(|block| (block.id as usize) < excess);
+        if pruned > 0 {
+            debug!("Bodies of {pruned} blocks over the memory limit are pruned");
+        }
+    }
+
+    /// Recomputes the difficulty for `NodeConfig::target_block_time` from the times of the last
+    /// blocks, see `Chain::next_difficulty`.
+    fn retarget(&mut self) {
+        let target = match self.config.target_block_time {
+            Some(target) => target,
+            None => return,
+        };
+        let difficulty = self.chain.next_difficulty(target.as_secs());
+        if difficulty != self.difficulty() {
+            info!(
+                "Difficulty is retargeted from {} to {difficulty}",
+                self.difficulty()
+            );
+            self.difficult = difficulty.clone();
+            self.chain.difficulty = difficulty;
         }
     }
 
     /// Asynchronously runs the node, listening for incoming messages on the receive channel `self.rx`.
     /// Messages received are processed based on their type, which can be one of the following:
     ///
-    /// - `Message::NewBlock(block)`: Adds the new block to the node's chain queue.
+    /// - `Message::NewBlock(block)`: Adds the new block to the node's chain queue, unless it's the echo of a block submitted by the host or its peer has too many queued blocks (see `NodeConfig::max_queued_per_peer`).
+    ///
+    /// - `Message::Envelope(peer, msg)`: Handles the wrapped message as received from the peer.
+    ///
+    /// New blocks larger than `NodeConfig::max_block_size` or rejected by the validator (see `with_validator`), blocks over the peer's share of the queue and chains with errors are answered with `Message::Reject`.
+    ///
+    /// - `Message::SubmitBlock(block)`: Adds the block submitted by the host to the node's chain queue and ignores its echo. With `NodeConfig::defer_own` the block is queued when another node relays it back, or after a delay (gossip doesn't deliver own messages). With `NodeConfig::confirmations` the node emits `NodeEvent::Confirmed` when the block is confirmed (see `subscribe`).
     ///
     /// - `Message::ChainRequest`: Sends a chain response containing the node's current chain to the requesting node.
     ///
-    /// - `Message::ChainResponse(chain)`: Compares the received chain with the current chain, replacing the current chain if the received chain is longer and contains no errors.
+    /// - `Message::BlockRequest(id)`: Sends a block response containing the block with the id, or `None` if the chain doesn't have it.
+    ///
+    /// - `Message::BlockResponse(block)`: Appends the received block if it extends the tip of the chain and passes validation. Blocks ahead of the tip are kept with `NodeConfig::orphan_window`.
+    ///
+    /// - `Message::DebugRequest`: Sends a debug response containing the internal state of the node (see `debug_info`).
+    ///
+    /// - `Message::ChainResponse(chain)`: Compares the received chain with the current chain, replacing the current chain if the received chain has more work (see `Chain::total_work`), or as much work and more blocks, and contains no errors. Mined blocks replaced this way (or by a remote last block with a lower hash) are reported by `NodeEvent::Reorg`. Checking the chain can be cancelled with `sync_control`.
+    ///
+    /// - `Message::InjectBlock(block)`: Appends an already mined block to the chain and announces it as `MinedBlock`. Like `SubmitBlock`, it's dropped when it comes from another node in `Message::Envelope`.
+    ///
+    /// - `Message::RemineTip(bits)`: Mines the last block again for more work (see `Chain::remine_tip`) off the loop, up to 8 bits more than the difficulty, then archives and sends the new tip. Only the host can request it, in solo mining mode.
     ///
     /// - `Message::MinedBlock(block)`: Compares the received block with the node's current last block, replacing the last block with the received block if it has a higher block ID and passes validation. If the received block has the same block ID as the last block and the node is not currently mining, then the node takes the received block as its own.
     ///
     /// If the node is currently mining and the mining process is complete, the newly mined block is added to the node's chain queue and a new mining process is started.
+    /// If the mining worker stops, the node restarts it and sends it the block being mined again.
+    /// With `NodeConfig::min_block_interval` the next block from the queue isn't appended until the interval since the previous one elapses.
+    /// With `NodeConfig::prefer_first_seen` the mined last block isn't replaced by a remote one with a lower hash.
+    /// With `NodeConfig::initial_sync` the node requests the chain of other nodes on start and doesn't mine until it receives one or the timeout elapses.
+    /// With `NodeConfig::sync_retry` the request is repeated meanwhile with exponential backoff.
+    /// With `NodeConfig::limits` the node prunes bodies of old blocks, evicts orphan blocks and rejects new blocks over the limits.
+    /// With `NodeConfig::empty_block_interval` the node mines a block with empty data when its queue stays empty for the interval.
+    /// With `NodeConfig::min_peers_to_mine` the next block isn't mined until enough peers are connected.
+    /// With `NodeConfig::mine_blocks` the node mines empty blocks without waiting and stops after the given number of blocks.
+    /// With `NodeConfig::target_block_time` the difficulty is recomputed after every mined block (see `Chain::next_difficulty`).
+    /// With `NodeConfig::nonce_workers` several workers try random nonces of the same block.
+    /// With `NodeConfig::record` every received and sent message is recorded (see `replay_log`).
+    /// With `NodeConfig::retention` the node periodically drops bodies of old blocks, keeping their headers.
+    /// With `NodeConfig::heartbeat` the node periodically logs a summary of its state and emits `NodeEvent::Heartbeat`.
+    /// With `NodeConfig::mining_timeout` a block which isn't mined in time is sent to the worker again (see `NodeConfig::stall_policy`).
+    /// With `NodeConfig::orphan_window` mined blocks ahead of the chain are kept and appended once a chain from another node reaches them.
+    /// With `NodeConfig::ack_new_blocks` every block queued from `Message::NewBlock` is acknowledged with `Message::Ack`.
+    /// With `NodeConfig::mining_nice` mining threads run with the lower OS priority.
+    /// With `NodeConfig::validation` chains from other nodes are checked by the policy before they're taken.
+    /// With `NodeConfig::difficulty_mode` hashes of mined blocks must start with the difficulty instead of ending with it.
+    /// With `NodeConfig::solo_mining` mined blocks and chains from other nodes are ignored and there is no initial sync.
     ///
-    /// The `run` function never returns, but instead diverges into an infinite loop that continues to process incoming messages.
-    pub async fn run(&mut self) {
-        let (tx_node, rx) = mpsc::channel::<(Block, String)>(16);
-        let (tx, mut rx_node) = mpsc::channel::<([u8; 32], u64)>(16);
+    /// The chain is changed by `Chain::apply_message`, while the node adds its policies (see `NodeConfig`), mining and the cancellable check of received chains.
+    ///
+    /// The `run` function processes incoming messages in a loop until shutdown message is received on `rx_cancel` (or `NodeConfig::mine_blocks` are mined).
+    /// Then it returns blocks which weren't mined yet (see `drain_pending`), so they can be resumed later.
+    pub async fn run(&mut self) -> VecDeque<Block> {
+        let (cancel_tx, _) = broadcast::channel(1);
 
-        let (cancel_tx, cancel_rx) = broadcast::channel(1);
+        if self.config.mining_nice.is_some() && !cfg!(target_os = "linux") {
+            warn!("Nice level of mining threads isn't supported on this platform, ignoring it");
+        }
+        let (mut tx_node, mut rx_node) = self.spawn_worker(cancel_tx.subscribe());
 
-        let _task = task::spawn(async move {
-            nonce_worker(rx, tx, cancel_rx).await;
-        });
+        // don't mine until the chain from another node is received or the timeout elapses
+        self.sync_deadline = self
+            .config
+            .initial_sync
+            .filter(|_| !self.config.solo_mining)
+            .map(|timeout| Instant::now() + timeout);
+        if self.sync_deadline.is_some() {
+            if let Err(e) = self.send(Message::ChainRequest).await {
+                error!("Sending chain request error: {:?}", e);
+            }
+        }
+        // repeat the chain request until the initial sync is over
+        let mut backoff = self
+            .config
+            .sync_retry
+            .map(|base| Backoff::new(base, SYNC_RETRY_MAX));
+        let mut retry_at = backoff.as_mut().map(|b| Instant::now() + b.next_delay());
+        let mut heartbeat_at = self.config.heartbeat.map(|period| Instant::now() + period);
+        // old bodies are pruned on start too
+        let mut prune_at = self.config.retention.map(|_| Instant::now());
+        let mut idle_since = Instant::now();
 
         loop {
+            self.update_health();
+            self.check_confirmations();
+            let delay = self.interval_delay();
+            let throttled = self.chain.status && !self.chain.queue.is_empty() && !delay.is_zero();
+            let deadline = self.sync_deadline.unwrap_or_else(Instant::now);
+            let retrying = self.sync_deadline.is_some() && retry_at.is_some();
+            let retry = retry_at.unwrap_or_else(Instant::now);
+            let heartbeat = heartbeat_at.unwrap_or_else(Instant::now);
+            let deferred = self
+                .deferred
+                .front()
+                .map_or_else(Instant::now, |(at, ..)| *at);
+            if !self.chain.status || !self.chain.queue.is_empty() {
+                idle_since = Instant::now();
+            }
+            let empty_block = match self.config.mine_blocks {
+                Some(_) => Some(idle_since),
+                None => self
+                    .config
+                    .empty_block_interval
+                    .map(|interval| idle_since + interval),
+            };
+            let idle = empty_block.is_some() && self.sync_deadline.is_none();
+            // peers are counted by the host, so their number is polled
+            let lonely = self.chain.status && !self.chain.queue.is_empty() && !self.enough_peers();
+            let stall = self
+                .config
+                .mining_timeout
+                .zip(self.job.started())
+                .map(|(timeout, started)| started + timeout);
             tokio::select! {
+            _ = tokio::time::sleep(delay), if throttled => {},
+            _ = tokio::time::sleep(PEERS_POLL), if lonely => {},
+            _ = tokio::time::sleep_until(deadline), if self.sync_deadline.is_some() => {
+                warn!("No chain from other nodes, initial sync is timed out");
+                self.sync_deadline = None;
+            },
+            _ = tokio::time::sleep_until(retry), if retrying => {
+                debug!("No chain from other nodes yet, repeating the request");
+                if let Err(e) = self.send(Message::ChainRequest).await {
+                    error!("Sending chain request error: {:?}", e);
+                }
+                retry_at = backoff.as_mut().map(|b| Instant::now() + b.next_delay());
+            },
+            _ = tokio::time::sleep_until(empty_block.unwrap_or(idle_since)), if idle => {
+                debug!("Queue is empty for a while, mining an empty block");
+                let mut block = Block::new(0, String::new());
+                block.salt = self.config.network_id.clone();
+                self.chain.add_queue(block);
+            },
+            _ = tokio::time::sleep_until(stall.unwrap_or_else(Instant::now)), if stall.is_some() => {
+                self.stalled(&tx_node).await;
+            },
+            _ = tokio::time::sleep_until(prune_at.unwrap_or_else(Instant::now)), if prune_at.is_some() => {
+                self.prune_old_bodies();
+                prune_at = Some(Instant::now() + PRUNE_INTERVAL);
+            },
+            remined = async { self.remine.as_mut().unwrap().await }, if self.remine.is_some() => {
+                self.remine = None;
+                match remined {
+                    Ok((old, tip)) => self.remined(old, tip).await,
+                    Err(e) => error!("Re-mining the tip failed: {e}"),
+                }
+            },
+            _ = tokio::time::sleep_until(deferred), if !self.deferred.is_empty() => {
+                self.queue_deferred(Instant::now()).await;
+            },
+            _ = tokio::time::sleep_until(heartbeat), if heartbeat_at.is_some() => {
+                self.heartbeat();
+                heartbeat_at = self.config.heartbeat.map(|period| Instant::now() + period);
+            },
             _ = self.rx_cancel.recv() => {
-                // graceful shutdown
-                cancel_tx.send(()).unwrap();
-                return;
+                // graceful shutdown, the worker may be stopped already
+                let _ = cancel_tx.send(());
+                return self.drain_pending();
             },
             msg = self.rx.recv() => {
-                if let Some(message) = msg {
-                    match message {
-                        Message::NewBlock(block) => {
-                            self.chain.add_queue(block);
-                        },
-                        Message::ChainRequest => {
-                            if let Err(e) = self.tx.send(Message::ChainResponce(self.chain.clone())).await {
-                                error!("Sending chain error: {:?}",e);
-                            } else {
-                                info!("Serve chain request");
-                            }
-
-                        },
-                        Message::ChainResponce(chain) => {
-                            if chain.have_errors().is_none(){
-                                if chain.blocks.len() > self.chain.blocks.len() {
-                                    self.chain = chain;
-                                    warn!("Taking chain from another node!");
-                                }
-                            } else {
-                                error!("Chain from another node has errors!")
-                            }
-                        }
-                        Message::MinedBlock(block) => {
-
-                            if block.hash != block.calc_hash() && block.string_hash().ends_with(&self.difficult){
-                                warn!("Reciever block with wrong hash field: {}",block);
-                                continue;
-                            }
-
-                            let last = self.chain.blocks.last_mut();
-                            match last {
-                                Some(last) => {
-
-                                    if block.id == last.id && self.chain.status && block.preequals(last) && block.hash < last.hash {
-                                            last.hash = block.hash;
-                                            last.nonce = block.nonce;
-                                            info!("Replaced host block with remote block");
-                                    }
-
-                                    if block.id == last.id && !self.chain.status && block.preequals(last) {
-                                        last.hash = block.hash;
-                                        last.nonce = block.nonce;
-                                        self.chain.status = true;
-                                        info!("Took remote block");
-                                    }
-
-                                    if block.id > last.id {
-                                        if let Err(e) = self.tx.send(Message::ChainRequest).await {
-                                            error!("Sending chain request error: {:?}",e);
-                                        }
-                                    }
-
-                                    if let Some(id) = self.chain.have_errors() {
-                                        warn!("Host chain have erros! Requesting remote");
-                                            for _ in id..self.chain.blocks.len(){
-                                                let block = self.chain.blocks.pop().unwrap();
-                                                self.chain.queue.push_front(block);
-                                            }
-                                            if let Err(e) = self.tx.send(Message::ChainRequest).await{
-                                                error!("Chain request sending error: {:?}",e);
-                                            }
-                                    }
-                                },
-                                None => {
-                                    info!("Host chain in empty, requesting remote");
-                                    if let Err(e) = self.tx.send(Message::ChainRequest).await{
-                                        error!("Chain request sending error: {:?}",e);
-                                    }
-                                }
-                            }
-                        },
-                    }
-                } else {
-                    error!("Error around net and node connection");
+                match msg {
+                    Some(message) => self.handle(message).await,
+                    None => error!("Error around net and node connection"),
                 }
             },
             nonce = rx_node.recv() => {
-                if let Some(nonce) = nonce {
-                    if !self.chain.status {
-                        let mut cloned_block = self.chain.blocks.last().unwrap().clone();
-                        cloned_block.hash = nonce.0;
-                        cloned_block.nonce = nonce.1;
-                        if cloned_block.hash == cloned_block.calc_hash(){
-                            let mut last = self.chain.blocks.last_mut().unwrap();
-                            last.hash = cloned_block.hash;
-                            last.nonce = cloned_block.nonce;
-                            self.chain.status = true;
-
-                            if let Err(e) = self.tx.send(Message::MinedBlock(last.clone())).await{
-                                error!("Sending error: {:?}",e)
-                            }
-                            info!("Mined!");
-
-                        }
+                match nonce {
+                    Some(nonce) => self.mined(nonce).await,
+                    None => {
+                        error!("Mining worker is stopped, restarting it");
+                        (tx_node, rx_node) = self.spawn_worker(cancel_tx.subscribe());
+                        self.redispatch(&tx_node).await;
                     }
                 }
+                if self.config.mine_blocks.is_some_and(|n| self.mined_total >= n) {
+                    info!("{} blocks are mined, stopping", self.mined_total);
+                    let _ = cancel_tx.send(());
+                    return self.drain_pending();
+                }
             }
             }
 
-            if self.chain.status {
+            if self.chain.status
+                && self.interval_delay().is_zero()
+                && self.sync_deadline.is_none()
+                && self.enough_peers()
+                && self.can_build_on_tip()
+            {
                 self.chain.status = !self.chain.try_add();
 
                 if !self.chain.status {
+                    self.last_added = Some(Instant::now());
                     let last_block = self.chain.blocks.last().unwrap(); // we know!
-                    let diff = self.difficult.clone();
-                    if let Err(e) = tx_node.send((last_block.clone(), diff)).await {
+                    let diff = self.difficulty().to_owned();
+                    if !self.job.start(last_block.id) {
+                        error!(
+                            "Mining job #{:?} is in flight, not dispatching #{}",
+                            self.job.in_flight(),
+                            last_block.id
+                        );
+                    } else if let Err(e) = tx_node.send((last_block.clone(), diff)).await {
                         warn!("Can't send data to worker: {e}");
                     }
                 }
             }
+            self.prune_over_limit();
+            // fail fast on corruption in debug builds
+            self.chain.assert_invariants();
+        }
+    }
+
+    /// Handles the messages one by one like `run`, but without the network, the worker and timers.
+    ///
+    /// Every block appended to the chain is mined right away by trying nonces in order, so the
+    /// replay of the same messages always ends with the same chain and an empty queue. If no nonce
+    /// meets the difficulty in many times the expected attempts (see `expected_attempts`), an
+    /// error is logged and the block is left being mined. Messages sent by the node still go to
+    /// its sender, which must have room for them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::{Block, Chain, Message, Node};
+    /// use tokio::sync::{broadcast, mpsc};
+    ///
+    /// # futures::executor::block_on(async {
+    /// let (tx, _rx_test) = mpsc::channel(16);
+    /// let (_tx_test, rx) = mpsc::channel(1);
+    /// let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+    /// let mut node = Node::new(Chain::with_genesis("doc"), tx, rx, rx_cancel, String::from("0"));
+    ///
+    /// let block = Block::new(0, String::from("Some data"));
+    /// node.replay(futures::stream::iter([Message::SubmitBlock(block)])).await;
+    ///
+    /// assert_eq!(node.chain().blocks.len(), 2);
+    /// assert!(node.chain().queue.is_empty());
+    /// # });
+    /// ```
+    pub async fn replay(&mut self, messages: impl Stream<Item = Message>) {
+        futures::pin_mut!(messages);
+        while let Some(message) = messages.next().await {
+            self.handle(message).await;
+            // timers aren't replayed, deferred blocks are queued right away
+            self.queue_deferred(Instant::now() + DEFER_OWN_DELAY).await;
+            if let Some(remine) = self.remine.take() {
+                match remine.await {
+                    Ok((old, tip)) => self.remined(old, tip).await,
+                    Err(e) => error!("Re-mining the tip failed: {e}"),
+                }
+            }
+            loop {
+                self.prune_over_limit();
+                if self.chain.status {
+                    if !self.can_build_on_tip() {
+                        break;
+                    }
+                    self.chain.status = !self.chain.try_add();
+                    if self.chain.status {
+                        break;
+                    }
+                }
+                let block = self.chain.blocks.last().unwrap().clone();
+                let attempts =
+                    (expected_attempts(self.difficulty()) * REPLAY_ATTEMPTS_FACTOR) as u64;
+                let nonce = match (0..attempts).find(|&nonce| {
+                    self.chain
+                        .difficulty_mode
+                        .matches(&block.hash_with_nonce(nonce), self.difficulty())
+                }) {
+                    Some(nonce) => nonce,
+                    None => {
+                        error!(
+                            "No nonce of block #{} meets the difficulty \"{}\" in {} attempts",
+                            block.id,
+                            self.difficulty(),
+                            attempts
+                        );
+                        break;
+                    }
+                };
+                // nonces are tried from 0
+                self.mined((block.hash_with_nonce(nonce), nonce, nonce + 1))
+                    .await;
+            }
+            self.check_confirmations();
+        }
+        self.update_health();
+    }
+
+    /// Handles the messages of a log recorded with `NodeConfig::record` (see `MessageLog::read`)
+    /// like `run`, so a node started with the same chain and settings goes through the same
+    /// states as the recorded one.
+    ///
+    /// Received messages are handled in order. Instead of mining, the last block takes the nonce
+    /// of the next `Message::MinedBlock` sent by the recorded node, so the blocks get the same
+    /// hashes. Timers of `run` (e.g. `NodeConfig::min_block_interval` or
+    /// `NodeConfig::empty_block_interval`) aren't replayed. Messages sent by the node still go to
+    /// its sender, which must have room for them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::{Block, Chain, DifficultyMode, Direction, LogEntry, Message, Node};
+    /// use tokio::sync::{broadcast, mpsc};
+    ///
+    /// # futures::executor::block_on(async {
+    /// let (tx, _rx_test) = mpsc::channel(16);
+    /// let (_tx_test, rx) = mpsc::channel(1);
+    /// let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+    /// let mut node = Node::new(Chain::with_genesis("doc"), tx, rx, rx_cancel, String::from("0"));
+    ///
+    /// let mut block = Block::new(1, String::from("Some data"));
+    /// block.prev = Chain::with_genesis("doc").blocks[0].hash;
+    /// block.nonce = (0..)
+    ///     .find(|&nonce| DifficultyMode::Suffix.matches(&block.hash_with_nonce(nonce), "0"))
+    ///     .unwrap();
+    /// block.hash = block.calc_hash();
+    ///
+    /// let log = [
+    ///     (Direction::In, Message::SubmitBlock(block.clone())),
+    ///     (Direction::Out, Message::MinedBlock(block.clone())),
+    /// ];
+    /// node.replay_log(log.map(|(direction, message)| LogEntry {
+    ///     time: 0,
+    ///     direction,
+    ///     message,
+    /// }))
+    /// .await;
+    ///
+    /// assert_eq!(node.chain().blocks[1].hash, block.hash);
+    /// # });
+    /// ```
+    pub async fn replay_log(&mut self, entries: impl IntoIterator<Item = LogEntry>) {
+        for entry in entries {
+            match (entry.direction, entry.message) {
+                (Direction::In, message) => self.handle(message).await,
+                (Direction::Out, Message::MinedBlock(block)) => {
+                    let tip = self.chain.blocks.last().map(|tip| tip.id);
+                    if self.chain.status || tip != Some(block.id) {
+                        continue;
+                    }
+                    let attempts = block.mining_attempts.unwrap_or(0);
+                    self.mined((block.hash, block.nonce, attempts)).await;
+                }
+                _ => continue,
+            }
+            self.prune_over_limit();
+            if self.chain.status && self.can_build_on_tip() {
+                self.chain.status = !self.chain.try_add();
+                if !self.chain.status {
+                    self.job.start(self.chain.blocks.last().unwrap().id);
+                }
+            }
+            self.check_confirmations();
+        }
+        self.update_health();
+    }
+
+    /// Removes blocks which weren't mined yet from the chain and returns them in order.
+    ///
+    /// The block which is mined now is returned first, followed by the queue and blocks deferred
+    /// with `NodeConfig::defer_own`.
+    pub fn drain_pending(&mut self) -> VecDeque<Block> {
+        if !self.chain.status {
+            self.chain.rollback(self.chain.blocks.len() - 1);
+            self.chain.status = true;
+            self.job.finish();
         }
+        let mut pending = self.chain.take_queue();
+        pending.extend(self.deferred.drain(..).map(|(_, _, block)| block));
+        pending
+    }
+}
+
+/// Returns the hashes of the blocks.
+fn hashes(blocks: &[Block]) -> Vec<[u8; 32]> {
+    blocks.iter().map(|block| block.hash).collect()
+}
+
+/// Handles the block which got its final hash in the chain of the node.
+fn appended(config: &NodeConfig, block: &Block) {
+    if let Some(dir) = &config.archive_dir {
+        if let Err(e) = archive_block(dir, block) {
+            error!("Can't archive block #{}: {e}", block.id);
+        }
+    }
+    if let Some(hook) = &config.on_block {
+        hook.call(block);
+    }
+}
+
+/// Controls the sync of a node with the chain from another node.
+///
+/// Checking a long chain takes a while, so it can be cancelled without stopping the whole node.
+/// The local chain stays unchanged then.
+#[derive(Debug, Default)]
+pub struct SyncControl {
+    /// Whether a chain is being checked now.
+    active: AtomicBool,
+    /// Whether the current sync is cancelled.
+    cancelled: AtomicBool,
+}
+
+impl SyncControl {
+    /// Cancels the current sync.
+    ///
+    /// Returns false if there is no sync to cancel.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::SyncControl;
+    ///
+    /// let sync = SyncControl::default();
+    /// assert!(!sync.is_active());
+    /// assert!(!sync.cancel());
+    /// ```
+    pub fn cancel(&self) -> bool {
+        if !self.is_active() {
+            return false;
+        }
+        self.cancelled.store(true, Ordering::SeqCst);
+        true
+    }
+
+    /// Returns true if a chain is being checked now.
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// Starts a new sync, forgetting about cancels of the previous one.
+    fn begin(&self) {
+        self.cancelled.store(false, Ordering::SeqCst);
+        self.active.store(true, Ordering::SeqCst);
+    }
+
+    /// Finishes the current sync.
+    fn end(&self) {
+        self.active.store(false, Ordering::SeqCst);
+    }
+
+    /// Returns true if the current sync is cancelled.
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// Tracks the mining job sent to `nonce_worker`.
+///
+/// The worker mines one block at a time and silently replaces its job with a new one, so the node
+/// must not dispatch a new job until the current one is finished.
+#[derive(Debug, Default)]
+pub struct MiningJob {
+    /// The id of the block which is mined now.
+    in_flight: Option<u64>,
+    /// When the current job was started.
+    started: Option<Instant>,
+}
+
+impl MiningJob {
+    /// Starts the job for the block with the given id.
+    ///
+    /// Returns false if another job is already in flight: the dispatch must be dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::MiningJob;
+    ///
+    /// let mut job = MiningJob::default();
+    /// assert!(job.start(1));
+    /// assert!(!job.start(2));
+    /// assert_eq!(job.finish(), Some(1));
+    /// assert!(job.start(2));
+    /// ```
+    pub fn start(&mut self, id: u64) -> bool {
+        if self.in_flight.is_some() {
+            return false;
+        }
+        self.in_flight = Some(id);
+        self.started = Some(Instant::now());
+        true
+    }
+
+    /// Finishes the current job and returns its block id.
+    pub fn finish(&mut self) -> Option<u64> {
+        self.started = None;
+        self.in_flight.take()
+    }
+
+    /// Returns when the current job was started.
+    pub fn started(&self) -> Option<Instant> {
+        self.started
+    }
+
+    /// Returns the id of the block which is mined now.
+    pub fn in_flight(&self) -> Option<u64> {
+        self.in_flight
     }
 }
 
@@ -206,14 +1471,17 @@ impl Node {
 ///
 /// The function takes a receiving end of a channel, `rx`, which is used to receive a tuple of
 /// the block and the difficulty string. It also takes a sending end of a channel, `tx`, which is
-/// used to send back the resulting hash and nonce. Lastly, it takes a receiving end of a broadcast
-/// channel, `cancel_rx`, which is used to gracefully shutdown the function.
+/// used to send back the resulting hash and nonce with the number of nonces tried. Lastly, it takes a receiving end of a broadcast
+/// channel, `cancel_rx`, which is used to gracefully shutdown the function. The `mode` tells which
+/// end of the hash must match the difficulty.
 pub async fn nonce_worker(
     mut rx: Receiver<(Block, String)>,
-    tx: Sender<([u8; 32], u64)>,
+    tx: Sender<([u8; 32], u64, u64)>,
     mut cancel_rx: broadcast::Receiver<()>,
+    mode: DifficultyMode,
 ) {
     let mut flag = false;
+    let mut attempts = 0;
     let mut block = Block::new(0, String::from("Dummy"));
     let mut diff = String::from("zzzz");
     loop {
@@ -227,16 +1495,17 @@ pub async fn nonce_worker(
                     block = b;
                         diff = s;
                         flag = true;
+                        attempts = 0;
                 }
 
             },
             _ = tokio::time::sleep(tokio::time::Duration::from_nanos(1)) => {
                 if flag{
                     let nonce = rand::random::<u64>();
-                    block.nonce = nonce;
-                    block.update_hash();
-                    if block.string_hash().ends_with(&diff){
-                        if let Err(e) = tx.send((block.hash, nonce)).await {
+                    let hash = block.hash_with_nonce(nonce);
+                    attempts += 1;
+                    if mode.matches(&hash, &diff){
+                        if let Err(e) = tx.send((hash, nonce, attempts)).await {
                             error!("Error around worker {:?}",e);
                         }
                         flag = false;
@@ -246,3 +1515,112 @@ pub async fn nonce_worker(
         }
     }
 }
+
+/// The workers of `nonce_workers` mining one block, cancelled when the round is dropped.
+struct Round {
+    /// Cancels the workers.
+    stop: broadcast::Sender<()>,
+    /// The job channels of the workers, kept open while the round lasts.
+    _jobs: Vec<Sender<(Block, String)>>,
+    /// The results of the workers.
+    results: Receiver<Solution>,
+}
+
+impl Drop for Round {
+    fn drop(&mut self) {
+        // the workers may be stopped already
+        let _ = self.stop.send(());
+    }
+}
+
+/// Mines blocks like `nonce_worker`, but with `count` of them trying random nonces of the same
+/// block independently.
+///
+/// Every block starts a round: the workers get their own cancel channel, so the first result
+/// is sent and the other workers of the round are cancelled. A new block cancels the previous
+/// round as well. The number of attempts is the one of the winning worker.
+pub async fn nonce_workers(
+    mut rx: Receiver<(Block, String)>,
+    tx: Sender<Solution>,
+    mut cancel_rx: broadcast::Receiver<()>,
+    count: usize,
+    mode: DifficultyMode,
+) {
+    let mut round: Option<Round> = None;
+    loop {
+        tokio::select! {
+            _ = cancel_rx.recv() => break,
+            m = rx.recv() => {
+                // the previous block is replaced, so its workers are cancelled
+                drop(round.take());
+                let (block, diff) = match m {
+                    Some(m) => m,
+                    None => break,
+                };
+                let (stop, _) = broadcast::channel(1);
+                let (round_tx, round_rx) = mpsc::channel(count.max(1));
+                let mut jobs = Vec::new();
+                for _ in 0..count.max(1) {
+                    let (job_tx, job_rx) = mpsc::channel(1);
+                    // the channel is new, so there is room for the job
+                    let _ = job_tx.try_send((block.clone(), diff.clone()));
+                    task::spawn(nonce_worker(job_rx, round_tx.clone(), stop.subscribe(), mode));
+                    jobs.push(job_tx);
+                }
+                round = Some(Round {
+                    stop,
+                    _jobs: jobs,
+                    results: round_rx,
+                });
+            },
+            res = async { round.as_mut().unwrap().results.recv().await }, if round.is_some() => {
+                round = None;
+                if let Some(result) = res {
+                    if let Err(e) = tx.send(result).await {
+                        error!("Error around worker {:?}", e);
+                    }
+                }
+            },
+        }
+    }
+}
+
+/// Picks a difficulty so that mining a block on this machine takes roughly `target` time.
+///
+/// Briefly runs `nonce_worker` on a dummy block with the easiest difficulty to measure the local
+/// hashrate, then returns the number of zero bytes (between 1 and 4) in the hash suffix
+/// whose expected attempts (256 per byte) are closest to the ones made in `target` time.
+/// Like `parse_difficulty`, the difficulty is whole bytes.
+pub async fn calibrate_difficulty(target: Duration) -> String {
+    let (tx, rx) = mpsc::channel::<(Block, String)>(1);
+    let (result_tx, mut result_rx) = mpsc::channel::<([u8; 32], u64, u64)>(1);
+    let (cancel_tx, cancel_rx) = broadcast::channel(1);
+
+    let task = task::spawn(nonce_worker(
+        rx,
+        result_tx,
+        cancel_rx,
+        DifficultyMode::Suffix,
+    ));
+
+    let start = Instant::now();
+    let mut found = 0u32;
+    while found < 16 && start.elapsed() < Duration::from_secs(2) {
+        let block = Block::new(0, format!("Calibration {found}"));
+        if tx.send((block, String::from("0"))).await.is_err() || result_rx.recv().await.is_none() {
+            break;
+        }
+        found += 1;
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    cancel_tx.send(()).ok();
+    task.await.ok();
+
+    // every solution with one hex zero takes 16 attempts on average
+    let hashrate = f64::from(found.max(1)) * 16.0 / elapsed.max(f64::EPSILON);
+    let attempts = (hashrate * target.as_secs_f64()).max(1.0);
+    let bytes = attempts.log(256.0).round().clamp(1.0, 4.0) as usize;
+    info!("Calibrated difficulty: {bytes} zero bytes at {hashrate:.0} H/s");
+    "00".repeat(bytes)
+}
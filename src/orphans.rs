@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+
+use crate::{Block, Chain};
+
+/// Mined blocks ahead of the tip of a chain which can't be connected to it yet, kept until the
+/// blocks in between arrive, so they don't have to be downloaded again (see
+/// `NodeConfig::orphan_window`).
+///
+/// # Examples
+///
+/// ```
+/// use rustychain::{Chain, OrphanPool};
+///
+/// let full = Chain::test_chain(4);
+/// let mut chain = Chain::test_chain(2);
+/// let mut orphans = OrphanPool::new(4);
+///
+/// assert!(orphans.insert(full.blocks[3].clone(), &chain));
+/// assert!(orphans.connect(&mut chain).is_empty());
+///
+/// chain.append_block(full.blocks[2].clone()).unwrap();
+/// assert_eq!(orphans.connect(&mut chain).len(), 1);
+/// assert_eq!(chain.blocks.len(), 4);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct OrphanPool {
+    /// How many ids after the tip are kept: a block is accepted if its id is in
+    /// `chain.blocks.len()..chain.blocks.len() + window`.
+    window: u64,
+    /// The blocks by id.
+    blocks: BTreeMap<u64, Block>,
+    /// The maximum number of blocks in the pool, see `with_max`.
+    max: Option<usize>,
+}
+
+impl OrphanPool {
+    /// Creates an empty pool keeping blocks up to `window` ids ahead of the tip.
+    pub fn new(window: u64) -> Self {
+        Self {
+            window,
+            blocks: BTreeMap::new(),
+            max: None,
+        }
+    }
+
+    /// Keeps at most `max` blocks in the pool (see `MemoryLimits::max_orphans`). A block closer
+    /// to the tip evicts the farthest one from a full pool.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustychain::{Chain, OrphanPool};
+    ///
+    /// let full = Chain::test_chain(6);
+    /// let chain = Chain::test_chain(2);
+    /// let mut orphans = OrphanPool::new(4).with_max(2);
+    ///
+    /// assert!(orphans.insert(full.blocks[4].clone(), &chain));
+    /// assert!(orphans.insert(full.blocks[5].clone(), &chain));
+    /// assert!(orphans.insert(full.blocks[3].clone(), &chain));
+    /// assert_eq!(orphans.ids(), [3, 4]);
+    /// ```
+    pub fn with_max(mut self, max: usize) -> Self {
+        self.max = Some(max);
+        self
+    }
+
+    /// Returns the number of blocks in the pool.
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Returns true if the pool has no blocks.
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Returns the ids of blocks in the pool in increasing order.
+    pub fn ids(&self) -> Vec<u64> {
+        self.blocks.keys().copied().collect()
+    }
+
+    /// Keeps the block if it's within the window ahead of the chain and has a valid hash.
+    ///
+    /// The first block seen with an id is kept. A full pool (see `with_max`) makes room only for
+    /// a block closer to the tip than the farthest one. Returns true if the block is added to the
+    /// pool.
+    pub fn insert(&mut self, block: Block, chain: &Chain) -> bool {
+        let next = chain.blocks.len() as u64;
+        if block.id < next || block.id - next >= self.window || !block.validate_hash() {
+            return false;
+        }
+        if self.blocks.contains_key(&block.id) {
+            return false;
+        }
+        if self.max.is_some_and(|max| self.blocks.len() >= max) {
+            // the farthest block is the last to connect
+            match self.blocks.last_key_value() {
+                Some((&last, _)) if last > block.id => self.blocks.remove(&last),
+                _ => return false,
+            };
+        }
+        self.blocks.insert(block.id, block);
+        true
+    }
+
+    /// Appends the blocks which follow the tip of the chain one by one with `Chain::append_block`
+    /// and returns them.
+    ///
+    /// Blocks behind the tip are dropped, as is a block which doesn't attach to the chain, so
+    /// the blocks after it wait for a replacement. Nothing is appended while the chain is busy.
+    /// Queued blocks are renumbered to follow the appended blocks (see `Chain::append_block`).
+    pub fn connect(&mut self, chain: &mut Chain) -> Vec<Block> {
+        if !chain.status {
+            return Vec::new();
+        }
+        let next = chain.blocks.len() as u64;
+        self.blocks = self.blocks.split_off(&next);
+
+        let mut appended = Vec::new();
+        while let Some(block) = self.blocks.remove(&(chain.blocks.len() as u64)) {
+            if chain.append_block(block.clone()).is_err() {
+                break;
+            }
+            appended.push(block);
+        }
+        appended
+    }
+}
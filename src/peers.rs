@@ -0,0 +1,88 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use libp2p::Multiaddr;
+use log::warn;
+
+/// Addresses of peers saved to a file, so a restarted node can dial them right away instead of
+/// waiting for discovery.
+///
+/// The file has one address per line.
+///
+/// # Examples
+///
+/// ```
+/// use rustychain::PeerBook;
+///
+/// let path = std::env::temp_dir().join("rustychain-doc-peers.txt");
+/// let mut book = PeerBook::load(&path).unwrap();
+/// book.add("/ip4/10.0.0.1/tcp/4001".parse().unwrap());
+/// book.save().unwrap();
+///
+/// let book = PeerBook::load(&path).unwrap();
+/// assert_eq!(book.addrs()[0].to_string(), "/ip4/10.0.0.1/tcp/4001");
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct PeerBook {
+    /// The file the addresses are saved to.
+    path: PathBuf,
+    /// The known addresses of peers.
+    addrs: Vec<Multiaddr>,
+}
+
+impl PeerBook {
+    /// Reads the addresses from the file. A missing file gives an empty book; lines which aren't
+    /// addresses are skipped.
+    pub fn load(path: &Path) -> io::Result<PeerBook> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let mut book = PeerBook {
+            path: path.to_owned(),
+            addrs: Vec::new(),
+        };
+        for line in content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+        {
+            match line.parse() {
+                Ok(addr) => {
+                    book.add(addr);
+                }
+                Err(e) => warn!("Skipping peer address \"{line}\": {e}"),
+            }
+        }
+        Ok(book)
+    }
+
+    /// Adds the address of a peer. Returns false if it's already known.
+    pub fn add(&mut self, addr: Multiaddr) -> bool {
+        if self.addrs.contains(&addr) {
+            return false;
+        }
+        self.addrs.push(addr);
+        true
+    }
+
+    /// Returns the known addresses of peers.
+    pub fn addrs(&self) -> &[Multiaddr] {
+        &self.addrs
+    }
+
+    /// Writes the addresses to the file.
+    ///
+    /// The addresses are written to a temporary file first and then renamed, so the file is never
+    /// left partially written.
+    pub fn save(&self) -> io::Result<()> {
+        let content: String = self.addrs.iter().map(|addr| format!("{addr}\n")).collect();
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, content)?;
+        fs::rename(&tmp, &self.path)
+    }
+}
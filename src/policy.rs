@@ -0,0 +1,55 @@
+use std::collections::BTreeMap;
+
+/// The checks run on a chain by `Chain::verify_with_policy`, see `NodeConfig::validation`.
+///
+/// The default policy checks the linkage and the proof of work of blocks, like
+/// `Chain::have_errors`.
+///
+/// # Examples
+///
+/// ```
+/// use rustychain::{Block, ValidationPolicy};
+///
+/// let mut policy = ValidationPolicy {
+///     genesis: Some(Block::genesis("hash-net").hash),
+///     max_time_drift: Some(2 * 60 * 60),
+///     ..Default::default()
+/// };
+/// policy.checkpoints.insert(0, Block::genesis("hash-net").hash);
+/// assert!(policy.linkage && policy.proof_of_work);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationPolicy {
+    /// Whether block ids go up from 0 one by one and every block points to the previous one.
+    pub linkage: bool,
+    /// Whether the hash of every block matches its content. Pruned blocks fail it, since their
+    /// content is gone.
+    pub proof_of_work: bool,
+    /// Whether every block but the genesis one meets the difficulty recorded in the chain (see
+    /// `Chain::meets_difficulty`).
+    pub recorded_difficulty: bool,
+    /// The hash the genesis block must have.
+    pub genesis: Option<[u8; 32]>,
+    /// How many seconds timestamps of blocks may be ahead of the local clock. Blocks without a
+    /// timestamp aren't checked.
+    pub max_time_drift: Option<u64>,
+    /// The minimum number of zero bits at the mined end of hashes of blocks but the genesis one
+    /// (see `Block::work_bits`).
+    pub min_difficulty_bits: Option<u32>,
+    /// The hashes blocks with these ids must have. Chains too short to reach a checkpoint pass it.
+    pub checkpoints: BTreeMap<u64, [u8; 32]>,
+}
+
+impl Default for ValidationPolicy {
+    fn default() -> Self {
+        Self {
+            linkage: true,
+            proof_of_work: true,
+            recorded_difficulty: false,
+            genesis: None,
+            max_time_drift: None,
+            min_difficulty_bits: None,
+            checkpoints: BTreeMap::new(),
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Message;
+
+/// Whether a recorded message was received or sent by the node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    /// The message was received from the network or the host.
+    In,
+    /// The message was sent by the node.
+    Out,
+}
+
+/// A message received or sent by a node, see `MessageLog`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// When the message was recorded, in milliseconds since the Unix epoch.
+    pub time: u64,
+    /// Whether the message was received or sent.
+    pub direction: Direction,
+    /// The message.
+    pub message: Message,
+}
+
+/// The file every message received and sent by a node is recorded to (see
+/// `NodeConfig::record`), so the session can be replayed with `Node::replay_log`.
+///
+/// The file has one `LogEntry` as JSON per line.
+///
+/// # Examples
+///
+/// ```
+/// use rustychain::{Direction, Message, MessageLog};
+///
+/// let path = std::env::temp_dir().join("rustychain-doc-log.jsonl");
+/// let mut log = MessageLog::create(&path).unwrap();
+/// log.record(Direction::In, &Message::ChainRequest).unwrap();
+/// log.record(Direction::Out, &Message::BlockRequest(1)).unwrap();
+///
+/// let entries = MessageLog::read(&path).unwrap();
+/// assert_eq!(entries.len(), 2);
+/// assert_eq!(entries[0].direction, Direction::In);
+/// assert!(matches!(entries[1].message, Message::BlockRequest(1)));
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct MessageLog {
+    /// The file entries are appended to.
+    file: File,
+}
+
+impl MessageLog {
+    /// Creates the file, truncating a previous log.
+    pub fn create(path: &Path) -> io::Result<MessageLog> {
+        Ok(MessageLog {
+            file: File::create(path)?,
+        })
+    }
+
+    /// Appends the message with the current time to the file.
+    ///
+    /// Every entry is written right away, so the log is complete up to the last message even if
+    /// the node crashes.
+    pub fn record(&mut self, direction: Direction, message: &Message) -> io::Result<()> {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64);
+        let entry = LogEntry {
+            time,
+            direction,
+            message: message.clone(),
+        };
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        self.file.write_all(&line)
+    }
+
+    /// Reads the entries of the log in order.
+    ///
+    /// Unlike `PeerBook::load`, an entry which can't be read is an error: a replay without it
+    /// wouldn't reproduce the session.
+    pub fn read(path: &Path) -> io::Result<Vec<LogEntry>> {
+        fs::read_to_string(path)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(io::Error::from))
+            .collect()
+    }
+}
@@ -0,0 +1,161 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Message;
+
+/// Counters of messages received and sent by a node, one pair per `Message` variant.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageStats {
+    /// Received `Message::NewBlock`.
+    pub new_block_in: u64,
+    /// Sent `Message::NewBlock`.
+    pub new_block_out: u64,
+    /// Received `Message::MinedBlock`.
+    pub mined_block_in: u64,
+    /// Sent `Message::MinedBlock`.
+    pub mined_block_out: u64,
+    /// Received `Message::ChainRequest`.
+    pub chain_request_in: u64,
+    /// Sent `Message::ChainRequest`.
+    pub chain_request_out: u64,
+    /// Received `Message::ChainResponce`.
+    pub chain_response_in: u64,
+    /// Sent `Message::ChainResponce`.
+    pub chain_response_out: u64,
+    /// Received `Message::InjectBlock`.
+    pub inject_block_in: u64,
+    /// Sent `Message::InjectBlock`.
+    pub inject_block_out: u64,
+    /// Received `Message::SubmitBlock`.
+    pub submit_block_in: u64,
+    /// Sent `Message::SubmitBlock`.
+    pub submit_block_out: u64,
+    /// Received `Message::DebugRequest`.
+    pub debug_request_in: u64,
+    /// Sent `Message::DebugRequest`.
+    pub debug_request_out: u64,
+    /// Received `Message::DebugResponse`.
+    pub debug_response_in: u64,
+    /// Sent `Message::DebugResponse`.
+    pub debug_response_out: u64,
+    /// Received `Message::Reject`.
+    pub reject_in: u64,
+    /// Sent `Message::Reject`.
+    pub reject_out: u64,
+    /// Received `Message::Ack`.
+    pub ack_in: u64,
+    /// Sent `Message::Ack`.
+    pub ack_out: u64,
+    /// Received `Message::RemineTip`.
+    pub remine_tip_in: u64,
+    /// Sent `Message::RemineTip`.
+    pub remine_tip_out: u64,
+    /// Received `Message::BlockRequest`.
+    pub block_request_in: u64,
+    /// Sent `Message::BlockRequest`.
+    pub block_request_out: u64,
+    /// Received `Message::BlockResponse`.
+    pub block_response_in: u64,
+    /// Sent `Message::BlockResponse`.
+    pub block_response_out: u64,
+}
+
+impl MessageStats {
+    /// Counts the received message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustychain::{Message, MessageStats};
+    ///
+    /// let mut stats = MessageStats::default();
+    /// stats.count_in(&Message::ChainRequest);
+    /// assert_eq!(stats.chain_request_in, 1);
+    /// assert_eq!(stats.chain_request_out, 0);
+    /// ```
+    pub fn count_in(&mut self, msg: &Message) {
+        *self.counter(msg).0 += 1;
+    }
+
+    /// Counts the sent message.
+    pub fn count_out(&mut self, msg: &Message) {
+        *self.counter(msg).1 += 1;
+    }
+
+    /// Returns the counters of received and sent messages of the same variant.
+    ///
+    /// Messages in `Message::Envelope` are counted by the wrapped message.
+    fn counter(&mut self, msg: &Message) -> (&mut u64, &mut u64) {
+        match msg {
+            Message::NewBlock(_) => (&mut self.new_block_in, &mut self.new_block_out),
+            Message::MinedBlock(_) => (&mut self.mined_block_in, &mut self.mined_block_out),
+            Message::ChainRequest => (&mut self.chain_request_in, &mut self.chain_request_out),
+            Message::ChainResponce(_) => {
+                (&mut self.chain_response_in, &mut self.chain_response_out)
+            }
+            Message::InjectBlock(_) => (&mut self.inject_block_in, &mut self.inject_block_out),
+            Message::SubmitBlock(_) => (&mut self.submit_block_in, &mut self.submit_block_out),
+            Message::DebugRequest => (&mut self.debug_request_in, &mut self.debug_request_out),
+            Message::DebugResponse(_) => {
+                (&mut self.debug_response_in, &mut self.debug_response_out)
+            }
+            Message::Envelope(_, msg) => self.counter(msg),
+            Message::Reject { .. } => (&mut self.reject_in, &mut self.reject_out),
+            Message::Ack { .. } => (&mut self.ack_in, &mut self.ack_out),
+            Message::RemineTip(_) => (&mut self.remine_tip_in, &mut self.remine_tip_out),
+            Message::BlockRequest(_) => (&mut self.block_request_in, &mut self.block_request_out),
+            Message::BlockResponse(_) => {
+                (&mut self.block_response_in, &mut self.block_response_out)
+            }
+        }
+    }
+}
+
+impl fmt::Display for MessageStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows = [
+            ("NewBlock", self.new_block_in, self.new_block_out),
+            ("MinedBlock", self.mined_block_in, self.mined_block_out),
+            (
+                "ChainRequest",
+                self.chain_request_in,
+                self.chain_request_out,
+            ),
+            (
+                "ChainResponce",
+                self.chain_response_in,
+                self.chain_response_out,
+            ),
+            ("InjectBlock", self.inject_block_in, self.inject_block_out),
+            ("SubmitBlock", self.submit_block_in, self.submit_block_out),
+            (
+                "DebugRequest",
+                self.debug_request_in,
+                self.debug_request_out,
+            ),
+            (
+                "DebugResponse",
+                self.debug_response_in,
+                self.debug_response_out,
+            ),
+            ("Reject", self.reject_in, self.reject_out),
+            ("Ack", self.ack_in, self.ack_out),
+            ("RemineTip", self.remine_tip_in, self.remine_tip_out),
+            (
+                "BlockRequest",
+                self.block_request_in,
+                self.block_request_out,
+            ),
+            (
+                "BlockResponse",
+                self.block_response_in,
+                self.block_response_out,
+            ),
+        ];
+        for (name, received, sent) in rows {
+            write!(f, "{}: in {}, out {}\r\n", name, received, sent)?;
+        }
+        Ok(())
+    }
+}
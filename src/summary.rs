@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Block;
+
+/// A compact view of a block for API responses, see `Block::summary`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockSummary {
+    /// The ID of the block.
+    pub id: u64,
+    /// The hash of the block as a hex string.
+    pub hash_hex: String,
+    /// The hash of the previous block as a hex string.
+    pub prev_hex: String,
+    /// The nonce of the block.
+    pub nonce: u64,
+    /// The length of the data of the block in bytes.
+    pub data_len: usize,
+    /// The data of the block, truncated to `BlockSummary::DATA_PREVIEW` chars.
+    pub data_preview: String,
+}
+
+impl BlockSummary {
+    /// The maximum number of chars of block data kept in the summary.
+    pub const DATA_PREVIEW: usize = 64;
+}
+
+impl From<&Block> for BlockSummary {
+    fn from(block: &Block) -> Self {
+        Self {
+            id: block.id,
+            hash_hex: block.string_hash(),
+            prev_hex: block.string_prev(),
+            nonce: block.nonce,
+            data_len: block.data.len(),
+            data_preview: preview(&block.data, Self::DATA_PREVIEW),
+        }
+    }
+}
+
+/// Returns the first `max` chars of the data, followed by `...` if it's truncated.
+pub(crate) fn preview(data: &str, max: usize) -> String {
+    let mut preview: String = data.chars().take(max).collect();
+    if preview.len() < data.len() {
+        preview.push_str("...");
+    }
+    preview
+}
@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+/// The result of comparing the tip of a chain with the tip of a peer, see `Chain::tip_matches`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TipComparison {
+    /// Both tips are the same block.
+    Same,
+    /// The tips have the same height but different hashes: chains are forked.
+    DifferentHashSameHeight,
+    /// The local chain is shorter than the peer's one.
+    Behind,
+    /// The local chain is longer than the peer's one.
+    Ahead,
+}
@@ -0,0 +1,53 @@
+use core::fmt;
+
+use crate::Chain;
+
+/// Application rules for transactions, checked by a `Node` before a block is queued.
+///
+/// Blocks with rejected transactions are answered with `Message::Reject` and never mined.
+///
+/// # Examples
+///
+/// ```
+/// use rustychain::{Chain, TransactionValidator};
+///
+/// #[derive(Debug)]
+/// struct NotEmpty;
+///
+/// impl TransactionValidator for NotEmpty {
+///     fn validate(&self, tx: &str, _chain: &Chain) -> Result<(), String> {
+///         if tx.is_empty() {
+///             return Err(String::from("empty transaction"));
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let chain = Chain::new();
+/// assert!(NotEmpty.validate("Some data", &chain).is_ok());
+/// assert!(NotEmpty.validate("", &chain).is_err());
+/// ```
+pub trait TransactionValidator: fmt::Debug + Send + Sync {
+    /// Checks the transaction against the current chain of the node.
+    ///
+    /// Returns the reason of rejection if the transaction is invalid.
+    fn validate(&self, tx: &str, chain: &Chain) -> Result<(), String>;
+}
+
+/// The validator which accepts every transaction, used by default.
+///
+/// # Examples
+///
+/// ```
+/// use rustychain::{AcceptAll, Chain, TransactionValidator};
+///
+/// assert!(AcceptAll.validate("anything", &Chain::new()).is_ok());
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AcceptAll;
+
+impl TransactionValidator for AcceptAll {
+    fn validate(&self, _tx: &str, _chain: &Chain) -> Result<(), String> {
+        Ok(())
+    }
+}
@@ -0,0 +1,32 @@
+#[cfg(test)]
+mod backoff_tests {
+    use std::time::Duration;
+
+    use rustychain::Backoff;
+
+    #[test]
+    fn test_backoff() {
+        let base = Duration::from_millis(100);
+        let max = Duration::from_millis(3200);
+        let mut backoff = Backoff::new(base, max);
+        let delays: Vec<Duration> = (0..10).map(|_| backoff.next_delay()).collect();
+
+        // the delays grow, with up to a half cut off
+        for (attempt, delay) in delays.iter().enumerate() {
+            let full = (base * 2u32.pow(attempt as u32)).min(max);
+            assert!(*delay >= full / 2 && *delay <= full, "{attempt}: {delay:?}");
+        }
+        for pair in delays[..6].windows(2) {
+            assert!(pair[1] >= pair[0], "{:?}", pair);
+        }
+        assert!(delays[5] > delays[0] * 8);
+
+        // capped at the maximum, with jitter
+        let capped = &delays[6..];
+        assert!(capped.iter().all(|delay| *delay <= max));
+        assert!(capped.iter().any(|delay| *delay != capped[0]));
+
+        backoff.reset();
+        assert!(backoff.next_delay() <= base);
+    }
+}
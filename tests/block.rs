@@ -2,6 +2,8 @@
 mod block_tests {
 
     use rustychain::Block;
+    use rustychain::BlockSummary;
+    use rustychain::HashAlgo;
 
     #[test]
     fn test_new() {
@@ -21,6 +23,12 @@ mod block_tests {
             hash: [1u8; 32],
             prev: [0u8; 32],
             nonce: 1,
+            salt: String::new(),
+            timestamp: 0,
+            transactions: Vec::new(),
+            mining_attempts: None,
+            hash_algo: HashAlgo::Sha256,
+            pruned: false,
         };
         let block2 = block1.clone();
         assert_eq!(block1.calc_hash(), block2.calc_hash());
@@ -59,11 +67,11 @@ mod block_tests {
         assert!(!block1.equals(&block2));
 
         block2 = block1.clone();
-        block2.hash = block1.hash.map(|v| v + 1);
+        block2.hash = block1.hash.map(|v| v.wrapping_add(1));
         assert!(!block1.equals(&block2));
 
         block2 = block1.clone();
-        block2.prev = block1.prev.map(|v| v + 1);
+        block2.prev = block1.prev.map(|v| v.wrapping_add(1));
         assert!(!block1.equals(&block2));
 
         block2 = block1.clone();
@@ -84,15 +92,258 @@ mod block_tests {
         assert!(!block1.preequals(&block2));
 
         block2 = block1.clone();
-        block2.hash = block1.hash.map(|v| v + 1);
+        block2.hash = block1.hash.map(|v| v.wrapping_add(1));
         assert!(block1.preequals(&block2));
 
         block2 = block1.clone();
-        block2.prev = block1.prev.map(|v| v + 1);
+        block2.prev = block1.prev.map(|v| v.wrapping_add(1));
         assert!(!block1.preequals(&block2));
 
         block2 = block1.clone();
         block2.nonce = block1.nonce + 1;
         assert!(block1.preequals(&block2));
     }
+
+    #[test]
+    fn test_is_self_consistent() {
+        let mut block = Block::new(1337, String::from("Leet block!"));
+        assert!(!block.is_self_consistent()); // forgot update_hash
+        block.update_hash();
+        assert!(block.is_self_consistent());
+        assert_eq!(block.id(), 1337);
+        assert_eq!(block.data(), "Leet block!");
+        assert_eq!(block.hash(), &block.calc_hash());
+
+        block.prev = block.prev.map(|v| v.wrapping_add(1));
+        assert!(!block.is_self_consistent());
+        block.update_hash();
+        assert!(block.is_self_consistent());
+    }
+
+    #[test]
+    fn test_with_nonce() {
+        let block = Block::with_nonce(1337, String::from("Leet block!"), [1u8; 32], 7);
+        assert_eq!(block.id, 1337);
+        assert_eq!(block.data, String::from("Leet block!"));
+        assert_eq!(block.prev, [1u8; 32]);
+        assert_eq!(block.nonce, 7);
+        assert!(block.validate_hash());
+
+        let mut manual = Block::new_with_timestamp(1337, String::from("Leet block!"), 0);
+        manual.prev = [1u8; 32];
+        manual.nonce = 7;
+        manual.update_hash();
+        assert!(block.equals(&manual));
+    }
+
+    #[test]
+    fn test_hash_with_nonce() {
+        let mut block = Block::new(7, String::from("Some data"));
+        block.prev = [1u8; 32];
+        for nonce in [0, 1, 1337, u64::MAX] {
+            let hash = block.hash_with_nonce(nonce);
+            let mut trial = block.clone();
+            trial.nonce = nonce;
+            assert_eq!(hash, trial.calc_hash());
+        }
+        assert_eq!(block.nonce, 0);
+    }
+
+    #[test]
+    fn test_salt() {
+        let mut block = Block::with_nonce(1, String::from("Some data"), [0u8; 32], 42);
+        let unsalted = block.hash;
+
+        block.salt = String::from("main-net");
+        let main = block.calc_hash();
+        block.salt = String::from("test-net");
+        let test = block.calc_hash();
+        assert_ne!(main, unsalted);
+        assert_ne!(main, test);
+
+        // mined on one network isn't valid on another
+        block.update_hash();
+        assert!(block.validate_hash());
+        block.salt = String::from("main-net");
+        assert!(!block.validate_hash());
+
+        // blocks without salt are decoded with empty one
+        let mut json: serde_json::Value = serde_json::to_value(&block).unwrap();
+        json.as_object_mut().unwrap().remove("salt");
+        let decoded: Block = serde_json::from_value(json).unwrap();
+        assert!(decoded.salt.is_empty());
+    }
+
+    #[test]
+    fn test_leading_zero_bits() {
+        let mut block = Block::new(1, String::from("Some data"));
+        assert_eq!(block.leading_zero_bits(), 256);
+
+        block.hash[2] = 0b0001_0000;
+        block.hash[3] = 0xff;
+        assert_eq!(block.leading_zero_bits(), 19);
+
+        block.hash[0] = 0x80;
+        assert_eq!(block.leading_zero_bits(), 0);
+    }
+
+    #[test]
+    fn test_trailing_zero_bits() {
+        let mut block = Block::new(1, String::from("Some data"));
+        assert_eq!(block.trailing_zero_bits(), 256);
+
+        block.hash[29] = 0b0000_1000;
+        block.hash[28] = 0xff;
+        assert_eq!(block.trailing_zero_bits(), 19);
+
+        // mined for "000" suffix
+        block.hash = [0xff; 32];
+        block.hash[31] = 0;
+        block.hash[30] = 0xf0;
+        assert!(block.string_hash().ends_with("000"));
+        assert_eq!(block.trailing_zero_bits(), 12);
+    }
+
+    #[test]
+    fn test_summary() {
+        let data = "ы".repeat(BlockSummary::DATA_PREVIEW + 10);
+        let block = Block::with_nonce(1, data.clone(), [7u8; 32], 42);
+        let summary = block.summary();
+
+        assert_eq!(summary.id, 1);
+        assert_eq!(summary.nonce, 42);
+        assert_eq!(summary.hash_hex.len(), 64);
+        assert_eq!(summary.hash_hex, block.string_hash());
+        assert_eq!(summary.prev_hex, "07".repeat(32));
+        assert_eq!(summary.data_len, data.len());
+        assert_eq!(
+            summary.data_preview,
+            format!("{}...", "ы".repeat(BlockSummary::DATA_PREVIEW))
+        );
+
+        // short data isn't truncated
+        let block = Block::new(2, String::from("Some data"));
+        assert_eq!(block.summary().data_preview, "Some data");
+
+        let json = serde_json::to_string(&summary).unwrap();
+        assert!(json.contains("\"hash_hex\""));
+    }
+
+    #[test]
+    fn test_canonical_bytes() {
+        use sha2::{Digest, Sha256};
+
+        let mut block = Block::new(3, String::from("Some data"));
+        block.prev = [7u8; 32];
+        block.nonce = 42;
+        for salt in ["", "test-net"] {
+            block.salt = String::from(salt);
+            let hash: [u8; 32] = Sha256::digest(block.canonical_bytes()).into();
+            assert_eq!(hash, block.calc_hash());
+            assert_eq!(hash, block.hash_with_nonce(42));
+        }
+        let bytes = block.canonical_bytes();
+        assert_eq!(&bytes[..8], &8u64.to_be_bytes());
+        assert_eq!(&bytes[8..16], b"test-net");
+        assert_eq!(&bytes[bytes.len() - 16..bytes.len() - 8], &42u64.to_be_bytes());
+        assert_eq!(&bytes[bytes.len() - 8..], &block.timestamp.to_be_bytes());
+
+        // a zero timestamp is hashed too
+        block.timestamp = 0;
+        let bytes = block.canonical_bytes();
+        assert_eq!(&bytes[bytes.len() - 16..bytes.len() - 8], &42u64.to_be_bytes());
+        assert_eq!(&bytes[bytes.len() - 8..], &0u64.to_be_bytes());
+        assert_eq!(block.calc_hash(), block.hash_with_nonce(42));
+
+        // so a transaction can't pass for the timestamp of another block
+        let mut forged = block.clone();
+        forged.timestamp = 9;
+        forged.transactions = vec![String::from("x")];
+        block.transactions = vec![String::from("\0\0\0\0\0\0\0\u{1}x")];
+        assert_ne!(block.canonical_bytes(), forged.canonical_bytes());
+        assert_ne!(block.calc_hash(), forged.calc_hash());
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let mut block = Block::new_with_timestamp(1, String::from("Some data"), 1_700_000_000);
+        block.update_hash();
+        assert!(block.validate_hash());
+        assert!(block.to_string().ends_with("time 2023-11-14 22:13:20 UTC"));
+        assert_eq!(block.header().timestamp, 1_700_000_000);
+
+        // tampering with the time invalidates the hash
+        let mut tampered = block.clone();
+        tampered.timestamp += 1;
+        assert!(!tampered.validate_hash());
+        assert!(!tampered.equals(&block));
+        assert!(!tampered.preequals(&block));
+
+        // serde keeps the time
+        let json = serde_json::to_string(&block).unwrap();
+        assert!(serde_json::from_str::<Block>(&json).unwrap().equals(&block));
+        let bytes = bincode::serialize(&block).unwrap();
+        assert!(bincode::deserialize::<Block>(&bytes).unwrap().equals(&block));
+
+        // blocks from before timestamps stay valid
+        let mut old = Block::with_nonce(1, String::from("Some data"), [0u8; 32], 7);
+        let json = serde_json::to_string(&old).unwrap().replace(",\"timestamp\":0", "");
+        assert!(!json.contains("timestamp"));
+        old = serde_json::from_str(&json).unwrap();
+        assert_eq!(old.timestamp, 0);
+        assert!(old.validate_hash());
+    }
+
+    #[test]
+    fn test_transactions() {
+        let mut block = Block::with_transactions(1, vec![String::from("Alice"), String::from("Bob")]);
+        assert_eq!(block.data, "Alice");
+        assert_eq!(block.transactions().collect::<Vec<_>>(), ["Alice", "Bob"]);
+        assert!(block.to_string().contains("data \"Alice\", \"Bob\", nonce"));
+
+        block.add_transaction(String::from("Carol"));
+        assert!(block.validate_hash());
+        assert_eq!(block.hash_with_nonce(block.nonce), block.hash);
+        assert_eq!(block.transactions().last(), Some("Carol"));
+
+        // transactions are hashed in order and can't be merged or moved
+        let mut swapped = block.clone();
+        swapped.transactions.swap(0, 1);
+        assert!(!swapped.validate_hash());
+        assert!(!swapped.preequals(&block));
+        let mut merged = block.clone();
+        merged.transactions = vec![String::from("BobCarol")];
+        assert_ne!(merged.calc_hash(), block.hash);
+
+        // serde keeps the transactions
+        let json = serde_json::to_string(&block).unwrap();
+        assert!(serde_json::from_str::<Block>(&json).unwrap().equals(&block));
+        let bytes = bincode::serialize(&block).unwrap();
+        assert!(bincode::deserialize::<Block>(&bytes).unwrap().equals(&block));
+
+        // blocks with a single transaction are hashed as before, and old blocks are decoded
+        let single = Block::with_transactions(1, vec![String::from("Alice")]);
+        let block = Block::new_single(1, String::from("Alice"));
+        assert_eq!(block.transactions().collect::<Vec<_>>(), single.transactions().collect::<Vec<_>>());
+        assert!(single.transactions.is_empty());
+        let json = serde_json::to_string(&single).unwrap().replace(",\"transactions\":[]", "");
+        assert!(serde_json::from_str::<Block>(&json).unwrap().equals(&single));
+        assert_eq!(Block::with_nonce(1, String::from("Alice"), [0u8; 32], 0).canonical_bytes().len(), 8 + 5 + 32 + 8 + 8);
+    }
+
+    #[test]
+    fn test_mining_attempts() {
+        let mut block = Block::with_nonce(1, String::from("Data"), [0u8; 32], 42);
+        let hash = block.hash;
+        block.mining_attempts = Some(43);
+        assert!(block.validate_hash());
+        assert_eq!(block.calc_hash(), hash);
+        assert!(block.to_string().ends_with(", 43 attempts"));
+
+        // older blocks have no attempts
+        let json = serde_json::to_string(&block).unwrap().replace(",\"mining_attempts\":43", "");
+        assert_eq!(serde_json::from_str::<Block>(&json).unwrap().mining_attempts, None);
+        let bytes = bincode::serialize(&block).unwrap();
+        assert_eq!(bincode::deserialize::<Block>(&bytes).unwrap().mining_attempts, Some(43));
+    }
 }
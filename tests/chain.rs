@@ -3,8 +3,19 @@ mod chain_tests {
 
     use std::collections::VecDeque;
 
+    use rustychain::ActivitySummary;
     use rustychain::Block;
+    use rustychain::BlockHeader;
+    use rustychain::BlockSummary;
     use rustychain::Chain;
+    use rustychain::ChainError;
+    use rustychain::ChainStats;
+    use rustychain::DbFormat;
+    use rustychain::DifficultyMode;
+    use rustychain::HashAlgo;
+    use rustychain::TipComparison;
+    use rustychain::ValidationPolicy;
+    use rustychain::RETARGET_WINDOW;
 
     #[test]
     fn test_have_errors() {
@@ -21,6 +32,9 @@ mod chain_tests {
             blocks: vec![block0, block1, block2],
             status: true,
             queue: VecDeque::new(),
+            difficulty: String::new(),
+            difficulty_mode: DifficultyMode::Suffix,
+            hash_algo: HashAlgo::Sha256,
         };
 
         assert_eq!(chain.have_errors(), None);
@@ -36,7 +50,7 @@ mod chain_tests {
         assert_eq!(chain.have_errors(), Some(1));
         let block1 = chain.blocks.get_mut(1).unwrap();
         block1.id = 1;
-        block1.prev = block1.prev.map(|x| x + 1);
+        block1.prev = block1.prev.map(|x| x.wrapping_add(1));
         assert_eq!(chain.have_errors(), Some(1));
     }
 
@@ -54,6 +68,9 @@ mod chain_tests {
             blocks: vec![block0],
             status: false,
             queue: VecDeque::from(vec![block1, block2]),
+            difficulty: String::new(),
+            difficulty_mode: DifficultyMode::Suffix,
+            hash_algo: HashAlgo::Sha256,
         };
         assert!(!chain.try_add());
         assert_eq!(chain.blocks.len(), 1);
@@ -77,4 +94,1070 @@ mod chain_tests {
         chain.blocks.last_mut().unwrap().update_hash();
         assert_eq!(chain.have_errors(), None);
     }
+
+    #[test]
+    fn test_append_block() {
+        let mut block0 = Block::new(0, String::from("First"));
+        block0.update_hash();
+        let mut block1 = Block::new(1, String::from("Second"));
+        block1.prev = block0.hash;
+        block1.update_hash();
+
+        let mut chain = Chain::new();
+        assert_eq!(
+            chain.append_block(block1.clone()),
+            Err(ChainError::WrongId {
+                expected: 0,
+                found: 1
+            })
+        );
+        assert_eq!(chain.append_block(block0), Ok(()));
+
+        let mut broken = block1.clone();
+        broken.nonce += 1;
+        assert_eq!(chain.append_block(broken), Err(ChainError::InvalidHash(1)));
+
+        chain.status = false;
+        assert_eq!(chain.append_block(block1.clone()), Err(ChainError::Busy));
+        chain.status = true;
+
+        assert_eq!(chain.append_block(block1), Ok(()));
+        assert_eq!(chain.blocks.len(), 2);
+        assert_eq!(chain.have_errors(), None);
+    }
+
+    #[test]
+    fn test_contains_data() {
+        let mut chain = Chain::with_genesis("test-net");
+        for data in ["First", "Second", "Third"] {
+            chain.add_queue(Block::new(0, String::from(data)));
+            assert!(chain.try_add());
+            chain.blocks.last_mut().unwrap().update_hash();
+        }
+
+        assert!(chain.contains_data("Second"));
+        assert_eq!(chain.block_id_for_data("Second"), Some(2));
+        assert_eq!(chain.block_id_for_data("Fourth"), None);
+
+        chain.rollback(2);
+        assert_eq!(chain.blocks.len(), 2);
+        assert!(!chain.contains_data("Second"));
+        assert!(!chain.contains_data("Third"));
+        assert_eq!(chain.block_id_for_data("First"), Some(1));
+        assert_eq!(chain.queue.len(), 2);
+        assert_eq!(chain.queue[0].data, "Second");
+
+        // other transactions of blocks are found too
+        let mut block = Block::new(0, String::from("Fifth"));
+        block.add_transaction(String::from("Sixth"));
+        chain.queue.clear();
+        chain.add_queue(block);
+        assert!(chain.try_add());
+        assert_eq!(chain.block_id_for_data("Sixth"), Some(2));
+    }
+
+    #[test]
+    fn test_export_headers() {
+        let mut chain = Chain::with_genesis("test-net");
+        for data in ["First", "Second", "Third"] {
+            chain.add_queue(Block::new(0, String::from(data)));
+            assert!(chain.try_add());
+            chain.blocks.last_mut().unwrap().update_hash();
+        }
+
+        let headers = chain.export_headers();
+        assert_eq!(headers.len(), chain.blocks.len());
+        assert_eq!(BlockHeader::verify_linkage(&headers), Ok(()));
+        for (header, block) in headers.iter().zip(chain.blocks.iter()) {
+            assert_eq!(header.id, block.id);
+            assert_eq!(header.hash, block.hash);
+        }
+
+        // light client gets JSON
+        let json = serde_json::to_string(&headers).unwrap();
+        let mut headers: Vec<BlockHeader> = serde_json::from_str(&json).unwrap();
+        assert_eq!(BlockHeader::verify_linkage(&headers), Ok(()));
+
+        headers[2].prev = headers[0].hash;
+        assert_eq!(
+            BlockHeader::verify_linkage(&headers),
+            Err(ChainError::InvalidPrev(2))
+        );
+    }
+
+    #[test]
+    fn test_split_at() {
+        let mut chain = Chain::with_genesis("test-net");
+        for data in ["First", "Second", "Third", "Fourth"] {
+            chain.add_queue(Block::new(0, String::from(data)));
+            assert!(chain.try_add());
+            chain.blocks.last_mut().unwrap().update_hash();
+        }
+        assert_eq!(chain.blocks.len(), 5);
+
+        let (lower, rest) = chain.split_at(2);
+        assert_eq!(lower.blocks.len(), 3);
+        assert_eq!(lower.have_errors(), None);
+        assert!(lower.blocks[0].equals(&chain.blocks[0]));
+        assert_eq!(rest.len(), 2);
+        assert_eq!(rest[0].id, 3);
+        assert_eq!(rest[0].prev, lower.blocks[2].hash);
+
+        // detached blocks still link to each other
+        let headers: Vec<BlockHeader> = rest.iter().map(|b| b.header()).collect();
+        assert_eq!(BlockHeader::verify_linkage(&headers), Ok(()));
+
+        // original chain is untouched
+        assert_eq!(chain.blocks.len(), 5);
+
+        let (whole, rest) = chain.split_at(10);
+        assert_eq!(whole.blocks.len(), 5);
+        assert!(rest.is_empty());
+
+        // the height past the end doesn't overflow
+        let (whole, rest) = chain.split_at(u64::MAX);
+        assert_eq!(whole.blocks.len(), 5);
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn test_with_capacity() {
+        let mut chain = Chain::with_capacity(16);
+        let mut expected = Chain::new();
+        assert!(chain.blocks.capacity() >= 16);
+        assert_eq!(chain.status, expected.status);
+        assert!(chain.queue.is_empty());
+
+        let first = Block::new(0, String::from("First"));
+        for c in [&mut chain, &mut expected] {
+            c.add_queue(first.clone());
+            assert!(c.try_add());
+            c.blocks.last_mut().unwrap().update_hash();
+        }
+        assert!(chain.blocks[0].equals(&expected.blocks[0]));
+        assert_eq!(chain.have_errors(), None);
+        assert!(chain.blocks.capacity() >= 16);
+    }
+
+    #[test]
+    fn test_verify_against_headers() {
+        let mut chain = Chain::test_chain(3);
+        let headers = chain.export_headers();
+        assert_eq!(chain.verify_against_headers(&headers), Ok(()));
+
+        // body with other data, but claiming the same header
+        let mut forged = chain.clone();
+        forged.blocks[1].data = String::from("Forged");
+        assert_eq!(forged.blocks[1].header(), headers[1]);
+        assert_eq!(
+            forged.verify_against_headers(&headers),
+            Err(ChainError::InvalidHash(1))
+        );
+
+        // other header
+        let mut other = chain.clone();
+        other.blocks[2].nonce += 1;
+        other.blocks[2].update_hash();
+        assert_eq!(
+            other.verify_against_headers(&headers),
+            Err(ChainError::HeaderMismatch(2))
+        );
+
+        // missing body
+        chain.blocks.pop();
+        assert_eq!(
+            chain.verify_against_headers(&headers),
+            Err(ChainError::HeaderMismatch(2))
+        );
+    }
+
+    #[test]
+    fn test_recent_activity() {
+        let mut chain = Chain::with_genesis("test-net");
+        let long = "x".repeat(ActivitySummary::DATA_PREVIEW + 10);
+        for data in ["First", "Second", long.as_str()] {
+            chain.add_queue(Block::new(0, String::from(data)));
+            assert!(chain.try_add());
+            chain.blocks.last_mut().unwrap().update_hash();
+        }
+
+        let activity = chain.recent_activity(2);
+        assert_eq!(activity.len(), 2);
+        assert_eq!(activity[0].height, 2);
+        assert_eq!(activity[0].data, "Second");
+        assert_eq!(activity[0].hash, chain.blocks[2].short_hash());
+        assert_eq!(activity[1].height, 3);
+        assert_eq!(
+            activity[1].data,
+            format!("{}...", "x".repeat(ActivitySummary::DATA_PREVIEW))
+        );
+
+        assert_eq!(chain.recent_activity(10).len(), 4);
+
+        // timestamps of `test_chain` are one second apart, the genesis block has none
+        let activity = Chain::test_chain(4).recent_activity(10);
+        assert_eq!(activity[2].timestamp, 2);
+        let since: Vec<_> = activity.iter().map(|a| a.since_previous).collect();
+        assert_eq!(since, [None, None, Some(1), Some(1)]);
+        let activity = Chain::test_chain(4).recent_activity(1);
+        assert_eq!(activity[0].since_previous, Some(1));
+    }
+
+    #[test]
+    fn test_chain_file() {
+        let name = format!("rustychain-chain-{}.json", rand::random::<u64>());
+        let path = std::env::temp_dir().join(name);
+        let chain = Chain::test_chain(3);
+        chain.save_to_file(&path, DbFormat::Json).unwrap();
+
+        let loaded = Chain::load_from_file(&path).unwrap();
+        assert_eq!(loaded.blocks.len(), 3);
+        assert_eq!(loaded.export_headers(), chain.export_headers());
+
+        let content = std::fs::read(&path).unwrap();
+
+        // truncated
+        std::fs::write(&path, &content[..content.len() - 10]).unwrap();
+        assert_eq!(
+            Chain::load_from_file(&path).unwrap_err(),
+            ChainError::ChecksumMismatch
+        );
+
+        // corrupted
+        let mut corrupted = content.clone();
+        let last = corrupted.len() - 20;
+        corrupted[last] ^= 1;
+        std::fs::write(&path, &corrupted).unwrap();
+        assert_eq!(
+            Chain::load_from_file(&path).unwrap_err(),
+            ChainError::ChecksumMismatch
+        );
+
+        // saved with an invalid block, so the checksum matches
+        for format in [DbFormat::Json, DbFormat::Bincode] {
+            let mut tampered = chain.clone();
+            tampered.blocks[1].data = String::from("Tampered");
+            tampered.save_to_file(&path, format).unwrap();
+            assert_eq!(
+                Chain::load_from_file(&path).unwrap_err(),
+                ChainError::InvalidHash(1)
+            );
+
+            tampered.blocks[1] = chain.blocks[1].clone();
+            tampered.blocks[2].prev = [0u8; 32];
+            tampered.blocks[2].update_hash();
+            tampered.save_to_file(&path, format).unwrap();
+            assert_eq!(
+                Chain::load_from_file(&path).unwrap_err(),
+                ChainError::InvalidPrev(2)
+            );
+        }
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            Chain::load_from_file(&path),
+            Err(ChainError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_tip_matches() {
+        let mut chain = Chain::test_chain(3);
+        let tip = chain.blocks[2].hash;
+
+        assert_eq!(chain.tip_matches(2, &tip), TipComparison::Same);
+        assert_eq!(
+            chain.tip_matches(2, &[0u8; 32]),
+            TipComparison::DifferentHashSameHeight
+        );
+        assert_eq!(chain.tip_matches(3, &[0u8; 32]), TipComparison::Behind);
+        assert_eq!(
+            chain.tip_matches(1, &chain.blocks[1].hash),
+            TipComparison::Ahead
+        );
+
+        // the block which is mined now isn't the tip
+        chain.add_queue(Block::new(0, String::from("Third")));
+        assert!(chain.try_add());
+        chain.status = false;
+        assert_eq!(chain.tip_matches(2, &tip), TipComparison::Same);
+
+        assert_eq!(Chain::new().tip_matches(0, &tip), TipComparison::Behind);
+    }
+
+    #[test]
+    fn test_chain_file_formats() {
+        let mut chain = Chain::test_chain(3);
+        chain.blocks[2].salt = String::from("test-net");
+        chain.blocks[2].update_hash();
+        chain.add_queue(Block::new(0, String::from("Queued")));
+
+        let mut sizes = Vec::new();
+        for format in [DbFormat::Json, DbFormat::Bincode] {
+            let name = format!("rustychain-chain-{}.{}", rand::random::<u64>(), format);
+            let path = std::env::temp_dir().join(name);
+            chain.save_to_file(&path, format).unwrap();
+            sizes.push(std::fs::metadata(&path).unwrap().len());
+
+            // the format is detected on load
+            let loaded = Chain::load_from_file(&path).unwrap();
+            assert_eq!(loaded.export_headers(), chain.export_headers());
+            assert_eq!(loaded.blocks[2].salt, "test-net");
+            assert_eq!(loaded.queue.len(), 1);
+            assert!(loaded.queue[0].equals(&chain.queue[0]));
+            assert_eq!(loaded.status, chain.status);
+            std::fs::remove_file(&path).unwrap();
+        }
+        assert!(sizes[1] < sizes[0]);
+    }
+
+    #[test]
+    fn test_test_chain() {
+        let chain = Chain::test_chain(10);
+        assert_eq!(chain.have_errors(), None);
+        assert_eq!(chain.blocks.last().unwrap().id, 9);
+        assert!(chain.status);
+        assert!(chain.blocks.iter().all(|block| block.validate_hash()));
+
+        // the data is deterministic
+        assert_eq!(
+            chain.export_headers(),
+            Chain::test_chain(10).export_headers()
+        );
+        assert!(Chain::test_chain(0).blocks.is_empty());
+    }
+
+    #[test]
+    fn test_diff() {
+        let base = Chain::test_chain(4);
+        assert!(base.diff(&base).is_empty());
+
+        // deliberately fork the chain
+        let mut local = base.clone();
+        let mut remote = base.clone();
+        let extend = |chain: &mut Chain, data: &str| {
+            chain.add_queue(Block::new(0, String::from(data)));
+            assert!(chain.try_add());
+            chain.blocks.last_mut().unwrap().update_hash();
+        };
+        extend(&mut local, "Local A");
+        extend(&mut local, "Local B");
+        extend(&mut remote, "Remote A");
+
+        let diff = local.diff(&remote);
+        assert_eq!(diff.fork_point, 4);
+        assert_eq!(local.fork_point(&remote), remote.fork_point(&local));
+        let data = |blocks: &[BlockSummary]| {
+            blocks
+                .iter()
+                .map(|block| block.data_preview.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(data(&diff.only_local), ["Local A", "Local B"]);
+        assert_eq!(data(&diff.only_remote), ["Remote A"]);
+        assert_eq!(diff.only_local[0].id, 4);
+        assert!(diff.to_string().starts_with("fork point: #4"));
+
+        // a shorter chain without forks
+        let diff = local.diff(&base);
+        assert_eq!(diff.fork_point, 4);
+        assert_eq!(diff.only_local.len(), 2);
+        assert!(diff.only_remote.is_empty());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn test_assert_invariants() {
+        use std::panic::catch_unwind;
+
+        let mut chain = Chain::test_chain(4);
+        chain.add_queue(Block::new(0, String::from("Queued")));
+        chain.assert_invariants();
+
+        let mut wrong_id = chain.clone();
+        wrong_id.blocks[2].id = 3;
+        let mut wrong_queue = chain.clone();
+        wrong_queue.queue[0].id = 2;
+        let mut wrong_genesis = chain.clone();
+        wrong_genesis.blocks[0].prev = [1; 32];
+        let mut wrong_tip = chain.clone();
+        wrong_tip.blocks[3].nonce += 1;
+        let mut wrong_status = Chain::new();
+        wrong_status.status = false;
+
+        for corrupted in [
+            wrong_id,
+            wrong_queue,
+            wrong_genesis,
+            wrong_tip,
+            wrong_status,
+        ] {
+            assert!(corrupted.invariant_error().is_some());
+            assert!(catch_unwind(|| corrupted.assert_invariants()).is_err());
+        }
+
+        // the last block isn't mined yet
+        chain.blocks[3].nonce += 1;
+        chain.status = false;
+        chain.assert_invariants();
+    }
+
+    #[test]
+    fn test_validate_range() {
+        let local = Chain::test_chain(3);
+        let remote = Chain::test_chain(6);
+        let range = &remote.blocks[3..];
+        assert_eq!(local.validate_range(range), Ok(()));
+        assert_eq!(local.validate_range(&[]), Ok(()));
+
+        // doesn't attach to the tip
+        let mut other = Chain::with_genesis("other-chain");
+        for id in 1..6 {
+            other.add_queue(Block::new(0, format!("Block {id}")));
+            other.try_add();
+            other.blocks[id].update_hash();
+        }
+        assert_eq!(
+            local.validate_range(&other.blocks[3..]),
+            Err(ChainError::InvalidPrev(3))
+        );
+        assert_eq!(
+            local.validate_range(&remote.blocks[4..]),
+            Err(ChainError::WrongId {
+                expected: 3,
+                found: 4
+            })
+        );
+
+        // internally broken
+        let mut broken = range.to_vec();
+        broken[1].data = String::from("Tampered");
+        assert_eq!(
+            local.validate_range(&broken),
+            Err(ChainError::InvalidHash(4))
+        );
+        broken[1].update_hash();
+        assert_eq!(
+            local.validate_range(&broken),
+            Err(ChainError::InvalidPrev(5))
+        );
+        let gap = [range[0].clone(), range[2].clone()];
+        assert_eq!(
+            local.validate_range(&gap),
+            Err(ChainError::WrongId {
+                expected: 4,
+                found: 5
+            })
+        );
+    }
+
+    #[test]
+    fn test_from_blocks() {
+        let valid = Chain::test_chain(5);
+        let chain = Chain::from_blocks(valid.blocks.clone()).unwrap();
+        assert_eq!(chain.export_headers(), valid.export_headers());
+        assert!(chain.status);
+        assert!(chain.queue.is_empty());
+
+        // gathered out of order
+        let mut shuffled = valid.blocks.clone();
+        shuffled.reverse();
+        shuffled.swap(0, 2);
+        let chain = Chain::from_blocks(shuffled).unwrap();
+        assert_eq!(chain.export_headers(), valid.export_headers());
+
+        // block #2 is missing
+        let mut gap = valid.blocks.clone();
+        gap.remove(2);
+        assert_eq!(
+            Chain::from_blocks(gap).unwrap_err(),
+            ChainError::WrongId {
+                expected: 2,
+                found: 3
+            }
+        );
+        assert!(Chain::from_blocks(Vec::new()).unwrap().blocks.is_empty());
+    }
+
+    #[test]
+    fn test_take_queue() {
+        let mut chain = Chain::test_chain(2);
+        for data in ["First", "Second", "Third"] {
+            chain.add_queue(Block::new(0, data.to_owned()));
+        }
+
+        let queue = chain.take_queue();
+        let data: Vec<&str> = queue.iter().map(|block| block.data.as_str()).collect();
+        assert_eq!(data, ["First", "Second", "Third"]);
+        assert!(chain.queue.is_empty());
+        assert!(chain.take_queue().is_empty());
+
+        // restored blocks are mined first
+        chain.add_queue(Block::new(0, "Fourth".to_owned()));
+        chain.restore_queue(queue);
+        let ids: Vec<u64> = chain.queue.iter().map(|block| block.id).collect();
+        assert_eq!(ids, [2, 3, 4, 5]);
+        assert_eq!(chain.queue[0].data, "First");
+        assert_eq!(chain.queue[3].data, "Fourth");
+    }
+
+    #[test]
+    fn test_common_ancestor() {
+        let base = Chain::test_chain(3);
+        let extend = |chain: &mut Chain, data: &str| {
+            chain.add_queue(Block::new(0, String::from(data)));
+            assert!(chain.try_add());
+            chain.blocks.last_mut().unwrap().update_hash();
+        };
+        let mut first = base.clone();
+        let mut second = base.clone();
+        let mut third = base.clone();
+        extend(&mut first, "First A");
+        extend(&mut first, "First B");
+        extend(&mut second, "Second A");
+        extend(&mut third, "Third A");
+
+        assert_eq!(Chain::common_ancestor(&[&first, &second, &third]), Some(2));
+        assert_eq!(Chain::common_ancestor(&[&third, &base, &first]), Some(2));
+        assert_eq!(Chain::common_ancestor(&[&first]), Some(4));
+
+        let other = Chain::with_genesis("other-chain");
+        assert_eq!(Chain::common_ancestor(&[&first, &second, &other]), None);
+    }
+
+    #[test]
+    fn test_replace_tip_if() {
+        let mut chain = Chain::test_chain(3);
+        let tip = chain.blocks[2].clone();
+        let mut other = tip.clone();
+        other.nonce = 7;
+        other.update_hash();
+        let lower = |new: &Block, old: &Block| new.hash < old.hash;
+        let (low, high) = if other.hash < tip.hash {
+            (other, tip.clone())
+        } else {
+            (tip.clone(), other)
+        };
+
+        // replaced only when better
+        chain.blocks[2] = high.clone();
+        assert!(!chain.replace_tip_if(high.clone(), lower));
+        assert!(chain.replace_tip_if(low.clone(), lower));
+        assert_eq!(chain.blocks[2].hash, low.hash);
+        assert_eq!(chain.blocks[2].nonce, low.nonce);
+        assert!(!chain.replace_tip_if(high.clone(), lower));
+        assert_eq!(chain.blocks[2].hash, low.hash);
+        assert_eq!(chain.blocks.len(), 3);
+        assert_eq!(chain.have_errors(), None);
+
+        // other blocks never replace the tip
+        let mut different = high.clone();
+        different.data = String::from("Other data");
+        assert!(!chain.replace_tip_if(different, |_, _| true));
+        assert!(!chain.replace_tip_if(chain.blocks[1].clone(), |_, _| true));
+        assert!(!Chain::new().replace_tip_if(high, |_, _| true));
+        assert_eq!(chain.blocks[2].hash, low.hash);
+    }
+
+    #[test]
+    fn test_stats() {
+        let stats = Chain::new().stats();
+        assert_eq!(stats, ChainStats::default());
+
+        let mut chain = Chain::test_chain(3);
+        chain.add_queue(Block::new(0, String::from("Mining")));
+        chain.add_queue(Block::new(0, String::from("Queued")));
+        assert!(chain.try_add());
+        chain.status = false;
+        chain.blocks[1].hash[31] = 0x10; // 4 bits of work
+        chain.blocks[2].hash[31] = 0x00; // 8 bits of work
+        chain.blocks[2].hash[30] = 0x01;
+
+        // the block being mined has no work yet
+        let stats = chain.stats();
+        assert_eq!(stats.height, 3);
+        assert_eq!(stats.total_blocks, 4);
+        assert_eq!(stats.queue_len, 1);
+        assert_eq!(stats.total_work, 16.0 + 256.0);
+        let data = "test-chain".len() + 2 * "Block 1".len() + "Mining".len();
+        assert_eq!(stats.total_data_bytes, data);
+        // the block being mined isn't timed either
+        assert_eq!(stats.avg_block_time, Some(1.0));
+        assert!(stats.to_string().starts_with("height: 3\r\n"));
+        assert!(stats.to_string().ends_with("block time: 1.0 s\r\n"));
+    }
+
+    #[test]
+    fn test_pending_ids() {
+        let mut chain = Chain::test_chain(3);
+        assert!(chain.pending_ids().is_empty());
+        for data in ["First", "Second", "Third"] {
+            chain.add_queue(Block::new(0, data.to_owned()));
+        }
+        assert_eq!(chain.pending_ids(), [3, 4, 5]);
+        assert_eq!(chain.pending_data(), ["First", "Second", "Third"]);
+
+        assert!(chain.try_add());
+        assert_eq!(chain.pending_ids(), [4, 5]);
+        assert_eq!(chain.pending_data(), ["Second", "Third"]);
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let chain = Chain::test_chain(3);
+        let dot = chain.to_dot();
+        assert!(dot.starts_with("digraph chain {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert_eq!(dot.matches("[label=").count(), 3);
+        assert_eq!(dot.matches(" -> ").count(), 2);
+        assert!(dot.contains("b2 -> b1;"));
+        assert!(dot.contains(&format!("#1\\n{}", chain.blocks[1].short_hash())));
+
+        // a broken link has no edge
+        let mut broken = chain.clone();
+        broken.blocks[2].prev = [1; 32];
+        assert_eq!(broken.to_dot().matches(" -> ").count(), 1);
+    }
+
+    #[test]
+    fn test_search_data() {
+        let mut chain = Chain::with_genesis("test-net");
+        for data in ["Alice pays Bob", "Bob pays Carol", "Carol pays Alice", "Dave"] {
+            chain.add_queue(Block::new(0, data.to_owned()));
+            assert!(chain.try_add());
+            chain.blocks.last_mut().unwrap().update_hash();
+        }
+        chain.add_queue(Block::new(0, String::from("Queued Alice")));
+
+        let ids = |blocks: Vec<&Block>| blocks.iter().map(|b| b.id).collect::<Vec<_>>();
+        assert_eq!(ids(chain.search_data("Alice")), [1, 3]);
+        assert_eq!(ids(chain.search_data("pays")), [1, 2, 3]);
+        assert_eq!(ids(chain.search_data("test")), [0]);
+        assert!(chain.search_data("Eve").is_empty());
+        assert_eq!(ids(chain.find_blocks(|b| b.data.len() < 5)), [4]);
+    }
+
+    #[test]
+    fn test_get_block() {
+        let mut chain = Chain::test_chain(4);
+        for block in chain.blocks.iter() {
+            assert!(chain.get_block(block.id).unwrap().equals(block));
+            assert!(chain.get_block_by_hash(&block.hash).unwrap().equals(block));
+        }
+        assert!(chain.get_block(4).is_none());
+        assert!(chain.get_block(u64::MAX).is_none());
+
+        // queued blocks aren't in the chain yet
+        chain.add_queue(Block::new(0, String::from("Queued")));
+        let queued = chain.queue[0].calc_hash();
+        assert!(chain.get_block(4).is_none());
+        assert!(chain.get_block_by_hash(&queued).is_none());
+
+        // a misplaced block isn't returned by id
+        chain.blocks[2].id = 3;
+        assert!(chain.get_block(2).is_none());
+        assert_eq!(chain.get_block(3).unwrap().data, "Block 3");
+    }
+
+    #[test]
+    fn test_remine_tip() {
+        let mut chain = Chain::test_chain(4);
+        let old = chain.blocks[3].clone();
+        chain.remine_tip(10).unwrap();
+        let tip = &chain.blocks[3];
+        assert!(tip.trailing_zero_bits() >= 10);
+        assert!(tip.validate_hash());
+        assert!(tip.preequals(&old));
+        assert_eq!(chain.have_errors(), None);
+
+        // enough work already
+        let hash = chain.blocks[3].hash;
+        chain.remine_tip(4).unwrap();
+        assert_eq!(chain.blocks[3].hash, hash);
+
+        // the recorded difficulty is kept
+        chain.difficulty = String::from("8000");
+        chain.remine_tip(15).unwrap();
+        assert!(chain.blocks[3].trailing_zero_bits() >= 15);
+        assert!(chain.meets_difficulty(&chain.blocks[3]));
+        assert_eq!(chain.remine_tip(16), Err(ChainError::ImpossibleWork(16)));
+        chain.difficulty = String::from("a");
+        assert_eq!(chain.remine_tip(2), Err(ChainError::ImpossibleWork(2)));
+        chain.difficulty = String::new();
+        assert_eq!(chain.remine_tip(257), Err(ChainError::ImpossibleWork(257)));
+
+        chain.status = false;
+        assert_eq!(chain.remine_tip(16), Err(ChainError::Busy));
+        assert_eq!(Chain::with_genesis("test-net").remine_tip(4), Err(ChainError::NoTip));
+        assert_eq!(Chain::new().remine_tip(4), Err(ChainError::NoTip));
+    }
+
+    #[test]
+    fn test_remine_tip_prefix() {
+        let mut chain = Chain::test_chain(2);
+        chain.difficulty = String::new();
+        chain.difficulty_mode = DifficultyMode::Prefix;
+        chain.remine_tip(10).unwrap();
+        assert!(chain.blocks[1].leading_zero_bits() >= 10);
+        assert!(chain.total_work() >= 1 << 10);
+        assert_eq!(chain.have_errors(), None);
+
+        // the work is counted at the start of hashes
+        let policy = ValidationPolicy {
+            min_difficulty_bits: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(chain.verify_with_policy(&policy), Ok(()));
+
+        // a hash starting with "1" has exactly 3 leading zero bits
+        chain.difficulty = String::from("1");
+        assert_eq!(chain.remine_tip(4), Err(ChainError::ImpossibleWork(4)));
+        chain.remine_tip(3).unwrap();
+        assert!(chain.blocks[1].string_hash().starts_with('1'));
+        assert_eq!(chain.blocks[1].leading_zero_bits(), 3);
+    }
+
+    #[test]
+    fn test_integrity_report() {
+        let mut chain = Chain::test_chain(3);
+        chain.difficulty = String::from("00");
+        chain.add_queue(Block::new(0, String::from("Queued")));
+        let report = chain.integrity_report();
+        for section in ["height: 3", "status: ready", "work: ", "queue: 1", "errors: none"] {
+            assert!(report.contains(section), "no {:?} in {}", section, report);
+        }
+        assert!(report.contains(&format!("genesis: {}", chain.blocks[0].string_hash())));
+        assert!(report.contains(&format!("tip: {}", chain.blocks[2].string_hash())));
+        assert!(report.contains("difficulty: \"00\" (suffix)"));
+
+        // every invalid block is reported
+        chain.blocks[1].data = String::from("Tampered");
+        chain.blocks[2].prev = [0u8; 32];
+        chain.blocks[2].update_hash();
+        let report = chain.integrity_report();
+        assert!(report.contains("errors: 2"));
+        assert!(report.contains("#1: block #1 has invalid hash"));
+        assert!(report.contains("#2: block #2 doesn't point to previous block"));
+
+        // the block being mined isn't checked
+        assert!(chain.try_add());
+        chain.status = false;
+        let report = chain.integrity_report();
+        assert!(report.contains("status: mining #3"));
+        assert!(report.contains("errors: 2"));
+
+        let report = Chain::new().integrity_report();
+        assert!(report.contains("genesis: none"));
+        assert!(report.contains("tip: none"));
+        assert!(report.contains("difficulty: unknown"));
+    }
+
+    #[test]
+    fn test_queue_ids_follow_chain() {
+        let full = Chain::test_chain(3);
+        let mut chain = Chain::test_chain(1);
+        chain.add_queue(Block::new(0, String::from("Queued")));
+        assert_eq!(chain.queue[0].id, 1);
+
+        // a block appended ahead of the queue takes the id the queued block was given
+        chain.append_block(full.blocks[1].clone()).unwrap();
+        assert_eq!(chain.queue[0].id, 2);
+        chain.assert_invariants();
+
+        assert!(chain.try_add());
+        assert_eq!(chain.blocks[2].id, 2);
+        assert_eq!(chain.blocks[2].data, "Queued");
+        assert_eq!(chain.blocks[2].prev, chain.blocks[1].hash);
+        chain.blocks[2].update_hash();
+        chain.assert_invariants();
+        assert_eq!(chain.have_errors(), None);
+    }
+
+    #[test]
+    fn test_total_work() {
+        let mut chain = Chain::with_genesis("test-net");
+        assert_eq!(chain.total_work(), 0);
+        chain.add_queue(Block::new(0, String::from("First")));
+        assert!(chain.try_add());
+        chain.blocks[1].hash = [0xff; 32];
+        chain.blocks[1].hash[31] = 0xf0;
+        chain.blocks[1].hash[0] = 0x01;
+        assert_eq!(chain.total_work(), 16);
+        chain.difficulty_mode = DifficultyMode::Prefix;
+        assert_eq!(chain.total_work(), 128);
+
+        // the tip being mined isn't counted
+        chain.status = false;
+        assert_eq!(chain.total_work(), 0);
+
+        chain.status = true;
+        chain.blocks[1].hash = [0; 32];
+        chain.blocks.push(chain.blocks[1].clone());
+        assert_eq!(chain.total_work(), u128::MAX);
+    }
+
+    #[test]
+    fn test_swap_remote() {
+        let mut chain = Chain::test_chain(3);
+        chain.difficulty = String::from("0");
+        chain.add_queue(Block::new(0, String::from("Queued")));
+        let original = chain.clone();
+
+        // a failed swap leaves the chain intact
+        let mut invalid = Chain::test_chain(5);
+        invalid.blocks[3].prev = [1; 32];
+        assert_eq!(chain.swap_remote(invalid).unwrap_err(), ChainError::InvalidPrev(3));
+        assert_eq!(chain.export_headers(), original.export_headers());
+        assert_eq!(chain.queue.len(), 1);
+        assert_eq!(chain.difficulty, "0");
+
+        // the old chain is returned, and the difficulty is kept
+        let old = chain.swap_remote(Chain::test_chain(5)).unwrap();
+        assert_eq!(old.export_headers(), original.export_headers());
+        assert_eq!(old.queue.len(), 1);
+        assert_eq!(chain.blocks.len(), 5);
+        assert!(chain.queue.is_empty());
+        assert_eq!(chain.difficulty, "0");
+
+        // the tip being mined isn't checked
+        let mut busy = Chain::test_chain(5);
+        busy.add_queue(Block::new(0, String::from("Mining")));
+        assert!(busy.try_add());
+        busy.status = false;
+        chain.swap_remote(busy).unwrap();
+        assert!(!chain.status);
+    }
+
+    #[test]
+    fn test_find_all_errors() {
+        let mut chain = Chain::test_chain(6);
+        chain.blocks[1].data = String::from("Tampered");
+        chain.blocks[5].prev = [0u8; 32];
+        chain.blocks[5].update_hash();
+        assert_eq!(chain.find_all_errors(), [1, 5]);
+        assert_eq!(chain.have_errors(), Some(1));
+        assert_eq!(chain.have_errors_in(2..6), Some(5));
+
+        // the tip being mined isn't checked
+        chain.status = false;
+        assert_eq!(chain.find_all_errors(), [1]);
+        assert_eq!(chain.have_errors_in(2..6), None);
+
+        chain.blocks.truncate(1);
+        assert!(chain.find_all_errors().is_empty());
+        assert_eq!(chain.have_errors(), None);
+        assert!(Chain::new().find_all_errors().is_empty());
+    }
+
+    #[test]
+    fn test_next_template() {
+        let mut chain = Chain::test_chain(3);
+        assert!(chain.next_template().is_none());
+        chain.add_queue(Block::new(0, String::from("First")));
+        chain.add_queue(Block::new(0, String::from("Second")));
+        chain.queue[0].nonce = 42;
+
+        let template = chain.next_template().unwrap();
+        assert_eq!(template.id, 3);
+        assert_eq!(template.prev, chain.blocks[2].hash);
+        assert_eq!(template.data, chain.queue[0].data);
+        assert_eq!(template.nonce, 0);
+        assert_eq!(template.hash, [0u8; 32]);
+        assert_eq!(chain.queue.len(), 2);
+
+        // it's the block appended next
+        assert!(chain.try_add());
+        assert!(chain.blocks[3].preequals(&template));
+        chain.status = false;
+        assert!(chain.next_template().is_none());
+        chain.blocks[3].update_hash();
+        chain.status = true;
+        assert_eq!(chain.next_template().unwrap().prev, chain.blocks[3].hash);
+
+        assert_eq!(Chain::new().next_template().map(|block| block.id), None);
+        let mut empty = Chain::new();
+        empty.add_queue(Block::new(0, String::from("Genesis")));
+        assert_eq!(empty.next_template().unwrap().prev, [0u8; 32]);
+    }
+
+    #[test]
+    fn test_next_difficulty() {
+        // blocks with the given timestamps after the genesis one
+        let chain_with_times = |times: &[u64], difficulty: &str| {
+            let mut chain = Chain::with_genesis("test-net");
+            chain.difficulty = String::from(difficulty);
+            for &time in times {
+                chain.add_queue(Block::new_with_timestamp(0, String::new(), time));
+                assert!(chain.try_add());
+                chain.blocks.last_mut().unwrap().update_hash();
+            }
+            chain
+        };
+
+        // too fast: a byte is added at the far end
+        let fast: Vec<u64> = (0..5).map(|i| 1_000 + i * 2).collect();
+        let mut chain = chain_with_times(&fast, "ff");
+        assert_eq!(chain.next_difficulty(60), "00ff");
+        chain.difficulty_mode = DifficultyMode::Prefix;
+        assert_eq!(chain.next_difficulty(60), "ff00");
+
+        // too slow: a byte is removed, but not the last one
+        let slow: Vec<u64> = (0..5).map(|i| 1_000 + i * 3_600).collect();
+        let mut chain = chain_with_times(&slow, "00ff");
+        assert_eq!(chain.next_difficulty(60), "ff");
+        chain.difficulty_mode = DifficultyMode::Prefix;
+        assert_eq!(chain.next_difficulty(60), "00");
+        assert_eq!(chain_with_times(&slow, "00").next_difficulty(60), "00");
+
+        // close enough to the target
+        let on_target: Vec<u64> = (0..5).map(|i| 1_000 + i * 60).collect();
+        assert_eq!(chain_with_times(&on_target, "00").next_difficulty(60), "00");
+
+        // only the last blocks count
+        let mut times: Vec<u64> = (0..20).map(|i| 1_000 + i * 3_600).collect();
+        let last = *times.last().unwrap();
+        times.extend((1..=RETARGET_WINDOW as u64).map(|i| last + i));
+        assert_eq!(chain_with_times(&times, "00").next_difficulty(60), "0000");
+
+        // not enough timestamps: the genesis block has none, the tip being mined isn't counted
+        let mut chain = chain_with_times(&fast[..2], "00");
+        chain.status = false;
+        assert_eq!(chain.next_difficulty(60), "00");
+        assert_eq!(Chain::new().next_difficulty(60), "");
+    }
+
+    #[test]
+    fn test_hash_algo() {
+        let mut chains: Vec<Chain> = [HashAlgo::Sha256, HashAlgo::Sha3_256, HashAlgo::Blake3]
+            .into_iter()
+            .map(|algo| Chain::with_genesis_algo("test-net", algo))
+            .collect();
+        for chain in chains.iter_mut() {
+            chain.add_queue(Block::new_with_timestamp(0, String::from("Same data"), 1));
+            assert!(chain.try_add());
+            chain.blocks[1].update_hash();
+            assert_eq!(chain.have_errors(), None);
+        }
+        // the default keeps the hashes of SHA-256
+        assert_eq!(
+            chains[0].blocks[0].hash,
+            Chain::with_genesis("test-net").blocks[0].hash
+        );
+
+        // identical data gets different hashes
+        for (i, j) in [(0, 1), (0, 2), (1, 2)] {
+            assert_eq!(chains[i].blocks[1].data, chains[j].blocks[1].data);
+            assert_ne!(chains[i].blocks[1].hash, chains[j].blocks[1].hash);
+        }
+
+        // a block hashed with another function is invalid in the chain
+        let block = &mut chains[1].blocks[1];
+        block.hash_algo = HashAlgo::Sha256;
+        block.update_hash();
+        assert!(block.validate_hash());
+        assert_eq!(chains[1].have_errors(), Some(1));
+        let mut sha3 = chains.remove(1);
+        assert!(sha3.swap_remote(chains.remove(0)).is_err());
+    }
+
+    #[test]
+    fn test_prune_older_than() {
+        let now = 1_700_000_000;
+        let mut chain = Chain::with_genesis("test-net");
+        for (i, time) in [now - 7200, now - 5000, now - 4000, now - 60, now]
+            .iter()
+            .enumerate()
+        {
+            chain.add_queue(Block::new_with_timestamp(
+                0,
+                format!("Block {}", i + 1),
+                *time,
+            ));
+            chain
+                .queue
+                .back_mut()
+                .unwrap()
+                .add_transaction(String::from("Extra"));
+            assert!(chain.try_add());
+            chain.blocks.last_mut().unwrap().update_hash();
+        }
+        let headers = chain.export_headers();
+
+        // old bodies are pruned, headers remain and the chain validates
+        assert_eq!(chain.prune_older_than(now - 3600), 3);
+        for block in &chain.blocks[1..4] {
+            assert!(block.pruned);
+            assert!(block.data.is_empty() && block.transactions.is_empty());
+        }
+        assert_eq!(chain.blocks[4].data, "Block 4");
+        assert_eq!(chain.blocks[0].data, "test-net");
+        assert_eq!(chain.export_headers(), headers);
+        assert_eq!(chain.have_errors(), None);
+        assert_eq!(chain.prune_older_than(now - 3600), 0);
+
+        // but only here: hashes of pruned blocks from elsewhere can't be checked
+        assert_eq!(
+            chain.verify_against_headers(&headers),
+            Err(ChainError::PrunedBlock(1))
+        );
+        assert_eq!(
+            chain.verify_with_policy(&Default::default()),
+            Err(ChainError::PrunedBlock(1))
+        );
+        let mut other = Chain::with_genesis("test-net");
+        assert_eq!(
+            other.swap_remote(chain.clone()).unwrap_err(),
+            ChainError::PrunedBlock(1)
+        );
+
+        // the last mined block is kept whole, so the pruned hashes are still covered
+        assert_eq!(chain.prune_older_than(now + 1), 1);
+        assert!(!chain.blocks[5].pruned);
+        let mut tip_pruned = chain.clone();
+        tip_pruned.blocks[5].prune();
+        assert_eq!(tip_pruned.have_errors(), Some(5));
+
+        // a forged hash of a pruned block breaks the link of the next one
+        chain.blocks[2].hash[0] ^= 1;
+        assert_eq!(chain.find_all_errors(), [3]);
+    }
+
+    #[test]
+    fn test_forged_pruned_chain() {
+        // pruned blocks with zero hashes, which would count as infinite work, covered by a tip
+        let mut forged = Chain::with_genesis("test-net");
+        for id in 1..5 {
+            let mut block = Block::new_with_timestamp(id, String::new(), id);
+            block.prev = forged.blocks.last().unwrap().hash;
+            block.hash = [0u8; 32];
+            block.pruned = true;
+            forged.blocks.push(block);
+        }
+        let mut tip = Block::new_with_timestamp(5, String::from("Tip"), 5);
+        tip.prev = [0u8; 32];
+        tip.update_hash();
+        forged.blocks.push(tip);
+
+        // pruned blocks add no work
+        assert_eq!(
+            forged.total_work(),
+            1 << forged.blocks[5].trailing_zero_bits()
+        );
+
+        let mut chain = Chain::test_chain(3);
+        assert_eq!(
+            chain.swap_remote(forged.clone()).unwrap_err(),
+            ChainError::PrunedBlock(1)
+        );
+        assert_eq!(chain.blocks.len(), 3);
+
+        let name = format!("rustychain-forged-{}.json", rand::random::<u64>());
+        let path = std::env::temp_dir().join(name);
+        forged.save_to_file(&path, DbFormat::Json).unwrap();
+        assert_eq!(
+            Chain::load_from_file(&path).unwrap_err(),
+            ChainError::PrunedBlock(1)
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
 }
\ No newline at end of file
@@ -0,0 +1,77 @@
+#[cfg(test)]
+mod command_tests {
+    use rustychain::{Block, Command, CommandError};
+
+    #[test]
+    fn test_help() {
+        let help = Command::help();
+        for usage in [
+            "help",
+            "=<transaction>",
+            "ls queue",
+            "dump --report",
+            "dump --dot",
+            "compare <peer>",
+            "cancel-sync",
+            "inject <hex>",
+            "remine <bits>",
+            "exit",
+        ] {
+            assert!(help.contains(usage), "no {:?} in {}", usage, help);
+        }
+        assert!(matches!("help".parse(), Ok(Command::Help)));
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(matches!(" ls ".parse(), Ok(Command::Ls)));
+        assert!(matches!("ls  queue".parse(), Ok(Command::LsQueue)));
+        assert!(matches!("dump --dot".parse(), Ok(Command::DumpDot)));
+        assert!(matches!("remine 12".parse(), Ok(Command::Remine(12))));
+        assert!(matches!(
+            "compare peer-id".parse(),
+            Ok(Command::Compare(peer)) if peer == "peer-id"
+        ));
+        // the data of a transaction is kept as is
+        assert!(matches!(
+            "= Some data ".parse(),
+            Ok(Command::Transaction(data)) if data == " Some data "
+        ));
+        let block = Block::genesis("test-net");
+        assert!(matches!(
+            format!("inject {}", block.to_hex()).parse(),
+            Ok(Command::Inject(injected)) if injected.equals(&block)
+        ));
+    }
+
+    #[test]
+    fn test_unknown_command() {
+        let e = "mine".parse::<Command>().unwrap_err();
+        assert_eq!(e, CommandError::Unknown(String::from("mine")));
+        assert_eq!(
+            e.to_string(),
+            "unknown command mine, type help to list commands"
+        );
+
+        for line in [
+            "=",
+            "ls all",
+            "dump",
+            "compare",
+            "remine many",
+            "inject zz",
+            "exit now",
+        ] {
+            assert!(
+                matches!(
+                    line.parse::<Command>(),
+                    Err(CommandError::InvalidArgument { .. })
+                ),
+                "{:?} is parsed",
+                line
+            );
+        }
+        let e = "remine many".parse::<Command>().unwrap_err();
+        assert!(e.to_string().starts_with("invalid arguments of remine: "));
+    }
+}
@@ -0,0 +1,306 @@
+#[cfg(test)]
+mod consensus_tests {
+    use rustychain::{Block, Chain, HashAlgo, Message, RejectKind, ValidationPolicy};
+
+    /// Mines the last block of the chain like a node would.
+    fn mine_tip(chain: &mut Chain) -> Block {
+        let last = chain.blocks.last_mut().unwrap();
+        last.update_hash();
+        chain.status = true;
+        last.clone()
+    }
+
+    #[test]
+    fn test_new_block_and_chain_request() {
+        let mut chain = Chain::with_genesis("test-net");
+        let block = Block::new(0, String::from("First"));
+        assert!(chain
+            .apply_message(&Message::NewBlock(block.clone()))
+            .is_empty());
+        assert_eq!(chain.queue.len(), 1);
+
+        // the wrapped message is applied as well
+        let msg = Message::Envelope(String::from("peer"), Box::new(Message::NewBlock(block)));
+        assert!(chain.apply_message(&msg).is_empty());
+        assert_eq!(chain.queue.len(), 2);
+
+        match &chain.apply_message(&Message::ChainRequest)[..] {
+            [Message::ChainResponce(remote)] => assert_eq!(remote.queue.len(), 2),
+            out => panic!("Expected ChainResponce, but got: {:?}", out),
+        }
+    }
+
+    #[test]
+    fn test_mined_block() {
+        let mut local = Chain::with_genesis("test-net");
+        let mut remote = local.clone();
+        let first = Block::new(0, String::from("First"));
+        for chain in [&mut local, &mut remote] {
+            chain.apply_message(&Message::NewBlock(first.clone()));
+            assert!(chain.try_add());
+            chain.status = false;
+        }
+
+        // the remote node mines the block first
+        let mined = mine_tip(&mut remote);
+        assert!(local
+            .apply_message(&Message::MinedBlock(mined.clone()))
+            .is_empty());
+        assert!(local.status);
+        assert_eq!(local.blocks[1].hash, mined.hash);
+
+        // a block with the wrong hash is ignored
+        let mut forged = mined.clone();
+        forged.nonce += 1;
+        assert!(local.apply_message(&Message::MinedBlock(forged)).is_empty());
+        assert_eq!(local.blocks[1].hash, mined.hash);
+
+        // a block ahead of the chain makes it request the chain
+        remote.apply_message(&Message::NewBlock(Block::new(0, String::from("Second"))));
+        assert!(remote.try_add());
+        let ahead = mine_tip(&mut remote);
+        let out = local.apply_message(&Message::MinedBlock(ahead));
+        assert!(matches!(&out[..], [Message::ChainRequest]));
+
+        // the empty chain requests the chain too
+        let out = Chain::new().apply_message(&Message::MinedBlock(mined));
+        assert!(matches!(&out[..], [Message::ChainRequest]));
+
+        // the requested chain is taken as it's longer
+        let out = local.apply_message(&Message::ChainResponce(remote.clone()));
+        assert!(out.is_empty());
+        assert_eq!(local.export_headers(), remote.export_headers());
+    }
+
+    #[test]
+    fn test_replace_with_lower_hash() {
+        let mut chain = Chain::with_genesis("test-net");
+        chain.apply_message(&Message::NewBlock(Block::new(0, String::from("First"))));
+        assert!(chain.try_add());
+        let tip = mine_tip(&mut chain);
+
+        // the same block with another nonce
+        let mut other = tip.clone();
+        other.nonce += 1;
+        other.update_hash();
+        let (lower, higher) = if other.hash < tip.hash {
+            (other, tip)
+        } else {
+            (tip, other)
+        };
+
+        chain.blocks[1] = lower.clone();
+        chain.apply_message(&Message::MinedBlock(higher.clone()));
+        assert_eq!(chain.blocks[1].hash, lower.hash);
+
+        chain.blocks[1] = higher;
+        chain.apply_message(&Message::MinedBlock(lower.clone()));
+        assert_eq!(chain.blocks[1].hash, lower.hash);
+    }
+
+    #[test]
+    fn test_invalid_chain_and_inject() {
+        let mut chain = Chain::with_genesis("test-net");
+        let mut remote = chain.clone();
+        remote.apply_message(&Message::NewBlock(Block::new(0, String::from("First"))));
+        assert!(remote.try_add());
+        let mined = mine_tip(&mut remote);
+
+        let mut invalid = remote.clone();
+        invalid.blocks[1].prev = [1; 32];
+        match &chain.apply_message(&Message::ChainResponce(invalid))[..] {
+            [Message::Reject { what, .. }] => assert_eq!(*what, RejectKind::InvalidChain),
+            out => panic!("Expected Reject, but got: {:?}", out),
+        }
+        assert_eq!(chain.blocks.len(), 1);
+
+        // pruned blocks from elsewhere can't be checked
+        let mut pruned = remote.clone();
+        pruned.apply_message(&Message::NewBlock(Block::new(0, String::from("Second"))));
+        assert!(pruned.try_add());
+        mine_tip(&mut pruned);
+        pruned.blocks[1].prune();
+        pruned.blocks[1].hash = [0u8; 32];
+        pruned.blocks[2].prev = [0u8; 32];
+        mine_tip(&mut pruned);
+        match &chain.apply_message(&Message::ChainResponce(pruned))[..] {
+            [Message::Reject { what, .. }] => assert_eq!(*what, RejectKind::InvalidChain),
+            out => panic!("Expected Reject, but got: {:?}", out),
+        }
+        assert_eq!(chain.blocks.len(), 1);
+
+        // the injected block is appended and announced
+        match &chain.apply_message(&Message::InjectBlock(mined.clone()))[..] {
+            [Message::MinedBlock(block)] => assert_eq!(block.hash, mined.hash),
+            out => panic!("Expected MinedBlock, but got: {:?}", out),
+        }
+        assert_eq!(chain.blocks.len(), 2);
+
+        // but not twice
+        assert!(chain.apply_message(&Message::InjectBlock(mined)).is_empty());
+        assert_eq!(chain.blocks.len(), 2);
+    }
+
+    #[test]
+    fn test_chain_difficulty() {
+        let mut chain = Chain::with_genesis("test-net");
+        chain.apply_message(&Message::NewBlock(Block::new(0, String::from("First"))));
+        assert!(chain.try_add());
+        chain.status = false;
+
+        // the block meets a lower difficulty, but not the one of the chain
+        let mut low = chain.blocks[1].clone();
+        while !low.string_hash().ends_with('0') || low.string_hash().ends_with("00") {
+            low.nonce += 1;
+            low.update_hash();
+        }
+        chain.difficulty = String::from("00");
+        match &chain.apply_message(&Message::MinedBlock(low.clone()))[..] {
+            [Message::Reject { what, detail }] => {
+                assert_eq!(*what, RejectKind::LowDifficulty);
+                assert!(detail.contains("difficulty 00"));
+            }
+            out => panic!("Expected Reject, but got: {:?}", out),
+        }
+        assert!(!chain.status);
+
+        chain.difficulty = String::from("0");
+        assert!(chain
+            .apply_message(&Message::MinedBlock(low.clone()))
+            .is_empty());
+        assert!(chain.status);
+        assert_eq!(chain.blocks[1].hash, low.hash);
+
+        // the recorded difficulty is kept when a chain without one is taken
+        let mut remote = chain.clone();
+        remote.difficulty = String::new();
+        remote.apply_message(&Message::NewBlock(Block::new(0, String::from("Second"))));
+        assert!(remote.try_add());
+        mine_tip(&mut remote);
+        chain.apply_message(&Message::ChainResponce(remote));
+        assert_eq!(chain.blocks.len(), 3);
+        assert_eq!(chain.difficulty, "0");
+    }
+
+    #[test]
+    fn test_block_request() {
+        let mut remote = Chain::test_chain(3);
+        let mut chain = Chain::test_chain(1);
+
+        match &remote.apply_message(&Message::BlockRequest(1))[..] {
+            [Message::BlockResponse(Some(block))] => assert_eq!(block.data, "Block 1"),
+            out => panic!("Expected BlockResponse, but got: {:?}", out),
+        }
+        assert!(matches!(
+            &remote.apply_message(&Message::BlockRequest(3))[..],
+            [Message::BlockResponse(None)]
+        ));
+
+        // only a block extending the tip is appended
+        let second = remote.get_block(2).cloned();
+        assert!(chain
+            .apply_message(&Message::BlockResponse(second.clone()))
+            .is_empty());
+        assert_eq!(chain.blocks.len(), 1);
+        let first = remote.get_block(1).cloned();
+        assert!(chain
+            .apply_message(&Message::BlockResponse(first))
+            .is_empty());
+        let mut forged = second.clone().unwrap();
+        forged.nonce += 1;
+        assert!(chain
+            .apply_message(&Message::BlockResponse(Some(forged)))
+            .is_empty());
+        assert_eq!(chain.blocks.len(), 2);
+        assert!(chain
+            .apply_message(&Message::BlockResponse(None))
+            .is_empty());
+
+        chain.difficulty = String::from("zz");
+        match &chain.apply_message(&Message::BlockResponse(second.clone()))[..] {
+            [Message::Reject { what, .. }] => assert_eq!(*what, RejectKind::LowDifficulty),
+            out => panic!("Expected Reject, but got: {:?}", out),
+        }
+        chain.difficulty = String::new();
+        assert!(chain
+            .apply_message(&Message::BlockResponse(second))
+            .is_empty());
+        assert_eq!(chain.blocks.len(), 3);
+        assert_eq!(chain.have_errors(), None);
+    }
+
+    #[test]
+    fn test_chain_with_more_work() {
+        let mut chain = Chain::test_chain(3);
+        let longer = Chain::test_chain(4);
+
+        // a shorter chain with more work is taken
+        let mut remote = Chain::test_chain(2);
+        let bits = 128 - longer.total_work().leading_zeros() + 1;
+        remote.remine_tip(bits).unwrap();
+        assert!(remote.total_work() > longer.total_work());
+        assert!(chain
+            .apply_message(&Message::ChainResponce(remote.clone()))
+            .is_empty());
+        assert_eq!(chain.export_headers(), remote.export_headers());
+
+        // a longer chain with less work isn't
+        chain.apply_message(&Message::ChainResponce(longer));
+        assert_eq!(chain.export_headers(), remote.export_headers());
+    }
+
+    #[test]
+    fn test_chain_with_other_hash_algo() {
+        let mut chain = Chain::test_chain(2);
+        let mut remote = Chain::with_genesis_algo("test-chain", HashAlgo::Sha3_256);
+        for id in 1..4 {
+            remote.apply_message(&Message::NewBlock(Block::new(0, format!("Block {}", id))));
+            assert!(remote.try_add());
+            mine_tip(&mut remote);
+        }
+        assert_eq!(remote.have_errors(), None);
+
+        // blocks are checked with the local hash function
+        match &chain.apply_message(&Message::ChainResponce(remote))[..] {
+            [Message::Reject { what, .. }] => assert_eq!(*what, RejectKind::InvalidChain),
+            out => panic!("Expected Reject, but got: {:?}", out),
+        }
+        assert_eq!(chain.blocks.len(), 2);
+        assert_eq!(chain.have_errors(), None);
+    }
+
+    #[test]
+    fn test_chain_with_policy() {
+        let mut chain = Chain::test_chain(2);
+        let remote = Chain::test_chain(4);
+
+        // a chain failing the policy isn't taken
+        let policy = ValidationPolicy {
+            genesis: Some([1u8; 32]),
+            ..Default::default()
+        };
+        let msg = Message::ChainResponce(remote.clone());
+        match &chain.apply_message_with_policy(&msg, &policy)[..] {
+            [Message::Reject { what, .. }] => assert_eq!(*what, RejectKind::InvalidChain),
+            out => panic!("Expected Reject, but got: {:?}", out),
+        }
+        let policy = ValidationPolicy {
+            min_difficulty_bits: Some(64),
+            ..Default::default()
+        };
+        match &chain.apply_message_with_policy(&msg, &policy)[..] {
+            [Message::Reject { what, .. }] => assert_eq!(*what, RejectKind::LowDifficulty),
+            out => panic!("Expected Reject, but got: {:?}", out),
+        }
+        assert_eq!(chain.blocks.len(), 2);
+
+        // wrapped messages are checked with the policy too
+        let policy = ValidationPolicy {
+            genesis: Some(chain.blocks[0].hash),
+            ..Default::default()
+        };
+        let msg = Message::Envelope(String::from("peer"), Box::new(msg));
+        assert!(chain.apply_message_with_policy(&msg, &policy).is_empty());
+        assert_eq!(chain.export_headers(), remote.export_headers());
+    }
+}
@@ -0,0 +1,41 @@
+#[cfg(test)]
+mod difficulty_tests {
+
+    use rustychain::{describe_difficulty, expected_attempts, parse_difficulty};
+
+    #[test]
+    fn test_parse_difficulty() {
+        assert_eq!(parse_difficulty("00"), Ok(String::from("00")));
+        assert_eq!(parse_difficulty("DEADBEEF"), Ok(String::from("deadbeef")));
+        assert_eq!(parse_difficulty(&"f".repeat(64)), Ok("f".repeat(64)));
+    }
+
+    #[test]
+    fn test_parse_difficulty_invalid() {
+        assert!(parse_difficulty("0x00").is_err());
+        assert!(parse_difficulty("zz").is_err());
+        assert!(parse_difficulty("00 ").is_err());
+        assert!(parse_difficulty("").is_err());
+        assert!(parse_difficulty(&"0".repeat(65)).is_err());
+    }
+
+    #[test]
+    fn test_parse_difficulty_odd() {
+        let err = parse_difficulty("0").unwrap_err();
+        assert!(err.contains("even number"));
+        assert!(err.contains("1 nibbles (4 bits)"));
+        assert!(parse_difficulty("000").is_err());
+        assert!(parse_difficulty(&"f".repeat(63)).is_err());
+        assert_eq!(parse_difficulty("00"), Ok(String::from("00")));
+    }
+
+    #[test]
+    fn test_attempts() {
+        assert_eq!(expected_attempts("0"), 16.0);
+        assert_eq!(expected_attempts("00"), 256.0);
+        assert_eq!(expected_attempts("0000"), 65536.0);
+        assert_eq!(describe_difficulty("00"), "~256 attempts");
+        assert_eq!(describe_difficulty("fee1dead"), "~4294967296 attempts");
+        assert_eq!(describe_difficulty(&"0".repeat(64)), "~1.2e77 attempts");
+    }
+}
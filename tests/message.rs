@@ -0,0 +1,172 @@
+#[cfg(test)]
+mod message_tests {
+
+    use rustychain::{encode_json, Block, Chain, Message, MessageError};
+    use sha2::{Digest, Sha256};
+
+    #[test]
+    fn test_canonical_id() {
+        let block = Block::with_nonce(1, String::from("Data"), [0u8; 32], 42);
+        let msg = Message::MinedBlock(block.clone());
+
+        // same block serialized in different ways
+        let compact = serde_json::to_string(&msg).unwrap();
+        let pretty = serde_json::to_string_pretty(&msg).unwrap();
+        assert_ne!(compact, pretty);
+        assert_eq!(
+            Message::gossip_id(compact.as_bytes()),
+            Message::gossip_id(pretty.as_bytes())
+        );
+        assert_eq!(
+            Message::gossip_id(compact.as_bytes()),
+            hex::encode(msg.canonical_id())
+        );
+
+        // other block or other variant
+        let other = Block::with_nonce(1, String::from("Data"), [0u8; 32], 43);
+        assert_ne!(
+            msg.canonical_id(),
+            Message::MinedBlock(other).canonical_id()
+        );
+        assert_ne!(msg.canonical_id(), Message::NewBlock(block).canonical_id());
+        assert_ne!(
+            Message::gossip_id(b"garbage"),
+            Message::gossip_id(b"other garbage")
+        );
+
+        // other messages aren't decoded, their raw bytes are hashed with the kind
+        let chain = serde_json::to_vec(&Message::ChainResponce(Chain::test_chain(3))).unwrap();
+        assert_eq!(Message::gossip_id(&chain), Message::gossip_id(&chain));
+        assert_ne!(
+            Message::gossip_id(&chain),
+            hex::encode(Sha256::digest(&chain))
+        );
+    }
+
+    #[test]
+    fn test_from_slice() {
+        let msg = Message::MinedBlock(Block::with_nonce(1, String::from("Data"), [0u8; 32], 42));
+        let json = serde_json::to_vec(&msg).unwrap();
+        assert!(matches!(
+            Message::from_slice(&json),
+            Ok(Message::MinedBlock(block)) if block.nonce == 42
+        ));
+
+        // truncated
+        for len in [0, 1, json.len() / 2, json.len() - 1] {
+            assert!(matches!(
+                Message::from_slice(&json[..len]),
+                Err(MessageError::Decode(_))
+            ));
+        }
+        // invalid utf8
+        assert_eq!(
+            Message::from_slice(b"{\"NewBlock\":\xff\xfe}").unwrap_err(),
+            MessageError::Utf8
+        );
+        // unknown variant and wrong types
+        for bad in [
+            &b"\"Unknown\""[..],
+            b"{\"MinedBlock\":42}",
+            b"{\"NewBlock\":{\"id\":-1}}",
+            b"[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[",
+        ] {
+            assert!(matches!(
+                Message::from_slice(bad),
+                Err(MessageError::Decode(_))
+            ));
+        }
+        // huge
+        let huge = vec![b' '; Message::MAX_SIZE + 1];
+        assert_eq!(
+            Message::from_slice(&huge).unwrap_err(),
+            MessageError::TooLarge(Message::MAX_SIZE + 1)
+        );
+    }
+
+    #[test]
+    fn test_chain_limit() {
+        let mut chain = Chain::with_genesis("test-net");
+        for i in 0..100 {
+            chain.add_queue(Block::new(0, format!("Block {i}")));
+        }
+        let data = serde_json::to_vec(&Message::ChainResponce(chain)).unwrap();
+        let limit = data.len() - 1;
+        assert_eq!(
+            Message::from_slice_with_chain_limit(&data, limit).unwrap_err(),
+            MessageError::TooLarge(data.len())
+        );
+        assert!(Message::from_slice_with_chain_limit(&data, data.len()).is_ok());
+
+        // the oversized chain isn't decoded at all, so its content doesn't matter
+        let garbage = format!(r#"{{"ChainResponce": [{}0]}}"#, "0, ".repeat(limit));
+        assert_eq!(
+            Message::from_slice_with_chain_limit(garbage.as_bytes(), limit).unwrap_err(),
+            MessageError::TooLarge(garbage.len())
+        );
+        assert!(matches!(
+            Message::from_slice(garbage.as_bytes()),
+            Err(MessageError::Decode(_))
+        ));
+
+        // other messages are limited by `Message::MAX_SIZE` only
+        let block = Block::new(0, "x".repeat(limit));
+        let data = serde_json::to_vec(&Message::NewBlock(block)).unwrap();
+        assert!(Message::from_slice_with_chain_limit(&data, limit).is_ok());
+        assert_eq!(Message::peek_kind(&data).unwrap(), "NewBlock");
+    }
+
+    /// A value whose serialization always fails, like a map with non-string keys.
+    struct Unencodable;
+
+    impl serde::Serialize for Unencodable {
+        fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom("crafted failure"))
+        }
+    }
+
+    #[test]
+    fn test_encode_failure() {
+        // the hot loop skips such values instead of panicking
+        let err = encode_json(&Unencodable).unwrap_err();
+        assert!(matches!(err, MessageError::Encode(ref e) if e.contains("crafted failure")));
+        assert!(encode_json(&[Unencodable]).is_err());
+
+        // peers would drop a message that large, so it isn't sent
+        let block = Block::new(0, "a".repeat(Message::MAX_SIZE));
+        let err = Message::NewBlock(block).to_bytes().unwrap_err();
+        assert!(matches!(err, MessageError::TooLarge(len) if len > Message::MAX_SIZE));
+
+        let block = Block::new(0, "Some data".to_owned());
+        let data = Message::NewBlock(block.clone()).to_bytes().unwrap();
+        assert!(matches!(Message::from_slice(&data), Ok(Message::NewBlock(b)) if b.equals(&block)));
+    }
+
+    #[test]
+    fn test_block_messages() {
+        let block = Block::genesis("test-net");
+        let json = encode_json(&Message::BlockRequest(7)).unwrap();
+        assert_eq!(json, b"{\"BlockRequest\":7}");
+        assert!(matches!(
+            Message::from_slice(&json),
+            Ok(Message::BlockRequest(7))
+        ));
+
+        let json = encode_json(&Message::BlockResponse(Some(block.clone()))).unwrap();
+        assert!(matches!(
+            Message::from_slice(&json),
+            Ok(Message::BlockResponse(Some(decoded))) if decoded.equals(&block)
+        ));
+        let json = encode_json(&Message::BlockResponse(None)).unwrap();
+        assert_eq!(json, b"{\"BlockResponse\":null}");
+        assert!(matches!(
+            Message::from_slice(&json),
+            Ok(Message::BlockResponse(None))
+        ));
+        assert_eq!(Message::BlockRequest(7).to_string(), "BlockRequest(#7)");
+        assert_eq!(
+            Message::BlockResponse(None).to_string(),
+            "BlockResponse(none)"
+        );
+    }
+}
@@ -0,0 +1,181 @@
+#[cfg(test)]
+mod mining_tests {
+    use std::sync::atomic::AtomicBool;
+
+    use rustychain::{
+        mine_blocking, mining_threads, set_thread_nice, thread_nonces, Block, Chain,
+        DifficultyMode, Message, Node, NodeConfig,
+    };
+    use tokio::sync::{broadcast, mpsc};
+
+    #[test]
+    fn test_mining_threads() {
+        assert_eq!(mining_threads(Some(3)), 3);
+        assert_eq!(mining_threads(Some(0)), 1);
+        assert_eq!(mining_threads(Some(usize::MAX)), 1);
+        assert!(mining_threads(None) >= 1);
+    }
+
+    #[test]
+    fn test_fallback_to_current_thread() {
+        let block = Block::new(0, String::from("Some data"));
+        // no threads are spawned, so the block is mined on the current thread
+        let (hash, nonce, attempts) = mine_blocking(
+            &block,
+            "0",
+            DifficultyMode::Suffix,
+            0,
+            None,
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+        assert_eq!(block.hash_with_nonce(nonce), hash);
+        assert!(hex::encode(hash).ends_with('0'));
+        // every nonce is tried from 0
+        assert_eq!(attempts, nonce + 1);
+
+        // stopped search finds nothing
+        assert_eq!(
+            mine_blocking(
+                &block,
+                "0",
+                DifficultyMode::Suffix,
+                2,
+                None,
+                &AtomicBool::new(true)
+            ),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_parallel_node() {
+        let (tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(10);
+        let (tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        let node = Node::new(
+            Chain::new(),
+            tx_node,
+            rx_node,
+            rx_cancel,
+            String::from("00"),
+        );
+        let mut node = node.with_config(NodeConfig {
+            mining_threads: Some(2),
+            ..Default::default()
+        });
+        let handle = tokio::task::spawn(async move { node.run().await });
+
+        for i in 0..3 {
+            let block = Block::new(0, format!("Block {}", i));
+            tx_test.send(Message::NewBlock(block)).await.unwrap();
+            match rx_test.recv().await.unwrap() {
+                Message::MinedBlock(block) => {
+                    assert_eq!(block.id, i);
+                    assert!(block.validate_hash());
+                    assert!(block.string_hash().ends_with("00"));
+                    assert!(block.mining_attempts.is_some_and(|attempts| attempts > 0));
+                }
+                msg => panic!("Expected MinedBlock, but got: {:?}", msg),
+            }
+        }
+
+        tx_cancel.send(()).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[test]
+    fn test_thread_nonces() {
+        let block = Block::new(0, String::from("Some data"));
+        let (hash, nonce, attempts) = mine_blocking(
+            &block,
+            "0",
+            DifficultyMode::Suffix,
+            4,
+            None,
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+        assert_eq!(block.hash_with_nonce(nonce), hash);
+
+        // the nonce belongs to exactly one thread
+        let owners = (0..4)
+            .filter(|&thread| {
+                thread_nonces(thread, 4)
+                    .take_while(|&n| n <= nonce)
+                    .any(|n| n == nonce)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(owners, [nonce as usize % 4]);
+        // the attempts of other threads are counted too
+        assert!(attempts > nonce / 4);
+
+        // the thread tried every nonce of its class before the found one
+        for n in thread_nonces(owners[0], 4).take_while(|&n| n < nonce) {
+            assert!(!hex::encode(block.hash_with_nonce(n)).ends_with('0'));
+        }
+
+        // the classes cover the nonce space without overlaps
+        let mut nonces = (0..4)
+            .flat_map(|thread| thread_nonces(thread, 4).take(16))
+            .collect::<Vec<_>>();
+        nonces.sort_unstable();
+        assert_eq!(nonces, (0..64).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_nice_mining() {
+        let block = Block::new(0, String::from("Some data"));
+        let (hash, nonce, _) = mine_blocking(
+            &block,
+            "00",
+            DifficultyMode::Suffix,
+            2,
+            Some(19),
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+        assert_eq!(block.hash_with_nonce(nonce), hash);
+        assert!(hex::encode(hash).ends_with("00"));
+
+        // the nice level is set on the mining thread only
+        let nice = std::thread::spawn(|| set_thread_nice(5)).join().unwrap();
+        assert_eq!(nice.is_ok(), cfg!(target_os = "linux"));
+    }
+
+    #[tokio::test]
+    async fn test_nice_node() {
+        let (tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(10);
+        let (tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        let node = Node::new(
+            Chain::new(),
+            tx_node,
+            rx_node,
+            rx_cancel,
+            String::from("00"),
+        );
+        let mut node = node.with_config(NodeConfig {
+            mining_nice: Some(19),
+            ..Default::default()
+        });
+        let handle = tokio::task::spawn(async move { node.run().await });
+
+        tx_test
+            .send(Message::NewBlock(Block::new(0, String::from("Block"))))
+            .await
+            .unwrap();
+        match rx_test.recv().await.unwrap() {
+            Message::MinedBlock(block) => {
+                assert!(block.validate_hash());
+                assert!(block.string_hash().ends_with("00"));
+            }
+            msg => panic!("Expected MinedBlock, but got: {:?}", msg),
+        }
+
+        tx_cancel.send(()).unwrap();
+        handle.await.unwrap();
+    }
+}
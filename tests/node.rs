@@ -1,6 +1,16 @@
 #[cfg(test)]
 mod node_tests {
-    use rustychain::{nonce_worker, Block, Chain, Message, Node};
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use rustychain::{
+        archive_path, calibrate_difficulty, nonce_worker, Block, BlockHook, Chain, DbFormat,
+        DifficultyMode, Direction, Health, MemoryLimits, Message, MessageLog, MessageStats,
+        MiningJob, Node, NodeConfig, NodeDebug, NodeEvent, RejectKind, StallPolicy,
+        TransactionValidator, ValidationPolicy,
+    };
     use tokio::sync::{broadcast, mpsc};
 
     #[tokio::test]
@@ -63,19 +73,2003 @@ mod node_tests {
         let (result_tx, mut result_rx) = mpsc::channel(10);
         let (cancel_tx, cancel_rx) = broadcast::channel(1);
 
-        let handle = tokio::task::spawn(nonce_worker(rx, result_tx, cancel_rx));
+        let handle = tokio::task::spawn(nonce_worker(
+            rx,
+            result_tx,
+            cancel_rx,
+            DifficultyMode::Suffix,
+        ));
 
         let mut block = Block::new(1, String::from("test"));
         let diff = String::from("0");
         tx.send((block.clone(), diff.clone())).await.unwrap();
 
-        let (hash, nonce) = result_rx.recv().await.unwrap();
+        let (hash, nonce, attempts) = result_rx.recv().await.unwrap();
         block.hash = hash;
         block.nonce = nonce;
         assert!(block.validate_hash());
         assert!(block.string_hash().ends_with(&diff));
+        assert!(attempts > 0);
 
         cancel_tx.send(()).unwrap();
         handle.await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_inject() {
+        let (tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(10);
+        let (tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        let diff = String::from("0");
+        let mut node = Node::new(Chain::new(), tx_node, rx_node, rx_cancel, diff.clone());
+
+        let handle = tokio::task::spawn(async move {
+            node.run().await;
+        });
+
+        // mine blocks by hand
+        let mut genesis = Block::new(0, String::from("Genesis"));
+        while {
+            genesis.nonce += 1;
+            genesis.update_hash();
+            !genesis.string_hash().ends_with(&diff)
+        } {}
+        let mut next = Block::new(1, String::from("Next"));
+        next.prev = genesis.hash;
+        while {
+            next.nonce += 1;
+            next.update_hash();
+            !next.string_hash().ends_with(&diff)
+        } {}
+
+        for block in [genesis, next] {
+            let block = Block::from_hex(&block.to_hex()).unwrap();
+            tx_test
+                .send(Message::InjectBlock(block.clone()))
+                .await
+                .unwrap();
+            let msg = rx_test.recv().await.unwrap();
+            if let Message::MinedBlock(res) = msg {
+                assert!(res.equals(&block));
+            } else {
+                panic!("Expected MinedBlock, but got: {:?}", msg);
+            }
+        }
+
+        tx_test.send(Message::ChainRequest).await.unwrap();
+        let msg = rx_test.recv().await.unwrap();
+        if let Message::ChainResponce(chain) = msg {
+            assert_eq!(chain.blocks.len(), 2);
+            assert_eq!(chain.have_errors(), None);
+        } else {
+            panic!("Expected ChainResponce, but got: {:?}", msg);
+        }
+
+        tx_cancel.send(()).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_shared_genesis() {
+        let diff = String::from("0");
+        let genesis = Block::genesis("test-net");
+        let mut chains = vec![];
+
+        for data in ["First", "Second", "Third"] {
+            let (tx_test, rx_node) = mpsc::channel::<Message>(10);
+            let (tx_node, mut rx_test) = mpsc::channel::<Message>(10);
+            let (tx_cancel, rx_cancel) = broadcast::channel(1);
+            let chain = Chain::with_genesis("test-net");
+            let mut node = Node::new(chain, tx_node, rx_node, rx_cancel, diff.clone());
+
+            let handle = tokio::task::spawn(async move {
+                node.run().await;
+            });
+
+            tx_test
+                .send(Message::NewBlock(Block::new(0, String::from(data))))
+                .await
+                .unwrap();
+            let msg = rx_test.recv().await.unwrap();
+            if let Message::MinedBlock(res) = msg {
+                assert_eq!(res.id, 1);
+                assert_eq!(res.prev, genesis.hash);
+            } else {
+                panic!("Expected MinedBlock, but got: {:?}", msg);
+            }
+
+            tx_test.send(Message::ChainRequest).await.unwrap();
+            if let Message::ChainResponce(chain) = rx_test.recv().await.unwrap() {
+                chains.push(chain);
+            }
+
+            tx_cancel.send(()).unwrap();
+            handle.await.unwrap();
+        }
+
+        assert_eq!(chains.len(), 3);
+        for chain in chains {
+            assert_eq!(chain.have_errors(), None);
+            assert!(chain.blocks[0].equals(&genesis));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_calibrate_difficulty() {
+        let diff = calibrate_difficulty(Duration::from_millis(100)).await;
+        assert!(!diff.is_empty());
+        assert!(diff.len() <= 8);
+        assert_eq!(diff.len() % 2, 0);
+        assert!(diff.chars().all(|c| c == '0'));
+    }
+
+    #[test]
+    fn test_mining_job() {
+        let mut job = MiningJob::default();
+        assert_eq!(job.in_flight(), None);
+
+        assert!(job.start(1));
+        assert!(!job.start(1)); // coalesced
+        assert!(!job.start(2)); // double dispatch
+        assert_eq!(job.in_flight(), Some(1));
+
+        assert_eq!(job.finish(), Some(1));
+        assert_eq!(job.finish(), None);
+        assert!(job.start(2));
+        assert_eq!(job.in_flight(), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_drain_pending() {
+        let (tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (tx_node, _rx_test) = mpsc::channel::<Message>(10);
+        let (tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        // too hard to mine in test
+        let diff = String::from("ffffffffffffffff");
+        let mut node = Node::new(Chain::new(), tx_node, rx_node, rx_cancel, diff);
+
+        let handle = tokio::task::spawn(async move { node.run().await });
+
+        for data in ["First", "Second", "Third"] {
+            tx_test
+                .send(Message::NewBlock(Block::new(0, String::from(data))))
+                .await
+                .unwrap();
+        }
+        // node must get all blocks before shutdown
+        tx_test.send(Message::ChainRequest).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        tx_cancel.send(()).unwrap();
+        let pending = handle.await.unwrap();
+
+        let data: Vec<&str> = pending.iter().map(|b| b.data.as_str()).collect();
+        assert_eq!(data, vec!["First", "Second", "Third"]);
+    }
+
+    #[tokio::test]
+    async fn test_stats() {
+        let (tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(10);
+        let (tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        // too hard to mine in test
+        let diff = String::from("ffffffffffffffff");
+        let mut node = Node::new(Chain::new(), tx_node, rx_node, rx_cancel, diff);
+        let stats = node.stats();
+
+        let handle = tokio::task::spawn(async move { node.run().await });
+
+        for data in ["First", "Second"] {
+            tx_test
+                .send(Message::NewBlock(Block::new(0, String::from(data))))
+                .await
+                .unwrap();
+        }
+        for _ in 0..3 {
+            tx_test.send(Message::ChainRequest).await.unwrap();
+            let msg = rx_test.recv().await.unwrap();
+            assert!(matches!(msg, Message::ChainResponce(_)));
+        }
+
+        let expected = MessageStats {
+            new_block_in: 2,
+            chain_request_in: 3,
+            chain_response_out: 3,
+            ..Default::default()
+        };
+        assert_eq!(*stats.lock().unwrap(), expected);
+
+        tx_cancel.send(()).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_archive() {
+        let dir =
+            std::env::temp_dir().join(format!("rustychain-archive-{}", rand::random::<u64>()));
+
+        let (tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(10);
+        let (tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        let node = Node::new(Chain::new(), tx_node, rx_node, rx_cancel, String::from("0"));
+        let mut node = node.with_config(NodeConfig {
+            archive_dir: Some(dir.clone()),
+            ..Default::default()
+        });
+        let handle = tokio::task::spawn(async move { node.run().await });
+
+        let mut mined = Vec::new();
+        for i in 0..3 {
+            let block = Block::new(0, format!("Block {}", i));
+            tx_test.send(Message::NewBlock(block)).await.unwrap();
+            match rx_test.recv().await.unwrap() {
+                Message::MinedBlock(block) => mined.push(block),
+                msg => panic!("Expected MinedBlock, but got: {:?}", msg),
+            }
+        }
+
+        tx_cancel.send(()).unwrap();
+        handle.await.unwrap();
+
+        for block in mined {
+            let json = std::fs::read_to_string(archive_path(&dir, block.id)).unwrap();
+            let archived: Block = serde_json::from_str(&json).unwrap();
+            assert!(archived.equals(&block));
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_health() {
+        let (tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(10);
+        let (tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        let node = Node::new(Chain::new(), tx_node, rx_node, rx_cancel, String::from("0"));
+        let mut node = node.with_config(NodeConfig {
+            solo: true,
+            ..Default::default()
+        });
+        let health = node.health();
+        let handle = tokio::task::spawn(async move { node.run().await });
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!health.lock().unwrap().is_ready());
+
+        let block = Block::new(0, String::from("Some data"));
+        tx_test.send(Message::NewBlock(block)).await.unwrap();
+        let msg = rx_test.recv().await.unwrap();
+        assert!(matches!(msg, Message::MinedBlock(_)));
+
+        // the health is updated right after the message is sent
+        let start = std::time::Instant::now();
+        while !health.lock().unwrap().is_ready() {
+            assert!(start.elapsed() < Duration::from_secs(5), "Node isn't ready");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(
+            *health.lock().unwrap(),
+            Health {
+                height: 1,
+                valid: true,
+                peers: 0,
+                solo: true,
+                syncing: false,
+            }
+        );
+
+        tx_cancel.send(()).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_own_echo() {
+        let (tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(10);
+        let (tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        let mut node = Node::new(Chain::new(), tx_node, rx_node, rx_cancel, String::from("0"));
+        let handle = tokio::task::spawn(async move { node.run().await });
+
+        let own = Block::new(0, String::from("Own"));
+        let other = Block::new(0, String::from("Other"));
+        tx_test
+            .send(Message::SubmitBlock(own.clone()))
+            .await
+            .unwrap();
+        tx_test.send(Message::NewBlock(own)).await.unwrap();
+        tx_test.send(Message::NewBlock(other)).await.unwrap();
+
+        let mut mined = Vec::new();
+        for _ in 0..2 {
+            match rx_test.recv().await.unwrap() {
+                Message::MinedBlock(block) => mined.push(block.data),
+                msg => panic!("Expected MinedBlock, but got: {:?}", msg),
+            }
+        }
+        assert_eq!(mined, vec![String::from("Own"), String::from("Other")]);
+
+        tx_cancel.send(()).unwrap();
+        assert!(handle.await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_min_block_interval() {
+        let (tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(10);
+        let (tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        let node = Node::new(Chain::new(), tx_node, rx_node, rx_cancel, String::from("0"));
+        let mut node = node.with_config(NodeConfig {
+            min_block_interval: Some(Duration::from_secs(1)),
+            ..Default::default()
+        });
+        let handle = tokio::task::spawn(async move { node.run().await });
+
+        for data in ["First", "Second"] {
+            tx_test
+                .send(Message::NewBlock(Block::new(0, String::from(data))))
+                .await
+                .unwrap();
+        }
+
+        let mut mined_at = Vec::new();
+        for _ in 0..2 {
+            match rx_test.recv().await.unwrap() {
+                Message::MinedBlock(_) => mined_at.push(std::time::Instant::now()),
+                msg => panic!("Expected MinedBlock, but got: {:?}", msg),
+            }
+        }
+        assert!(mined_at[1] - mined_at[0] >= Duration::from_millis(900));
+
+        tx_cancel.send(()).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_debug_info() {
+        let (tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(10);
+        let (tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        // block is never mined with such difficulty
+        let diff = "f".repeat(64);
+        let mut node = Node::new(Chain::new(), tx_node, rx_node, rx_cancel, diff);
+        let handle = tokio::task::spawn(async move { node.run().await });
+
+        for data in ["First", "Second"] {
+            tx_test
+                .send(Message::NewBlock(Block::new(0, String::from(data))))
+                .await
+                .unwrap();
+        }
+        tx_test.send(Message::DebugRequest).await.unwrap();
+
+        match rx_test.recv().await.unwrap() {
+            Message::DebugResponse(debug) => assert_eq!(
+                debug,
+                NodeDebug {
+                    height: 1,
+                    status: false,
+                    queue: 1,
+                    in_flight: Some(0),
+                    last_sync_secs: None,
+                    peers: 0,
+                    orphans: 0,
+                }
+            ),
+            msg => panic!("Expected DebugResponse, but got: {:?}", msg),
+        }
+
+        tx_cancel.send(()).unwrap();
+        assert_eq!(handle.await.unwrap().len(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_cancel_sync() {
+        let (tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(10);
+        let (tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        let local = Chain::with_genesis("test-net");
+        let mut node = Node::new(
+            local.clone(),
+            tx_node,
+            rx_node,
+            rx_cancel,
+            String::from("0"),
+        );
+        let sync = node.sync_control();
+        let handle = tokio::task::spawn(async move { node.run().await });
+
+        // long valid chain takes a while to check
+        let mut remote = Chain::with_genesis("test-net");
+        for i in 1..200_000 {
+            let prev = remote.blocks.last().unwrap().hash;
+            remote
+                .blocks
+                .push(Block::with_nonce(i, String::new(), prev, 0));
+        }
+        tx_test.send(Message::ChainResponce(remote)).await.unwrap();
+
+        let start = std::time::Instant::now();
+        while !sync.is_active() {
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "Sync isn't started"
+            );
+            std::hint::spin_loop();
+        }
+        assert!(sync.cancel());
+
+        // node is still responsive and keeps its chain
+        tx_test.send(Message::ChainRequest).await.unwrap();
+        match rx_test.recv().await.unwrap() {
+            Message::ChainResponce(chain) => {
+                assert_eq!(chain.blocks.len(), 1);
+                assert!(chain.blocks[0].equals(&local.blocks[0]));
+            }
+            msg => panic!("Expected ChainResponce, but got: {:?}", msg),
+        }
+        assert!(!sync.is_active());
+
+        tx_cancel.send(()).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_max_queued_per_peer() {
+        let (tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(10);
+        let (tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        // blocks are never mined with such difficulty, so they stay in the queue
+        let diff = "f".repeat(64);
+        let node = Node::new(Chain::new(), tx_node, rx_node, rx_cancel, diff);
+        let mut node = node.with_config(NodeConfig {
+            max_queued_per_peer: Some(2),
+            ..Default::default()
+        });
+        let handle = tokio::task::spawn(async move { node.run().await });
+
+        let from = |peer: &str, data: &str| {
+            let msg = Message::NewBlock(Block::new(0, String::from(data)));
+            Message::Envelope(String::from(peer), Box::new(msg))
+        };
+        // the first block is mined at once and leaves the queue
+        for data in ["A1", "A2", "A3", "A4", "A5"] {
+            tx_test.send(from("noisy", data)).await.unwrap();
+        }
+        for data in ["B1", "B2"] {
+            tx_test.send(from("quiet", data)).await.unwrap();
+        }
+        // extra blocks of the noisy peer are rejected
+        for _ in 0..2 {
+            match rx_test.recv().await.unwrap() {
+                Message::Reject { what, .. } => assert_eq!(what, RejectKind::QueueFull),
+                msg => panic!("Expected Reject, but got: {:?}", msg),
+            }
+        }
+        // wait until all blocks are handled
+        tx_test.send(Message::DebugRequest).await.unwrap();
+        match rx_test.recv().await.unwrap() {
+            Message::DebugResponse(debug) => assert_eq!(debug.queue, 4),
+            msg => panic!("Expected DebugResponse, but got: {:?}", msg),
+        }
+
+        tx_cancel.send(()).unwrap();
+        let pending: Vec<String> = handle.await.unwrap().into_iter().map(|b| b.data).collect();
+        assert_eq!(pending, vec!["A1", "A2", "A3", "B1", "B2"]);
+    }
+
+    #[tokio::test]
+    async fn test_confirmations() {
+        let (tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(10);
+        let (tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        let node = Node::new(Chain::new(), tx_node, rx_node, rx_cancel, String::from("0"));
+        let mut node = node.with_config(NodeConfig {
+            confirmations: Some(2),
+            ..Default::default()
+        });
+        let mut events = node.subscribe();
+        let handle = tokio::task::spawn(async move { node.run().await });
+
+        let own = Block::new(0, String::from("Own"));
+        tx_test.send(Message::SubmitBlock(own)).await.unwrap();
+        let first = Block::new(0, String::from("First"));
+        tx_test.send(Message::NewBlock(first)).await.unwrap();
+        for _ in 0..2 {
+            assert!(matches!(rx_test.recv().await, Some(Message::MinedBlock(_))));
+        }
+        // only one block on top of it yet
+        tx_test.send(Message::DebugRequest).await.unwrap();
+        assert!(matches!(
+            rx_test.recv().await,
+            Some(Message::DebugResponse(_))
+        ));
+        assert!(events.try_recv().is_err());
+
+        let second = Block::new(0, String::from("Second"));
+        tx_test.send(Message::NewBlock(second)).await.unwrap();
+        assert!(matches!(rx_test.recv().await, Some(Message::MinedBlock(_))));
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            event,
+            NodeEvent::Confirmed {
+                id: 0,
+                data: String::from("Own"),
+                confirmations: 2,
+            }
+        );
+
+        tx_cancel.send(()).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_initial_sync() {
+        let (tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(10);
+        let (tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        let node = Node::new(Chain::new(), tx_node, rx_node, rx_cancel, String::from("0"));
+        let mut node = node.with_config(NodeConfig {
+            initial_sync: Some(Duration::from_secs(1)),
+            ..Default::default()
+        });
+        let start = std::time::Instant::now();
+        let handle = tokio::task::spawn(async move { node.run().await });
+
+        // the node asks peers for their chain first
+        assert!(matches!(rx_test.recv().await, Some(Message::ChainRequest)));
+
+        let block = Block::new(0, String::from("Some data"));
+        tx_test.send(Message::NewBlock(block)).await.unwrap();
+        let early = tokio::time::timeout(Duration::from_millis(500), rx_test.recv()).await;
+        assert!(early.is_err(), "Mined before initial sync: {:?}", early);
+
+        // nobody answered, so the node mines after the timeout
+        assert!(matches!(rx_test.recv().await, Some(Message::MinedBlock(_))));
+        assert!(start.elapsed() >= Duration::from_secs(1));
+
+        tx_cancel.send(()).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reject_oversized() {
+        let (tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(10);
+        let (tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        let node = Node::new(Chain::new(), tx_node, rx_node, rx_cancel, String::from("0"));
+        let mut node = node.with_config(NodeConfig {
+            max_block_size: Some(8),
+            ..Default::default()
+        });
+        let handle = tokio::task::spawn(async move { node.run().await });
+
+        let block = Block::new(0, "x".repeat(9));
+        tx_test.send(Message::NewBlock(block)).await.unwrap();
+        match rx_test.recv().await.unwrap() {
+            Message::Reject { what, detail } => {
+                assert_eq!(what, RejectKind::TooLarge);
+                assert!(detail.contains("9 bytes"));
+            }
+            msg => panic!("Expected Reject, but got: {:?}", msg),
+        }
+
+        // all transactions of the block count
+        let mut block = Block::new(0, "x".repeat(4));
+        block.add_transaction("x".repeat(5));
+        tx_test.send(Message::NewBlock(block)).await.unwrap();
+        match rx_test.recv().await.unwrap() {
+            Message::Reject { what, detail } => {
+                assert_eq!(what, RejectKind::TooLarge);
+                assert!(detail.contains("9 bytes"));
+            }
+            msg => panic!("Expected Reject, but got: {:?}", msg),
+        }
+
+        // small blocks are still mined
+        let block = Block::new(0, "x".repeat(8));
+        tx_test.send(Message::NewBlock(block)).await.unwrap();
+        assert!(matches!(rx_test.recv().await, Some(Message::MinedBlock(_))));
+
+        tx_cancel.send(()).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replay() {
+        let recorded = [
+            Message::SubmitBlock(Block::new(0, String::from("First"))),
+            Message::Envelope(
+                String::from("peer"),
+                Box::new(Message::NewBlock(Block::new(0, String::from("Second")))),
+            ),
+            Message::ChainRequest,
+            Message::NewBlock(Block::new(0, String::from("Third"))),
+        ]
+        .iter()
+        .map(|msg| serde_json::to_string(msg).unwrap())
+        .collect::<Vec<_>>();
+
+        let mut tips = Vec::new();
+        for _ in 0..2 {
+            let (tx_node, mut rx_test) = mpsc::channel::<Message>(16);
+            let (_tx_test, rx_node) = mpsc::channel::<Message>(1);
+            let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+            let chain = Chain::with_genesis("test-net");
+            let mut node = Node::new(chain, tx_node, rx_node, rx_cancel, String::from("00"));
+
+            let messages = recorded
+                .iter()
+                .map(|line| serde_json::from_str::<Message>(line).unwrap());
+            node.replay(futures::stream::iter(messages)).await;
+
+            let chain = node.chain();
+            assert_eq!(chain.blocks.len(), 4);
+            assert!(chain.status);
+            assert!(chain.queue.is_empty());
+            assert!(chain.have_errors().is_none());
+            let tip = chain.blocks.last().unwrap();
+            assert_eq!(tip.data, "Third");
+            assert!(tip.string_hash().ends_with("00"));
+            tips.push(tip.hash);
+
+            // the chain is served after the first two blocks are mined
+            let mut mined = 0;
+            while let Ok(msg) = rx_test.try_recv() {
+                match msg {
+                    Message::MinedBlock(_) => mined += 1,
+                    Message::ChainResponce(chain) => assert_eq!(chain.blocks.len(), 3),
+                    msg => panic!("Unexpected message: {:?}", msg),
+                }
+            }
+            assert_eq!(mined, 3);
+        }
+        assert_eq!(tips[0], tips[1]);
+
+        // a difficulty which can't be met doesn't hang the replay
+        let (tx_node, _rx_test) = mpsc::channel::<Message>(16);
+        let (_tx_test, rx_node) = mpsc::channel::<Message>(1);
+        let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+        let chain = Chain::with_genesis("test-net");
+        let mut node = Node::new(chain, tx_node, rx_node, rx_cancel, String::from("zz"));
+        let block = Block::new(0, String::from("First"));
+        node.replay(futures::stream::iter([Message::SubmitBlock(block)]))
+            .await;
+        assert_eq!(node.chain().blocks.len(), 2);
+        assert!(!node.chain().status);
+    }
+
+    #[tokio::test]
+    async fn test_prefer_first_seen() {
+        for prefer_first_seen in [false, true] {
+            let (tx_node, _rx_test) = mpsc::channel::<Message>(16);
+            let (_tx_test, rx_node) = mpsc::channel::<Message>(1);
+            let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+            let chain = Chain::with_genesis("test-net");
+            let node = Node::new(chain, tx_node, rx_node, rx_cancel, String::from("0"));
+            let mut node = node.with_config(NodeConfig {
+                prefer_first_seen,
+                ..Default::default()
+            });
+
+            let block = Block::new(0, String::from("Some data"));
+            node.replay(futures::stream::iter([Message::NewBlock(block)]))
+                .await;
+            let tip = node.chain().blocks[1].clone();
+
+            // the same block mined by another node with a lower hash
+            let mut lower = tip.clone();
+            while lower.hash >= tip.hash || !lower.string_hash().ends_with('0') {
+                lower.nonce = rand::random();
+                lower.update_hash();
+            }
+            node.replay(futures::stream::iter([Message::MinedBlock(lower.clone())]))
+                .await;
+
+            let expected = if prefer_first_seen {
+                tip.hash
+            } else {
+                lower.hash
+            };
+            assert_eq!(node.chain().blocks[1].hash, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_min_difficulty_bits() {
+        // valid, but not mined for any difficulty
+        let remote = Chain::test_chain(5);
+        assert_eq!(remote.have_errors(), None);
+        let weakest = remote.blocks[1..]
+            .iter()
+            .min_by_key(|block| block.trailing_zero_bits())
+            .unwrap();
+        let work = weakest.trailing_zero_bits();
+
+        for min_difficulty_bits in [None, Some(work + 1)] {
+            let (tx_node, mut rx_test) = mpsc::channel::<Message>(16);
+            let (_tx_test, rx_node) = mpsc::channel::<Message>(1);
+            let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+            let chain = Chain::with_genesis("test-chain");
+            let node = Node::new(chain, tx_node, rx_node, rx_cancel, String::from("0"));
+            let mut node = node.with_config(NodeConfig {
+                min_difficulty_bits,
+                ..Default::default()
+            });
+
+            let messages = [
+                Message::ChainResponce(remote.clone()),
+                Message::MinedBlock(weakest.clone()),
+            ];
+            node.replay(futures::stream::iter(messages)).await;
+
+            if min_difficulty_bits.is_none() {
+                assert_eq!(node.chain().blocks.len(), 5);
+                assert!(rx_test.try_recv().is_err());
+                continue;
+            }
+            assert_eq!(node.chain().blocks.len(), 1);
+            for _ in 0..2 {
+                match rx_test.try_recv().unwrap() {
+                    Message::Reject { what, .. } => assert_eq!(what, RejectKind::LowDifficulty),
+                    msg => panic!("Expected Reject, but got: {:?}", msg),
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_retry() {
+        let (_tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(10);
+        let (tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        let node = Node::new(Chain::new(), tx_node, rx_node, rx_cancel, String::from("0"));
+        let mut node = node.with_config(NodeConfig {
+            initial_sync: Some(Duration::from_secs(10)),
+            sync_retry: Some(Duration::from_millis(100)),
+            ..Default::default()
+        });
+        let handle = tokio::task::spawn(async move { node.run().await });
+
+        // nobody answers, so the request is repeated less and less often
+        let mut last = std::time::Instant::now();
+        let mut intervals = Vec::new();
+        for _ in 0..4 {
+            assert!(matches!(rx_test.recv().await, Some(Message::ChainRequest)));
+            intervals.push(last.elapsed());
+            last = std::time::Instant::now();
+        }
+        assert!(intervals[3] > intervals[1]);
+        assert!(intervals[3] >= Duration::from_millis(200));
+
+        tx_cancel.send(()).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_restore_pending() {
+        let name = format!("rustychain-pending-{}.json", rand::random::<u64>());
+        let path = std::env::temp_dir().join(name);
+
+        let (tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(10);
+        let (tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        // block is never mined with such difficulty
+        let diff = "f".repeat(64);
+        let chain = Chain::with_genesis("test-net");
+        let mut node = Node::new(chain, tx_node, rx_node, rx_cancel, diff.clone());
+        let handle = tokio::task::spawn(async move {
+            let pending = node.run().await;
+            (pending, node)
+        });
+
+        for data in ["First", "Second"] {
+            let block = Block::new(0, String::from(data));
+            tx_test.send(Message::NewBlock(block)).await.unwrap();
+        }
+        tx_test.send(Message::DebugRequest).await.unwrap();
+        assert!(matches!(
+            rx_test.recv().await,
+            Some(Message::DebugResponse(_))
+        ));
+        tx_cancel.send(()).unwrap();
+
+        // shut down with both blocks unmined
+        let (pending, node) = handle.await.unwrap();
+        assert_eq!(pending.len(), 2);
+        let mut chain = node.chain().clone();
+        chain.restore_queue(pending);
+        chain.save_to_file(&path, DbFormat::Json).unwrap();
+
+        // restored node has them queued again
+        let chain = Chain::load_from_file(&path).unwrap();
+        chain.assert_invariants();
+        let (tx_node, _rx_test) = mpsc::channel::<Message>(10);
+        let (_tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+        let node = Node::new(chain, tx_node, rx_node, rx_cancel, diff);
+        assert_eq!(node.debug_info().queue, 2);
+        assert_eq!(node.debug_info().height, 1);
+        let queued: Vec<&str> = node.chain().queue.iter().map(|b| b.data.as_str()).collect();
+        assert_eq!(queued, ["First", "Second"]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_solo_mining() {
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(32);
+        let (_tx_test, rx_node) = mpsc::channel::<Message>(1);
+        let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+        let config = NodeConfig {
+            solo_mining: true,
+            ..Default::default()
+        };
+        let chain = Chain::with_genesis("test-net");
+        let mut node =
+            Node::new(chain, tx_node, rx_node, rx_cancel, String::from("0")).with_config(config);
+
+        // a longer chain and a competing block from the network
+        let mut remote = Chain::with_genesis("test-net");
+        for id in 1..10 {
+            remote.add_queue(Block::new(0, format!("Remote {}", id)));
+            remote.try_add();
+            remote.blocks[id].update_hash();
+        }
+        let foreign = remote.blocks[1].clone();
+        let mut messages = Vec::new();
+        for i in 0..5 {
+            let block = Block::new(0, format!("Transaction {}", i));
+            messages.push(Message::SubmitBlock(block));
+            messages.push(Message::MinedBlock(foreign.clone()));
+            messages.push(Message::ChainResponce(remote.clone()));
+        }
+        node.replay(futures::stream::iter(messages)).await;
+
+        let chain = node.chain();
+        assert_eq!(chain.blocks.len(), 6);
+        assert!(chain.have_errors().is_none());
+        for (i, block) in chain.blocks[1..].iter().enumerate() {
+            assert_eq!(block.data, format!("Transaction {}", i));
+        }
+        assert!(node.debug_info().last_sync_secs.is_none());
+        assert!(node.health().lock().unwrap().is_ready());
+
+        // nothing but own blocks is announced
+        while let Ok(msg) = rx_test.try_recv() {
+            assert!(matches!(msg, Message::MinedBlock(_)), "{:?}", msg);
+        }
+    }
+
+    #[derive(Debug)]
+    struct RejectInvalid;
+
+    impl TransactionValidator for RejectInvalid {
+        fn validate(&self, tx: &str, _chain: &Chain) -> Result<(), String> {
+            if tx.contains("invalid") {
+                return Err(String::from("contains \"invalid\""));
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validator() {
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(16);
+        let (_tx_test, rx_node) = mpsc::channel::<Message>(1);
+        let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+        let chain = Chain::with_genesis("test-net");
+        let mut node = Node::new(chain, tx_node, rx_node, rx_cancel, String::from("0"))
+            .with_validator(RejectInvalid);
+
+        let mut block = Block::new(0, String::from("Other data"));
+        block.add_transaction(String::from("Other invalid data"));
+        let messages = [
+            Message::NewBlock(Block::new(0, String::from("Some invalid data"))),
+            Message::NewBlock(block),
+            Message::NewBlock(Block::new(0, String::from("Some data"))),
+        ];
+        node.replay(futures::stream::iter(messages)).await;
+
+        let chain = node.chain();
+        assert_eq!(chain.blocks.len(), 2);
+        assert_eq!(chain.blocks[1].data, "Some data");
+        assert!(chain.queue.is_empty());
+        match rx_test.try_recv() {
+            Ok(Message::Reject { what, detail }) => {
+                assert_eq!(what, RejectKind::InvalidTransaction);
+                assert!(detail.contains("Some invalid data"));
+            }
+            msg => panic!("Unexpected message: {:?}", msg),
+        }
+        match rx_test.try_recv() {
+            Ok(Message::Reject { what, detail }) => {
+                assert_eq!(what, RejectKind::InvalidTransaction);
+                assert!(detail.contains("Other invalid data"));
+            }
+            msg => panic!("Unexpected message: {:?}", msg),
+        }
+        assert!(matches!(rx_test.try_recv(), Ok(Message::MinedBlock(_))));
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat() {
+        let (_tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (tx_node, _rx_test) = mpsc::channel::<Message>(10);
+        let (tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        let interval = Duration::from_millis(100);
+        let chain = Chain::with_genesis("test-net");
+        let node = Node::new(chain, tx_node, rx_node, rx_cancel, String::from("0"));
+        let mut node = node.with_config(NodeConfig {
+            heartbeat: Some(interval),
+            ..Default::default()
+        });
+        let mut events = node.subscribe();
+        let handle = tokio::task::spawn(async move { node.run().await });
+
+        let event = tokio::time::timeout(interval * 2, events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        match event {
+            NodeEvent::Heartbeat { state, mined } => {
+                assert_eq!(state.height, 1);
+                assert_eq!(state.queue, 0);
+                assert_eq!(mined, 0);
+            }
+            event => panic!("Unexpected event: {:?}", event),
+        }
+
+        tx_cancel.send(()).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_memory_limits() {
+        let (tx_node, _rx_test) = mpsc::channel::<Message>(16);
+        let (_tx_test, rx_node) = mpsc::channel::<Message>(1);
+        let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+        let config = NodeConfig {
+            limits: MemoryLimits {
+                max_chain_blocks: Some(3),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let chain = Chain::with_genesis("test-net");
+        let mut node =
+            Node::new(chain, tx_node, rx_node, rx_cancel, String::from("0")).with_config(config);
+
+        let messages = (1..=5).map(|i| Message::NewBlock(Block::new(0, format!("Block {}", i))));
+        node.replay(futures::stream::iter(messages)).await;
+
+        // the chain keeps growing, bodies of old blocks are pruned
+        let chain = node.chain();
+        assert_eq!(chain.blocks.len(), 6);
+        assert!(chain.queue.is_empty());
+        let pruned = chain
+            .blocks
+            .iter()
+            .map(|block| block.pruned)
+            .collect::<Vec<_>>();
+        assert_eq!(pruned, [false, true, true, false, false, false]);
+        assert_eq!(chain.have_errors(), None);
+
+        // longer chains are taken and pruned the same way
+        let mut remote = Chain::test_chain(10);
+        let bits = 128 - node.chain().total_work().leading_zeros() + 1;
+        remote.remine_tip(bits).unwrap();
+        assert!(remote.total_work() > node.chain().total_work());
+        let messages = [Message::ChainResponce(remote.clone())];
+        node.replay(futures::stream::iter(messages)).await;
+        let chain = node.chain();
+        assert_eq!(chain.blocks.len(), 10);
+        assert_eq!(chain.blocks.iter().filter(|block| block.pruned).count(), 6);
+        assert!(!chain.blocks[7].pruned);
+        assert_eq!(chain.blocks[9].hash, remote.blocks[9].hash);
+        assert_eq!(chain.have_errors(), None);
+    }
+
+    #[tokio::test]
+    async fn test_queue_limit() {
+        let (tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(10);
+        let (tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        let node = Node::new(Chain::new(), tx_node, rx_node, rx_cancel, String::from("0"));
+        // the node doesn't mine while it waits for the chain, so the queue fills up
+        let mut node = node.with_config(NodeConfig {
+            initial_sync: Some(Duration::from_secs(60)),
+            limits: MemoryLimits {
+                max_queue: Some(2),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let handle = tokio::task::spawn(async move { node.run().await });
+        assert!(matches!(rx_test.recv().await, Some(Message::ChainRequest)));
+
+        for i in 1..=3 {
+            let block = Block::new(0, format!("Block {}", i));
+            tx_test.send(Message::NewBlock(block)).await.unwrap();
+        }
+        match rx_test.recv().await {
+            Some(Message::Reject { what, detail }) => {
+                assert_eq!(what, RejectKind::QueueFull);
+                assert!(detail.contains("Block 3"));
+            }
+            msg => panic!("Unexpected message: {:?}", msg),
+        }
+
+        tx_cancel.send(()).unwrap();
+        assert_eq!(handle.await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_max_orphans() {
+        let (tx_node, _rx_test) = mpsc::channel::<Message>(16);
+        let (_tx_test, rx_node) = mpsc::channel::<Message>(1);
+        let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+        let config = NodeConfig {
+            orphan_window: Some(8),
+            limits: MemoryLimits {
+                max_orphans: Some(3),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut node = Node::new(
+            Chain::test_chain(2),
+            tx_node,
+            rx_node,
+            rx_cancel,
+            String::from("0"),
+        )
+        .with_config(config);
+
+        // the farthest block is evicted by a closer one, the rest connect when the gap is filled
+        let full = Chain::test_chain(6);
+        let messages = [5, 4, 3, 2].map(|id| Message::BlockResponse(Some(full.blocks[id].clone())));
+        node.replay(futures::stream::iter(messages)).await;
+        assert_eq!(node.chain().blocks.len(), 5);
+        assert_eq!(node.chain().export_headers(), full.export_headers()[..5]);
+    }
+
+    #[tokio::test]
+    async fn test_empty_block_interval() {
+        let (_tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(10);
+        let (tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        let chain = Chain::with_genesis("test-net");
+        let node = Node::new(chain, tx_node, rx_node, rx_cancel, String::from("0"));
+        let mut node = node.with_config(NodeConfig {
+            empty_block_interval: Some(Duration::from_millis(50)),
+            ..Default::default()
+        });
+        let handle = tokio::task::spawn(async move {
+            node.run().await;
+            node
+        });
+
+        // no transactions are submitted
+        let msg = tokio::time::timeout(Duration::from_secs(5), rx_test.recv())
+            .await
+            .unwrap();
+        match msg {
+            Some(Message::MinedBlock(block)) => {
+                assert_eq!(block.id, 1);
+                assert!(block.data.is_empty());
+            }
+            msg => panic!("Unexpected message: {:?}", msg),
+        }
+
+        tx_cancel.send(()).unwrap();
+        let node = handle.await.unwrap();
+        assert!(node.chain().blocks.len() >= 2);
+        assert!(node.chain().have_errors().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_min_peers_to_mine() {
+        let (tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(10);
+        let (tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        let chain = Chain::with_genesis("test-net");
+        let node = Node::new(chain, tx_node, rx_node, rx_cancel, String::from("0"));
+        let mut node = node.with_config(NodeConfig {
+            min_peers_to_mine: Some(2),
+            ..Default::default()
+        });
+        let health = node.health();
+        let handle = tokio::task::spawn(async move { node.run().await });
+
+        health.lock().unwrap().peers = 1;
+        let block = Block::new(0, String::from("Some data"));
+        tx_test.send(Message::NewBlock(block)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        tx_test.send(Message::DebugRequest).await.unwrap();
+        match rx_test.recv().await {
+            Some(Message::DebugResponse(debug)) => {
+                assert_eq!(debug.height, 1);
+                assert_eq!(debug.queue, 1);
+                assert_eq!(debug.in_flight, None);
+            }
+            msg => panic!("Unexpected message: {:?}", msg),
+        }
+
+        // the block is mined once enough peers are connected
+        health.lock().unwrap().peers = 2;
+        let msg = tokio::time::timeout(Duration::from_secs(5), rx_test.recv())
+            .await
+            .unwrap();
+        assert!(matches!(msg, Some(Message::MinedBlock(block)) if block.id == 1));
+
+        tx_cancel.send(()).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_on_block() {
+        let (tx_node, _rx_test) = mpsc::channel::<Message>(16);
+        let (_tx_test, rx_node) = mpsc::channel::<Message>(1);
+        let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+        let ids = Arc::new(Mutex::new(Vec::new()));
+        let seen = ids.clone();
+        let config = NodeConfig {
+            on_block: Some(BlockHook::new(move |block: &Block| {
+                seen.lock().unwrap().push(block.id)
+            })),
+            ..Default::default()
+        };
+        let chain = Chain::with_genesis("test-net");
+        let mut node =
+            Node::new(chain, tx_node, rx_node, rx_cancel, String::from("0")).with_config(config);
+
+        let messages =
+            ["First", "Second"].map(|data| Message::NewBlock(Block::new(0, data.into())));
+        node.replay(futures::stream::iter(messages)).await;
+        assert_eq!(*ids.lock().unwrap(), [1, 2]);
+
+        // only new blocks of a longer chain
+        let mut remote = node.chain().clone();
+        for data in ["Third", "Fourth"] {
+            remote.add_queue(Block::new(0, String::from(data)));
+            assert!(remote.try_add());
+            remote.blocks.last_mut().unwrap().update_hash();
+        }
+        node.replay(futures::stream::iter([Message::ChainResponce(remote)]))
+            .await;
+        assert_eq!(node.chain().blocks.len(), 5);
+        assert_eq!(*ids.lock().unwrap(), [1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_reorg() {
+        let (tx_node, _rx_test) = mpsc::channel::<Message>(16);
+        let (_tx_test, rx_node) = mpsc::channel::<Message>(1);
+        let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+        let chain = Chain::with_genesis("test-net");
+        let mut node = Node::new(chain, tx_node, rx_node, rx_cancel, String::from("0"));
+        let mut events = node.subscribe();
+
+        let messages =
+            ["First", "Second"].map(|data| Message::NewBlock(Block::new(0, data.into())));
+        node.replay(futures::stream::iter(messages)).await;
+        let local = node.chain().clone();
+
+        // the remote chain forks after the first block and has more work
+        let mut remote = local.clone();
+        remote.blocks.truncate(2);
+        for data in ["Remote A", "Remote B", "Remote C"] {
+            remote.add_queue(Block::new(0, String::from(data)));
+            assert!(remote.try_add());
+            remote.blocks.last_mut().unwrap().update_hash();
+            remote.remine_tip(16).unwrap();
+        }
+        node.replay(futures::stream::iter([Message::ChainResponce(
+            remote.clone(),
+        )]))
+        .await;
+
+        let hashes = |blocks: &[Block]| blocks.iter().map(|b| b.hash).collect::<Vec<_>>();
+        assert_eq!(
+            events.try_recv().unwrap(),
+            NodeEvent::Reorg {
+                removed: hashes(&local.blocks[2..]),
+                added: hashes(&remote.blocks[2..]),
+            }
+        );
+        assert!(events.try_recv().is_err());
+
+        // a longer chain without forks isn't a reorg
+        let mut longer = remote.clone();
+        longer.add_queue(Block::new(0, String::from("Remote D")));
+        assert!(longer.try_add());
+        longer.blocks.last_mut().unwrap().update_hash();
+        node.replay(futures::stream::iter([Message::ChainResponce(longer)]))
+            .await;
+        assert_eq!(node.chain().blocks.len(), 6);
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ack_new_blocks() {
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(16);
+        let (_tx_test, rx_node) = mpsc::channel::<Message>(1);
+        let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+        let chain = Chain::with_genesis("test-net");
+        let node = Node::new(chain, tx_node, rx_node, rx_cancel, String::from("0"));
+        let mut node = node.with_config(NodeConfig {
+            ack_new_blocks: true,
+            max_block_size: Some(16),
+            ..Default::default()
+        });
+
+        let block = Block::new(0, String::from("Some data"));
+        let messages = [
+            Message::NewBlock(Block::new(0, String::from("Some data too large"))),
+            Message::Envelope(
+                String::from("peer"),
+                Box::new(Message::NewBlock(block.clone())),
+            ),
+        ];
+        node.replay(futures::stream::iter(messages)).await;
+
+        assert!(matches!(rx_test.try_recv(), Ok(Message::Reject { .. })));
+        match rx_test.try_recv() {
+            Ok(Message::Ack { data_hash }) => {
+                assert_eq!(data_hash, block.data_hash());
+                assert_eq!(data_hash, node.chain().blocks[1].data_hash());
+            }
+            msg => panic!("Expected Ack, but got: {:?}", msg),
+        }
+        assert!(matches!(rx_test.try_recv(), Ok(Message::MinedBlock(_))));
+        assert_eq!(node.stats().lock().unwrap().ack_out, 1);
+    }
+
+    #[tokio::test]
+    async fn test_orphan_window() {
+        let (tx_node, _rx_test) = mpsc::channel::<Message>(16);
+        let (_tx_test, rx_node) = mpsc::channel::<Message>(1);
+        let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+        let full = Chain::test_chain(9);
+        let node = Node::new(
+            Chain::test_chain(2),
+            tx_node,
+            rx_node,
+            rx_cancel,
+            String::new(),
+        );
+        let mut node = node.with_config(NodeConfig {
+            orphan_window: Some(8),
+            ..Default::default()
+        });
+
+        let mut messages = full.blocks[5..=8]
+            .iter()
+            .map(|block| Message::MinedBlock(block.clone()))
+            .collect::<Vec<_>>();
+        messages.push(Message::ChainResponce(Chain::test_chain(5)));
+        node.replay(futures::stream::iter(messages)).await;
+
+        assert_eq!(node.chain().export_headers(), full.export_headers());
+    }
+
+    #[tokio::test]
+    async fn test_harness() {
+        let (mut node, mut harness) = Node::test_harness(Chain::with_genesis("test-net"), "0");
+        let handle = tokio::task::spawn(async move { node.run().await });
+
+        let block = Block::new(0, String::from("Some data"));
+        harness.send(Message::SubmitBlock(block)).await;
+        match harness.recv().await {
+            Message::MinedBlock(block) => {
+                assert_eq!(block.id, 1);
+                assert_eq!(block.data, "Some data");
+                assert!(block.validate_hash());
+            }
+            msg => panic!("Expected MinedBlock, but got: {:?}", msg),
+        }
+
+        harness.shutdown();
+        assert!(handle.await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_mining_watchdog() {
+        // the worker won't mine 3 bytes of difficulty in time
+        let chain = Chain::with_genesis("test-net");
+        let (node, mut harness) = Node::test_harness(chain, "ffffff");
+        let mut node = node.with_config(NodeConfig {
+            mining_timeout: Some(Duration::from_millis(200)),
+            stall_policy: StallPolicy::LowerDifficulty,
+            ..Default::default()
+        });
+        let mut events = node.subscribe();
+        let handle = tokio::task::spawn(async move { node.run().await });
+
+        harness
+            .send(Message::SubmitBlock(Block::new(
+                0,
+                String::from("Some data"),
+            )))
+            .await;
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let stalled = NodeEvent::MiningStalled {
+            id: 1,
+            difficulty: String::from("ffff"),
+        };
+        assert_eq!(event, stalled);
+
+        // the difficulty is lowered until the block is mined
+        let mined = tokio::time::timeout(Duration::from_secs(30), harness.recv())
+            .await
+            .unwrap();
+        match mined {
+            Message::MinedBlock(block) => {
+                assert_eq!(block.id, 1);
+                assert!(block.validate_hash());
+                assert!(block.string_hash().ends_with("ff"));
+            }
+            msg => panic!("Expected MinedBlock, but got: {:?}", msg),
+        }
+
+        harness.shutdown();
+        assert!(handle.await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_chain_difficulty() {
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(16);
+        let (_tx_test, rx_node) = mpsc::channel::<Message>(1);
+        let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        // the chain is mined with a higher difficulty than the node's own
+        let mut chain = Chain::with_genesis("test-net");
+        chain.difficulty = String::from("00");
+        chain.add_queue(Block::new(0, String::from("First")));
+        assert!(chain.try_add());
+        chain.status = false;
+        // far from the nonces the node tries itself
+        let mut low = chain.blocks[1].clone();
+        low.nonce = 1 << 32;
+        low.update_hash();
+        while !low.string_hash().ends_with('0') || low.string_hash().ends_with("00") {
+            low.nonce += 1;
+            low.update_hash();
+        }
+        let mut node = Node::new(chain, tx_node, rx_node, rx_cancel, String::from("0"));
+        assert_eq!(node.chain().difficulty, "00");
+
+        node.replay(futures::stream::iter([Message::MinedBlock(low.clone())]))
+            .await;
+        match rx_test.try_recv() {
+            Ok(Message::Reject { what, .. }) => assert_eq!(what, RejectKind::LowDifficulty),
+            msg => panic!("Expected Reject, but got: {:?}", msg),
+        }
+        assert_ne!(node.chain().blocks[1].hash, low.hash);
+    }
+
+    #[tokio::test]
+    async fn test_validation_policy() {
+        let remote = Chain::test_chain(3);
+        let policy = ValidationPolicy {
+            genesis: Some(Block::genesis("test-net").hash),
+            ..Default::default()
+        };
+        for (validation, taken) in [(ValidationPolicy::default(), true), (policy, false)] {
+            let (tx_node, mut rx_test) = mpsc::channel::<Message>(16);
+            let (_tx_test, rx_node) = mpsc::channel::<Message>(1);
+            let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+            let chain = Chain::with_genesis("test-net");
+            let config = NodeConfig {
+                validation,
+                ..Default::default()
+            };
+            let mut node = Node::new(chain, tx_node, rx_node, rx_cancel, String::from("0"))
+                .with_config(config);
+
+            node.replay(futures::stream::iter([Message::ChainResponce(
+                remote.clone(),
+            )]))
+            .await;
+            assert_eq!(node.chain().blocks.len() == 3, taken);
+            // a rejected chain doesn't count as a sync
+            assert_eq!(node.debug_info().last_sync_secs.is_some(), taken);
+            if !taken {
+                match rx_test.try_recv() {
+                    Ok(Message::Reject { what, detail }) => {
+                        assert_eq!(what, RejectKind::InvalidChain);
+                        assert!(detail.contains("genesis"));
+                    }
+                    msg => panic!("Expected Reject, but got: {:?}", msg),
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remine_tip() {
+        for solo_mining in [true, false] {
+            let (tx_node, mut rx_test) = mpsc::channel::<Message>(16);
+            let (_tx_test, rx_node) = mpsc::channel::<Message>(1);
+            let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+            let config = NodeConfig {
+                solo_mining,
+                ..Default::default()
+            };
+            let mut node = Node::new(
+                Chain::test_chain(3),
+                tx_node,
+                rx_node,
+                rx_cancel,
+                String::from("0"),
+            )
+            .with_config(config);
+            let tip = node.chain().blocks[2].hash;
+
+            node.replay(futures::stream::iter([Message::RemineTip(12)]))
+                .await;
+            let chain = node.chain();
+            assert_eq!(chain.blocks[2].hash != tip, solo_mining);
+            assert_eq!(chain.blocks[2].trailing_zero_bits() >= 12, solo_mining);
+            assert_eq!(chain.have_errors(), None);
+            // the node's difficulty is recorded in the chain and kept
+            if solo_mining {
+                assert!(chain.meets_difficulty(&chain.blocks[2]));
+                // the new tip is sent to other nodes
+                match rx_test.try_recv() {
+                    Ok(Message::MinedBlock(block)) => assert_eq!(block.hash, chain.blocks[2].hash),
+                    msg => panic!("Expected MinedBlock, but got: {:?}", msg),
+                }
+            }
+
+            // no more than 8 bits over the difficulty
+            let tip = chain.blocks[2].hash;
+            node.replay(futures::stream::iter([Message::RemineTip(13)]))
+                .await;
+            assert_eq!(node.chain().blocks[2].hash, tip);
+            let chain = node.chain();
+
+            // other nodes can't request it
+            let envelope =
+                Message::Envelope(String::from("peer"), Box::new(Message::RemineTip(16)));
+            let tip = chain.blocks[2].hash;
+            node.replay(futures::stream::iter([envelope])).await;
+            assert_eq!(node.chain().blocks[2].hash, tip);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_difficulty_consensus() {
+        let node = |difficulty: &str| {
+            let (tx_node, rx_test) = mpsc::channel::<Message>(16);
+            let (_tx_test, rx_node) = mpsc::channel::<Message>(1);
+            let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+            let chain = Chain::with_genesis("test-net");
+            let node = Node::new(chain, tx_node, rx_node, rx_cancel, difficulty.to_owned());
+            (node, rx_test)
+        };
+        let (mut first, _rx_first) = node("0");
+        let (mut second, _rx_second) = node("000");
+        assert_eq!(first.difficulty(), "0");
+        assert_eq!(second.difficulty(), "000");
+
+        let blocks =
+            ["First", "Second"].map(|data| Message::SubmitBlock(Block::new(0, data.to_owned())));
+        first.replay(futures::stream::iter(blocks)).await;
+        let chain = first.chain().clone();
+        second
+            .replay(futures::stream::iter([Message::ChainResponce(chain)]))
+            .await;
+        assert_eq!(second.chain().blocks.len(), 3);
+        assert_eq!(second.difficulty(), first.difficulty());
+
+        // the next block is mined with the difficulty of the chain, not the initial one
+        let block = Block::new(0, String::from("Third"));
+        second
+            .replay(futures::stream::iter([Message::SubmitBlock(block)]))
+            .await;
+        let tip = &second.chain().blocks[3];
+        let first_nonce =
+            (0..).find(|&nonce| hex::encode(tip.hash_with_nonce(nonce)).ends_with('0'));
+        assert_eq!(Some(tip.nonce), first_nonce);
+        assert_eq!(second.chain().difficulty, "0");
+    }
+
+    #[tokio::test]
+    async fn test_prefix_mode() {
+        let (tx, rx) = mpsc::channel(10);
+        let (result_tx, mut result_rx) = mpsc::channel(10);
+        let (cancel_tx, cancel_rx) = broadcast::channel(1);
+        let handle = tokio::task::spawn(nonce_worker(
+            rx,
+            result_tx,
+            cancel_rx,
+            DifficultyMode::Prefix,
+        ));
+        let block = Block::new(1, String::from("test"));
+        tx.send((block.clone(), String::from("0"))).await.unwrap();
+        let (hash, nonce, _) = result_rx.recv().await.unwrap();
+        assert_eq!(block.hash_with_nonce(nonce), hash);
+        assert!(hex::encode(hash).starts_with('0'));
+        cancel_tx.send(()).unwrap();
+        handle.await.unwrap();
+
+        let (tx_node, _rx_test) = mpsc::channel::<Message>(16);
+        let (_tx_test, rx_node) = mpsc::channel::<Message>(1);
+        let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+        let config = NodeConfig {
+            difficulty_mode: DifficultyMode::Prefix,
+            ..Default::default()
+        };
+        let chain = Chain::with_genesis("test-net");
+        let mut node =
+            Node::new(chain, tx_node, rx_node, rx_cancel, String::from("00")).with_config(config);
+        let blocks =
+            ["First", "Second"].map(|data| Message::SubmitBlock(Block::new(0, data.to_owned())));
+        node.replay(futures::stream::iter(blocks)).await;
+
+        let chain = node.chain();
+        assert_eq!(chain.blocks.len(), 3);
+        assert_eq!(chain.difficulty_mode, DifficultyMode::Prefix);
+        for block in chain.blocks[1..].iter() {
+            assert!(block.string_hash().starts_with("00"));
+            assert!(chain.meets_difficulty(block));
+        }
+        let policy = ValidationPolicy {
+            recorded_difficulty: true,
+            ..Default::default()
+        };
+        assert_eq!(chain.verify_with_policy(&policy), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_total_work() {
+        let (tx_node, _rx_test) = mpsc::channel::<Message>(16);
+        let (_tx_test, rx_node) = mpsc::channel::<Message>(1);
+        let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+        let mut chain = Chain::with_genesis("test-net");
+        chain.add_queue(Block::new_with_timestamp(0, String::from("Hard"), 1));
+        assert!(chain.try_add());
+        chain.blocks[1].update_hash();
+        chain.remine_tip(16).unwrap();
+        let mut node = Node::new(chain, tx_node, rx_node, rx_cancel, String::from("0"));
+
+        // a longer chain of easy blocks doesn't win over a shorter chain of hard blocks
+        let mut easy = Chain::with_genesis("test-net");
+        for data in ["Easy A", "Easy B", "Easy C"] {
+            easy.add_queue(Block::new_with_timestamp(0, String::from(data), 1));
+            assert!(easy.try_add());
+            easy.blocks.last_mut().unwrap().update_hash();
+        }
+        assert!(easy.total_work() < node.chain().total_work());
+        node.replay(futures::stream::iter([Message::ChainResponce(easy)]))
+            .await;
+        assert_eq!(node.chain().blocks[1].data, "Hard");
+
+        // a shorter chain of harder blocks wins
+        let mut hard = Chain::with_genesis("test-net");
+        hard.add_queue(Block::new_with_timestamp(0, String::from("Harder"), 1));
+        assert!(hard.try_add());
+        hard.blocks[1].update_hash();
+        let mut local = node.chain().clone();
+        local.add_queue(Block::new_with_timestamp(0, String::from("Easy"), 1));
+        assert!(local.try_add());
+        local.blocks[2].update_hash();
+        hard.remine_tip(128 - local.total_work().leading_zeros() + 1)
+            .unwrap();
+        assert!(hard.total_work() > local.total_work());
+        node.replay(futures::stream::iter([Message::ChainResponce(hard)]))
+            .await;
+        assert_eq!(node.chain().blocks[1].data, "Harder");
+    }
+
+    #[tokio::test]
+    async fn test_block_request() {
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(16);
+        let (_tx_test, rx_node) = mpsc::channel::<Message>(1);
+        let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+        let full = Chain::test_chain(4);
+        let node = Node::new(
+            Chain::test_chain(1),
+            tx_node,
+            rx_node,
+            rx_cancel,
+            String::new(),
+        );
+        let mut node = node.with_config(NodeConfig {
+            orphan_window: Some(4),
+            ..Default::default()
+        });
+
+        let messages = [3, 1, 2].map(|id| Message::BlockResponse(full.get_block(id).cloned()));
+        node.replay(futures::stream::iter(messages)).await;
+        assert_eq!(node.chain().export_headers(), full.export_headers());
+
+        let request = Message::Envelope(String::from("peer"), Box::new(Message::BlockRequest(2)));
+        node.replay(futures::stream::iter([request])).await;
+        match rx_test.try_recv() {
+            Ok(Message::BlockResponse(Some(block))) => assert!(block.equals(&full.blocks[2])),
+            msg => panic!("Expected BlockResponse, but got: {:?}", msg),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_worker_restart() {
+        let (mut node, mut harness) = Node::test_harness(Chain::with_genesis("test-net"), "0");
+        assert!(!harness.kill_worker());
+        let handle = tokio::task::spawn(async move { node.run().await });
+
+        for (i, data) in ["First", "Second", "Third"].into_iter().enumerate() {
+            if i == 1 {
+                // the worker dies while the node is idle
+                assert!(harness.kill_worker());
+            }
+            let block = Block::new(0, String::from(data));
+            harness.send(Message::SubmitBlock(block)).await;
+            if i == 2 {
+                // the worker dies while the block is mined
+                assert!(harness.kill_worker());
+            }
+            let msg = tokio::time::timeout(Duration::from_secs(5), harness.recv())
+                .await
+                .expect("Node keeps mining");
+            match msg {
+                Message::MinedBlock(block) => {
+                    assert_eq!(block.id, i as u64 + 1);
+                    assert_eq!(block.data, data);
+                    assert!(block.validate_hash());
+                }
+                msg => panic!("Expected MinedBlock, but got: {:?}", msg),
+            }
+        }
+
+        harness.shutdown();
+        assert!(handle.await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_nonce_workers() {
+        let (node, mut harness) = Node::test_harness(Chain::with_genesis("test-net"), "0");
+        let mut node = node.with_config(NodeConfig {
+            nonce_workers: Some(4),
+            ..Default::default()
+        });
+        let handle = tokio::task::spawn(async move { node.run().await });
+
+        for (i, data) in ["First", "Second", "Third"].into_iter().enumerate() {
+            let block = Block::new(0, String::from(data));
+            harness.send(Message::SubmitBlock(block)).await;
+            let msg = tokio::time::timeout(Duration::from_secs(5), harness.recv())
+                .await
+                .expect("Node mines the block");
+            match msg {
+                Message::MinedBlock(block) => {
+                    assert_eq!(block.id, i as u64 + 1);
+                    assert_eq!(block.data, data);
+                    assert!(block.validate_hash());
+                }
+                msg => panic!("Expected MinedBlock, but got: {:?}", msg),
+            }
+        }
+        // the other workers don't mine the blocks again
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(harness.rx.try_recv().is_err());
+
+        harness.shutdown();
+        assert!(handle.await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_record_replay() {
+        let name = format!("rustychain-log-{}.jsonl", rand::random::<u64>());
+        let path = std::env::temp_dir().join(name);
+
+        // the recorded session: blocks arrive while others are mined
+        let (node, mut harness) = Node::test_harness(Chain::with_genesis("test-net"), "0");
+        let mut node = node.with_config(NodeConfig {
+            record: Some(path.clone()),
+            ..Default::default()
+        });
+        let handle = tokio::task::spawn(async move {
+            node.run().await;
+            node
+        });
+        for data in ["First", "Second", "Third"] {
+            harness
+                .send(Message::SubmitBlock(Block::new(0, String::from(data))))
+                .await;
+        }
+        let block = Block::new(0, String::from("From peer"));
+        let envelope = Message::Envelope(String::from("peer"), Box::new(Message::NewBlock(block)));
+        harness.send(envelope).await;
+        harness.send(Message::BlockRequest(1)).await;
+        let mut mined = 0;
+        while mined < 4 {
+            let msg = tokio::time::timeout(Duration::from_secs(5), harness.recv())
+                .await
+                .expect("Node mines the blocks");
+            if matches!(msg, Message::MinedBlock(_)) {
+                mined += 1;
+            }
+        }
+        harness.shutdown();
+        let recorded = handle.await.unwrap().chain().clone();
+        assert_eq!(recorded.blocks.len(), 5);
+
+        let entries = MessageLog::read(&path).unwrap();
+        assert_eq!(
+            entries
+                .iter()
+                .filter(|e| e.direction == Direction::In)
+                .count(),
+            5
+        );
+        assert!(entries.windows(2).all(|w| w[0].time <= w[1].time));
+
+        // a fresh node goes through the same states
+        let (tx, _rx) = mpsc::channel(64);
+        let (_tx, rx) = mpsc::channel(1);
+        let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+        let chain = Chain::with_genesis("test-net");
+        let mut node = Node::new(chain, tx, rx, rx_cancel, String::from("0"));
+        node.replay_log(entries).await;
+
+        assert!(node.chain().status);
+        assert_eq!(node.chain().export_headers(), recorded.export_headers());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_target_block_time() {
+        let (node, mut harness) = Node::test_harness(Chain::with_genesis("test-net"), "0");
+        let mut node = node.with_config(NodeConfig {
+            target_block_time: Some(Duration::from_secs(3600)),
+            ..Default::default()
+        });
+        let handle = tokio::task::spawn(async move {
+            node.run().await;
+            node
+        });
+
+        // the worker tries about a nonce per millisecond, so only easy blocks are mined
+        let mut mined = Vec::new();
+        for data in ["First", "Second"] {
+            harness
+                .send(Message::SubmitBlock(Block::new(0, String::from(data))))
+                .await;
+            let msg = tokio::time::timeout(Duration::from_secs(5), harness.recv())
+                .await
+                .expect("Node mines the block");
+            match msg {
+                Message::MinedBlock(block) => mined.push(block),
+                msg => panic!("Expected MinedBlock, but got: {:?}", msg),
+            }
+        }
+        harness.shutdown();
+        let node = handle.await.unwrap();
+
+        // the first block has nothing to compare with, then blocks come too fast
+        assert!(mined[1].string_hash().ends_with('0'));
+        assert_eq!(node.difficulty(), "000");
+        assert_eq!(node.chain().difficulty, "000");
+    }
+
+    #[tokio::test]
+    async fn test_retention() {
+        let mut chain = Chain::with_genesis("test-net");
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        for time in [now - 7200, now - 7100, now - 10] {
+            chain.add_queue(Block::new_with_timestamp(0, String::from("Data"), time));
+            assert!(chain.try_add());
+            chain.blocks.last_mut().unwrap().update_hash();
+        }
+        let headers = chain.export_headers();
+        let (node, harness) = Node::test_harness(chain, "0");
+        let mut node = node.with_config(NodeConfig {
+            retention: Some(Duration::from_secs(3600)),
+            ..Default::default()
+        });
+        let handle = tokio::task::spawn(async move {
+            node.run().await;
+            node
+        });
+
+        // bodies are pruned on start
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        harness.shutdown();
+        let node = handle.await.unwrap();
+        let pruned: Vec<bool> = node.chain().blocks.iter().map(|b| b.pruned).collect();
+        assert_eq!(pruned, [false, true, true, false]);
+        assert_eq!(node.chain().export_headers(), headers);
+        assert_eq!(node.chain().have_errors(), None);
+    }
+
+    #[tokio::test]
+    async fn test_host_only_from_peers() {
+        let (tx_node, _rx_test) = mpsc::channel::<Message>(16);
+        let (_tx_test, rx_node) = mpsc::channel::<Message>(1);
+        let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+        let chain = Chain::with_genesis("test-net");
+        let mut node = Node::new(chain, tx_node, rx_node, rx_cancel, String::from("0"));
+
+        let mut remote = Chain::with_genesis("test-net");
+        remote.add_queue(Block::new(0, String::from("Injected")));
+        assert!(remote.try_add());
+        let mut injected = remote.blocks[1].clone();
+        while !injected.hash.ends_with(&[0]) {
+            injected.nonce += 1;
+            injected.update_hash();
+        }
+        let messages = [
+            Message::SubmitBlock(Block::new(0, String::from("Submitted"))),
+            Message::InjectBlock(injected),
+        ]
+        .map(|msg| Message::Envelope(String::from("peer"), Box::new(msg)));
+        node.replay(futures::stream::iter(messages)).await;
+
+        assert_eq!(node.chain().blocks.len(), 1);
+        assert!(node.chain().queue.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_inconsistent_remote_chain() {
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(16);
+        let (_tx_test, rx_node) = mpsc::channel::<Message>(1);
+        let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+        let chain = Chain::with_genesis("test-net");
+        let mut node = Node::new(chain, tx_node, rx_node, rx_cancel, String::from("0"));
+
+        // queued blocks of the other node are numbered after the chain
+        let mut remote = Chain::test_chain(3);
+        remote
+            .queue
+            .push_back(Block::new(42, String::from("Queued")));
+        node.replay(futures::stream::iter([Message::ChainResponce(remote)]))
+            .await;
+        assert_eq!(node.chain().blocks.len(), 4);
+        assert_eq!(node.chain().blocks[3].id, 3);
+        assert_eq!(node.chain().invariant_error(), None);
+
+        // the genesis block isn't linked, so only the state check finds it
+        let mut remote = Chain::test_chain(6);
+        remote.blocks[0].prev = [1; 32];
+        for index in 0..remote.blocks.len() {
+            if index > 0 {
+                remote.blocks[index].prev = remote.blocks[index - 1].hash;
+            }
+            remote.blocks[index].update_hash();
+        }
+        // more work than the local chain
+        remote.remine_tip(16).unwrap();
+        while rx_test.try_recv().is_ok() {}
+        node.replay(futures::stream::iter([Message::ChainResponce(remote)]))
+            .await;
+        assert_eq!(node.chain().blocks.len(), 4);
+        match rx_test.try_recv() {
+            Ok(Message::Reject { what, detail }) => {
+                assert_eq!(what, RejectKind::InvalidChain);
+                assert!(detail.contains("Genesis"));
+            }
+            msg => panic!("Expected Reject, but got: {:?}", msg),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_defer_own() {
+        let (tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (tx_node, mut rx_test) = mpsc::channel::<Message>(10);
+        let (tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        let node = Node::new(Chain::new(), tx_node, rx_node, rx_cancel, String::from("0"));
+        let mut node = node.with_config(NodeConfig {
+            defer_own: true,
+            ..Default::default()
+        });
+        let handle = tokio::task::spawn(async move { node.run().await });
+
+        // the relayed block is queued right away, once
+        let relayed = Block::new(0, String::from("Relayed"));
+        tx_test
+            .send(Message::SubmitBlock(relayed.clone()))
+            .await
+            .unwrap();
+        tx_test.send(Message::NewBlock(relayed)).await.unwrap();
+        let start = std::time::Instant::now();
+        match rx_test.recv().await.unwrap() {
+            Message::MinedBlock(block) => assert_eq!(block.data, "Relayed"),
+            msg => panic!("Expected MinedBlock, but got: {:?}", msg),
+        }
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        // nobody relays this one, so it's queued after a delay
+        let lonely = Block::new(0, String::from("Lonely"));
+        let start = std::time::Instant::now();
+        tx_test.send(Message::SubmitBlock(lonely)).await.unwrap();
+        match rx_test.recv().await.unwrap() {
+            Message::MinedBlock(block) => assert_eq!(block.data, "Lonely"),
+            msg => panic!("Expected MinedBlock, but got: {:?}", msg),
+        }
+        assert!(start.elapsed() >= Duration::from_secs(2));
+
+        tx_cancel.send(()).unwrap();
+        assert!(handle.await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_health_syncing() {
+        let (tx_test, rx_node) = mpsc::channel::<Message>(10);
+        let (tx_node, _rx_test) = mpsc::channel::<Message>(10);
+        let (tx_cancel, rx_cancel) = broadcast::channel(1);
+
+        let chain = Chain::with_genesis("test-net");
+        let node = Node::new(chain, tx_node, rx_node, rx_cancel, String::from("0"));
+        let mut node = node.with_config(NodeConfig {
+            solo: true,
+            initial_sync: Some(Duration::from_secs(60)),
+            ..Default::default()
+        });
+        let health = node.health();
+        let handle = tokio::task::spawn(async move { node.run().await });
+
+        // the genesis block is there, but the node waits for the chain
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(health.lock().unwrap().syncing);
+        assert!(!health.lock().unwrap().is_ready());
+
+        tx_test
+            .send(Message::ChainResponce(Chain::test_chain(3)))
+            .await
+            .unwrap();
+        let start = std::time::Instant::now();
+        while !health.lock().unwrap().is_ready() {
+            assert!(start.elapsed() < Duration::from_secs(5), "Node isn't ready");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        tx_cancel.send(()).unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_inconsistent_tip() {
+        let (tx_node, _rx_test) = mpsc::channel::<Message>(16);
+        let (_tx_test, rx_node) = mpsc::channel::<Message>(1);
+        let (_tx_cancel, rx_cancel) = broadcast::channel(1);
+        let mut chain = Chain::test_chain(2);
+        chain.blocks[1].data = String::from("Changed"); // the hash is stale now
+        let mut node = Node::new(chain, tx_node, rx_node, rx_cancel, String::from("0"));
+
+        // nothing is mined on top of the stale hash
+        let messages = [Message::NewBlock(Block::new(0, String::from("Next")))];
+        node.replay(futures::stream::iter(messages)).await;
+        assert_eq!(node.chain().blocks.len(), 2);
+        assert_eq!(node.chain().queue.len(), 1);
+    }
 }
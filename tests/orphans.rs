@@ -0,0 +1,63 @@
+#[cfg(test)]
+mod orphans_tests {
+    use rustychain::{Chain, OrphanPool};
+
+    #[test]
+    fn test_forward_window() {
+        let full = Chain::test_chain(9);
+        let mut chain = Chain::test_chain(2);
+        let mut orphans = OrphanPool::new(8);
+
+        for block in full.blocks[5..=8].iter() {
+            assert!(orphans.insert(block.clone(), &chain));
+        }
+        // the same id, a block behind the tip and a block beyond the window aren't kept
+        assert!(!orphans.insert(full.blocks[5].clone(), &chain));
+        assert!(!orphans.insert(full.blocks[1].clone(), &chain));
+        let mut far = full.blocks[8].clone();
+        far.id = 10;
+        far.update_hash();
+        assert!(!orphans.insert(far, &chain));
+        assert_eq!(orphans.ids(), [5, 6, 7, 8]);
+
+        // the gap isn't filled yet
+        assert!(orphans.connect(&mut chain).is_empty());
+        assert_eq!(orphans.len(), 4);
+
+        for block in full.blocks[2..=4].iter() {
+            chain.append_block(block.clone()).unwrap();
+        }
+        let connected = orphans.connect(&mut chain);
+        assert_eq!(
+            connected.iter().map(|b| b.id).collect::<Vec<_>>(),
+            [5, 6, 7, 8]
+        );
+        assert!(orphans.is_empty());
+        assert_eq!(chain.export_headers(), full.export_headers());
+    }
+
+    #[test]
+    fn test_connect_invalid() {
+        let full = Chain::test_chain(5);
+        let mut chain = Chain::test_chain(2);
+        let mut orphans = OrphanPool::new(4);
+
+        // a block from another chain doesn't attach, so the blocks after it wait
+        let other = Chain::with_genesis("other-net");
+        let mut fork = full.blocks[2].clone();
+        fork.prev = other.blocks[0].hash;
+        fork.update_hash();
+        assert!(orphans.insert(fork, &chain));
+        assert!(orphans.insert(full.blocks[3].clone(), &chain));
+        assert!(orphans.connect(&mut chain).is_empty());
+        assert_eq!(orphans.ids(), [3]);
+
+        // nothing is appended while the chain is busy
+        chain.append_block(full.blocks[2].clone()).unwrap();
+        chain.status = false;
+        assert!(orphans.connect(&mut chain).is_empty());
+        chain.status = true;
+        assert_eq!(orphans.connect(&mut chain).len(), 1);
+        assert_eq!(chain.blocks.len(), 4);
+    }
+}
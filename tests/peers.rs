@@ -0,0 +1,32 @@
+#[cfg(test)]
+mod peers_tests {
+    use libp2p::Multiaddr;
+    use rustychain::PeerBook;
+
+    #[test]
+    fn test_peer_book() {
+        let name = format!("rustychain-peers-{}.txt", rand::random::<u64>());
+        let path = std::env::temp_dir().join(name);
+        let peer: Multiaddr = "/ip4/10.0.0.1/tcp/4001".parse().unwrap();
+        let discovered: Multiaddr = "/ip4/192.168.0.7/tcp/36121".parse().unwrap();
+
+        // the first start: no file yet
+        let mut book = PeerBook::load(&path).unwrap();
+        assert!(book.addrs().is_empty());
+        assert!(book.add(peer.clone()));
+        assert!(book.add(discovered.clone()));
+        assert!(!book.add(peer.clone()));
+        book.save().unwrap();
+
+        // the next start dials the saved peers
+        let book = PeerBook::load(&path).unwrap();
+        assert_eq!(book.addrs(), [peer, discovered]);
+
+        // garbage is skipped
+        std::fs::write(&path, "not an address\n/ip4/10.0.0.2/tcp/1\n\n").unwrap();
+        let book = PeerBook::load(&path).unwrap();
+        assert_eq!(book.addrs().len(), 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,202 @@
+#[cfg(test)]
+mod policy_tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use rustychain::{Block, Chain, ChainError, ValidationPolicy};
+
+    /// Re-links the blocks after `id` to the changed block, keeping the chain valid otherwise.
+    fn relink(chain: &mut Chain, id: usize) {
+        for i in id + 1..chain.blocks.len() {
+            chain.blocks[i].prev = chain.blocks[i - 1].hash;
+            chain.blocks[i].update_hash();
+        }
+    }
+
+    #[test]
+    fn test_linkage() {
+        let mut chain = Chain::test_chain(4);
+        chain.blocks[2].prev = [0u8; 32];
+        chain.blocks[2].update_hash();
+        relink(&mut chain, 2);
+
+        let policy = ValidationPolicy::default();
+        assert_eq!(
+            chain.verify_with_policy(&policy),
+            Err(ChainError::InvalidPrev(2))
+        );
+        let policy = ValidationPolicy {
+            linkage: false,
+            ..Default::default()
+        };
+        assert_eq!(chain.verify_with_policy(&policy), Ok(()));
+
+        chain.blocks[3].id = 7;
+        chain.blocks[3].update_hash();
+        let policy = ValidationPolicy::default();
+        assert_eq!(
+            chain.verify_with_policy(&policy),
+            Err(ChainError::InvalidPrev(2))
+        );
+        chain.blocks[2] = Chain::test_chain(4).blocks[2].clone();
+        relink(&mut chain, 2);
+        assert_eq!(
+            chain.verify_with_policy(&policy),
+            Err(ChainError::WrongId {
+                expected: 3,
+                found: 7
+            })
+        );
+    }
+
+    #[test]
+    fn test_proof_of_work() {
+        let mut chain = Chain::test_chain(4);
+        chain.blocks[2].data = String::from("Tampered");
+
+        let policy = ValidationPolicy::default();
+        assert_eq!(
+            chain.verify_with_policy(&policy),
+            Err(ChainError::InvalidHash(2))
+        );
+        let policy = ValidationPolicy {
+            proof_of_work: false,
+            ..Default::default()
+        };
+        assert_eq!(chain.verify_with_policy(&policy), Ok(()));
+    }
+
+    #[test]
+    fn test_genesis() {
+        let chain = Chain::test_chain(3);
+        assert_eq!(
+            chain.verify_with_policy(&ValidationPolicy::default()),
+            Ok(())
+        );
+
+        let mut policy = ValidationPolicy {
+            genesis: Some(Block::genesis("other-net").hash),
+            ..Default::default()
+        };
+        assert_eq!(
+            chain.verify_with_policy(&policy),
+            Err(ChainError::GenesisMismatch)
+        );
+        policy.genesis = Some(Block::genesis("test-chain").hash);
+        assert_eq!(chain.verify_with_policy(&policy), Ok(()));
+    }
+
+    #[test]
+    fn test_checkpoints() {
+        let chain = Chain::test_chain(4);
+        let mut policy = ValidationPolicy::default();
+        policy.checkpoints.insert(2, chain.blocks[2].hash);
+        // the chain doesn't reach this one yet
+        policy.checkpoints.insert(10, [1u8; 32]);
+        assert_eq!(chain.verify_with_policy(&policy), Ok(()));
+
+        policy.checkpoints.insert(3, [1u8; 32]);
+        assert_eq!(
+            chain.verify_with_policy(&policy),
+            Err(ChainError::CheckpointMismatch(3))
+        );
+    }
+
+    #[test]
+    fn test_time_drift() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let mut chain = Chain::test_chain(4);
+        chain.blocks[2].timestamp = now + 60 * 60;
+        chain.blocks[2].update_hash();
+        relink(&mut chain, 2);
+        assert_eq!(
+            chain.verify_with_policy(&ValidationPolicy::default()),
+            Ok(())
+        );
+
+        let mut policy = ValidationPolicy {
+            max_time_drift: Some(60),
+            ..Default::default()
+        };
+        assert_eq!(
+            chain.verify_with_policy(&policy),
+            Err(ChainError::FutureTimestamp(2))
+        );
+        policy.max_time_drift = Some(2 * 60 * 60);
+        assert_eq!(chain.verify_with_policy(&policy), Ok(()));
+    }
+
+    #[test]
+    fn test_difficulty() {
+        let mut chain = Chain::test_chain(3);
+        // more work than the test chain has
+        chain.difficulty = String::from("0000000000");
+        assert_eq!(
+            chain.verify_with_policy(&ValidationPolicy::default()),
+            Ok(())
+        );
+
+        let policy = ValidationPolicy {
+            recorded_difficulty: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            chain.verify_with_policy(&policy),
+            Err(ChainError::LowDifficulty(1))
+        );
+        chain.difficulty = String::new();
+        assert_eq!(chain.verify_with_policy(&policy), Ok(()));
+
+        let mut policy = ValidationPolicy {
+            min_difficulty_bits: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(chain.verify_with_policy(&policy), Ok(()));
+        policy.min_difficulty_bits = Some(64);
+        assert_eq!(
+            chain.verify_with_policy(&policy),
+            Err(ChainError::LowDifficulty(1))
+        );
+    }
+
+    #[test]
+    fn test_order() {
+        let mut chain = Chain::test_chain(4);
+        chain.blocks[1].data = String::from("Tampered");
+        chain.blocks[3].prev = [0u8; 32];
+        chain.blocks[3].update_hash();
+
+        // the linkage is checked before hashes
+        let mut policy = ValidationPolicy::default();
+        assert_eq!(
+            chain.verify_with_policy(&policy),
+            Err(ChainError::InvalidPrev(3))
+        );
+        policy.genesis = Some([0u8; 32]);
+        assert_eq!(
+            chain.verify_with_policy(&policy),
+            Err(ChainError::GenesisMismatch)
+        );
+        policy.genesis = None;
+        policy.linkage = false;
+        assert_eq!(
+            chain.verify_with_policy(&policy),
+            Err(ChainError::InvalidHash(1))
+        );
+    }
+
+    #[test]
+    fn test_busy_tip() {
+        let mut chain = Chain::test_chain(3);
+        chain.add_queue(Block::new(0, String::from("Mining")));
+        assert!(chain.try_add());
+        chain.status = false;
+
+        let mut policy = ValidationPolicy::default();
+        policy.checkpoints.insert(3, [1u8; 32]);
+        assert_eq!(chain.verify_with_policy(&policy), Ok(()));
+        assert_eq!(chain.verify_with_policy_in(&policy, 2..10), Ok(()));
+    }
+}
@@ -6,25 +6,155 @@ mod test {
     use rustychain::{Block, Chain, Message};
     use std::collections::hash_map::DefaultHasher;
     use std::collections::HashMap;
+    use std::collections::HashSet;
     use std::error::Error;
     use std::hash::{Hash, Hasher};
-    use std::io::Write;
-    use std::process::{Command, Stdio};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::process::{Child, Command, ExitStatus, Stdio};
+    use std::sync::{mpsc, Arc, Mutex};
     use std::thread;
-    use std::time::Duration;
-    use tokio::time::sleep;
+    use std::time::{Duration, Instant};
+    use tokio::time::timeout;
+
+    /// Line logged by a node when it's ready to get transactions.
+    const READY_LINE: &str = "Now you can enter transactions via STDIN";
+
+    /// Reads a number from the environment variable or returns the default one.
+    fn env_or(name: &str, default: u64) -> u64 {
+        std::env::var(name)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default)
+    }
+
+    /// Lines of a child's output, collected in background to show them if the test fails.
+    struct Logs {
+        lines: Arc<Mutex<Vec<String>>>,
+        rx: mpsc::Receiver<String>,
+    }
+
+    impl Logs {
+        fn collect(stream: impl Read + Send + 'static) -> Self {
+            let lines = Arc::new(Mutex::new(vec![]));
+            let (tx, rx) = mpsc::channel();
+            let shared = lines.clone();
+            thread::spawn(move || {
+                for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                    shared.lock().unwrap().push(line.clone());
+                    let _ = tx.send(line);
+                }
+            });
+            Self { lines, rx }
+        }
+
+        /// Waits for a line containing the pattern. Returns false on timeout or closed stderr.
+        fn wait_for(&self, pattern: &str, timeout: Duration) -> bool {
+            let deadline = Instant::now() + timeout;
+            loop {
+                let left = deadline.saturating_duration_since(Instant::now());
+                match self.rx.recv_timeout(left) {
+                    Ok(line) if line.contains(pattern) => return true,
+                    Ok(_) => {}
+                    Err(_) => return false,
+                }
+            }
+        }
+
+        fn dump(&self) -> String {
+            self.lines.lock().unwrap().join("\n")
+        }
+    }
+
+    /// Waits for the child to exit, killing it on timeout.
+    fn wait_timeout(child: &mut Child, timeout: Duration) -> Option<ExitStatus> {
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            if let Some(status) = child.try_wait().expect("failed to wait for child process") {
+                return Some(status);
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        child.kill().ok();
+        child.wait().ok();
+        None
+    }
+
+    /// Runs `cargo build` up to `retries` times, each attempt is limited by `timeout`.
+    fn compile(retries: u64, timeout: Duration) -> Result<(), String> {
+        let mut errors = String::new();
+        for attempt in 1..=retries {
+            println!("Compile program, attempt {attempt}/{retries}");
+            let mut build = Command::new("cargo")
+                .arg("build")
+                .stderr(Stdio::piped())
+                .spawn()
+                .map_err(|e| format!("Can't start compilation on program: {e}"))?;
+            let logs = Logs::collect(build.stderr.take().expect("Stderr is piped"));
+            match wait_timeout(&mut build, timeout) {
+                Some(status) if status.success() => return Ok(()),
+                Some(status) => errors = format!("{status}:\n{}", logs.dump()),
+                None => errors = format!("timeout after {timeout:?}:\n{}", logs.dump()),
+            }
+        }
+        Err(errors)
+    }
+
+    /// Node process, killed if the test fails before its shutdown.
+    struct NodeProcess {
+        child: Child,
+        logs: Logs,
+        output: Logs,
+    }
+
+    impl NodeProcess {
+        fn spawn() -> Self {
+            Self::spawn_with(&["-d", "00"])
+        }
+
+        /// Runs the node with the arguments and info logs.
+        fn spawn_with(args: &[&str]) -> Self {
+            let mut child = Command::new("cargo")
+                .arg("run")
+                .arg("--")
+                .args(args)
+                .env("RUST_LOG", "info")
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .expect("failed to execute child process");
+            let logs = Logs::collect(child.stderr.take().expect("Stderr is piped"));
+            let output = Logs::collect(child.stdout.take().expect("Stdout is piped"));
+            Self {
+                child,
+                logs,
+                output,
+            }
+        }
+
+        /// Writes the command to stdin of the node.
+        fn command(&mut self, line: &str) {
+            let stdin = self.child.stdin.as_mut().expect("Stdin is piped");
+            writeln!(stdin, "{line}").expect("Node reads stdin");
+        }
+    }
+
+    impl Drop for NodeProcess {
+        fn drop(&mut self) {
+            self.child.kill().ok();
+        }
+    }
 
     #[tokio::test]
     async fn network_test() -> Result<(), Box<dyn Error>> {
-        // Compile program to run it in test
-        println!("Compile program");
-        let mut build = Command::new("cargo")
-            .arg("build")
-            .spawn()
-            .expect("Can't start compilation on program");
+        let retries = env_or("RUSTYCHAIN_TEST_COMPILE_RETRIES", 3);
+        let compile_timeout = Duration::from_secs(env_or("RUSTYCHAIN_TEST_COMPILE_TIMEOUT", 600));
+        let ready_timeout = Duration::from_secs(env_or("RUSTYCHAIN_TEST_READY_TIMEOUT", 60));
 
-        let status = build.wait().expect("failed to wait for child process");
-        assert!(status.success(), "Can't compile program!!!");
+        // Compile program to run it in test
+        if let Err(e) = compile(retries, compile_timeout) {
+            panic!("Can't compile program!!! {e}");
+        }
 
         // Creating mock
         println!("Mock starting...");
@@ -68,30 +198,75 @@ mod test {
         };
 
         swarm.listen_on("/ip4/0.0.0.0/tcp/0".parse()?)?;
-        println!("Mock started");
         // wait for network up
-        sleep(Duration::from_secs(5)).await;
+        timeout(ready_timeout, async {
+            while !matches!(
+                swarm.select_next_some().await,
+                SwarmEvent::NewListenAddr { .. }
+            ) {}
+        })
+        .await
+        .expect("Mock doesn't listen");
+        println!("Mock started");
         println!("Starting 3 nodes!");
 
         let mut children = Vec::new();
         for _ in 0..3 {
-            let child = Command::new("cargo")
-                .arg("run")
-                .arg("--")
-                .arg("-d")
-                .arg("00")
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .spawn()
-                .expect("failed to execute child process");
-            children.push(child);
+            children.push(NodeProcess::spawn());
+        }
+        for child in children.iter() {
+            assert!(
+                child.logs.wait_for(READY_LINE, ready_timeout),
+                "Node isn't ready:\n{}",
+                child.logs.dump()
+            );
         }
 
-        // to get up all childs
-        sleep(Duration::from_secs(5)).await;
+        // to get all childs into the topic
+        let mut subscribed = HashSet::new();
+        let waiting = timeout(ready_timeout, async {
+            while subscribed.len() < children.len() {
+                match swarm.select_next_some().await {
+                    SwarmEvent::Behaviour(MyBehaviourEvent::Mdns(mdns::Event::Discovered(
+                        list,
+                    ))) => {
+                        for (peer_id, _multiaddr) in list {
+                            swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer_id);
+                        }
+                    }
+                    SwarmEvent::Behaviour(MyBehaviourEvent::Gossipsub(
+                        gossipsub::Event::Subscribed { peer_id, .. },
+                    )) => {
+                        subscribed.insert(peer_id);
+                    }
+                    _ => {}
+                }
+            }
+        })
+        .await;
+        if waiting.is_err() {
+            let logs: Vec<String> = children.iter().map(|c| c.logs.dump()).collect();
+            panic!(
+                "Only {} nodes subscribed:\n{}",
+                subscribed.len(),
+                logs.join("\n\n")
+            );
+        }
 
         println!("3 nodes started!");
 
+        // node lists the mock in its peers
+        let mock = local_peer_id.to_string();
+        let listed = (0..5).any(|_| {
+            children[0].command("peers");
+            children[0].output.wait_for(&mock, Duration::from_secs(2))
+        });
+        assert!(
+            listed,
+            "Mock isn't listed in peers:\n{}",
+            children[0].output.dump()
+        );
+
         // test data
         let all_data = vec!["Genesis", "First", "Second", "Third", "Fourth"];
         let mut data = all_data.clone();
@@ -172,15 +347,19 @@ mod test {
 
         for mut child in children {
             println!("Shutdown child!");
-            let stdin = child.stdin.take().unwrap();
+            let stdin = child.child.stdin.take().unwrap();
 
             let writer_thread = thread::spawn(move || {
                 let mut stdin_writer = stdin;
                 writeln!(stdin_writer, "exit").unwrap();
             });
 
-            let status = child.wait().expect("failed to wait for child process");
-            assert!(status.success());
+            let status = wait_timeout(&mut child.child, ready_timeout);
+            assert!(
+                status.is_some_and(|s| s.success()),
+                "Node exited with {status:?}:\n{}",
+                child.logs.dump()
+            );
             writer_thread.join().unwrap();
         }
 
@@ -197,4 +376,76 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn mine_blocks_test() {
+        let retries = env_or("RUSTYCHAIN_TEST_COMPILE_RETRIES", 3);
+        let compile_timeout = Duration::from_secs(env_or("RUSTYCHAIN_TEST_COMPILE_TIMEOUT", 600));
+        let ready_timeout = Duration::from_secs(env_or("RUSTYCHAIN_TEST_READY_TIMEOUT", 60));
+        if let Err(e) = compile(retries, compile_timeout) {
+            panic!("Can't compile program!!! {e}");
+        }
+
+        let name = format!("rustychain-mine-blocks-{}.json", rand::random::<u64>());
+        let path = std::env::temp_dir().join(name);
+        // stdin stays open, the node exits by itself
+        let mut node = NodeProcess::spawn_with(&[
+            "--difficulty",
+            "00",
+            "--mine-blocks",
+            "3",
+            "--solo-mining",
+            "--chain-file",
+            path.to_str().expect("Temp path is UTF-8"),
+        ]);
+
+        let status = wait_timeout(&mut node.child, ready_timeout);
+        assert!(
+            status.is_some_and(|s| s.success()),
+            "Node exited with {status:?}:\n{}",
+            node.logs.dump()
+        );
+        let output = node.output.dump();
+        assert!(output.contains("Mined 3 blocks"), "{}", output);
+
+        let chain = Chain::load_from_file(&path).expect("Chain is saved");
+        std::fs::remove_file(&path).ok();
+        assert_eq!(chain.blocks.len(), 4);
+        assert_eq!(chain.have_errors(), None);
+        for block in &chain.blocks[1..] {
+            assert!(block.string_hash().ends_with("00"));
+        }
+    }
+
+    #[test]
+    fn quiet_test() {
+        let retries = env_or("RUSTYCHAIN_TEST_COMPILE_RETRIES", 3);
+        let compile_timeout = Duration::from_secs(env_or("RUSTYCHAIN_TEST_COMPILE_TIMEOUT", 600));
+        let ready_timeout = Duration::from_secs(env_or("RUSTYCHAIN_TEST_READY_TIMEOUT", 60));
+        if let Err(e) = compile(retries, compile_timeout) {
+            panic!("Can't compile program!!! {e}");
+        }
+
+        // RUST_LOG=info is overridden
+        let mut node = NodeProcess::spawn_with(&[
+            "-d",
+            "00",
+            "--quiet",
+            "--mine-blocks",
+            "1",
+            "--solo-mining",
+        ]);
+        let status = wait_timeout(&mut node.child, ready_timeout);
+        assert!(
+            status.is_some_and(|s| s.success()),
+            "Node exited with {status:?}:\n{}",
+            node.logs.dump()
+        );
+        // the host output is kept
+        let output = node.output.dump();
+        assert!(output.contains("Mined 1 blocks"), "{}", output);
+        let logs = node.logs.dump();
+        assert!(!logs.contains(READY_LINE), "{}", logs);
+        assert!(!logs.contains(" INFO "), "{}", logs);
+    }
 }